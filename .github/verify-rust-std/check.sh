@@ -25,4 +25,3 @@ popd
 pushd rapx-verify-rust-std/library/core
 cargo clean
 SAFETY_TOOL=safety-tool-rfl cargo safety-tool
-rm ../data.sqlite3
@@ -3,4 +3,4 @@
 #[cfg(feature = "std")]
 pub use safety_parser;
 
-pub use safety_macro::{checked, requires};
+pub use safety_macro::{checked, ensures, invariant, r#ref, requires, resolves};
@@ -1,45 +1,97 @@
 use proc_macro::TokenStream;
 use safety_parser::{
-    configuration::env::config_exists, proc_macro2::TokenStream as TokenStream2, quote::quote,
-    safety::SafetyAttrArgs as AttrArgs, split_attrs::split_attrs_and_rest, syn,
+    configuration::env::config_exists,
+    proc_macro2::TokenStream as TokenStream2,
+    quote::quote,
+    safety::{ContractKind, SafetyAttrArgs as AttrArgs, pointee_in_signature},
+    split_attrs::split_attrs_and_rest,
+    syn,
 };
 
+#[cfg(test)]
+mod tests;
+
 /// This is a shared function to annotate SPs on caller and callee.
 ///
 /// When `#[safety]` is removed, this function should be put into `#[requires]`
 /// or renamed `requires_inner`.
-fn tag(attr: TokenStream, item: TokenStream) -> TokenStream {
+fn tag(attr: TokenStream2, item: TokenStream2, kind: ContractKind) -> TokenStream2 {
     let mut ts = TokenStream2::new();
 
     // add registered tool attr
-    let tool_attr = {
-        let attr = TokenStream2::from(attr.clone());
-        quote! { #[rapx::requires(#attr)] }
+    let tool_attr = match kind {
+        ContractKind::Requires => quote! { #[rapx::requires(#attr)] },
+        ContractKind::Ensures => quote! { #[rapx::ensures(#attr)] },
+        ContractKind::Invariant => quote! { #[rapx::invariant(#attr)] },
     };
     ts.extend(tool_attr);
 
-    let input = split_attrs_and_rest(item.into());
+    let input = split_attrs_and_rest(item);
     if !input.gen_doc {
         // no need to generate docs on expressions
         ts.extend(input.attrs);
         ts.extend(input.rest);
-        return ts.into();
+        return ts;
     }
 
     // push doc attrs first
     ts.extend(input.attrs);
 
-    let attr_args: AttrArgs = syn::parse(attr).unwrap();
-    // push generated doc if available
+    let mut attr_args: AttrArgs = syn::parse2(attr).unwrap();
+    let sig = signature(&input.rest);
+    // errors are emitted after the item to not take attributes on the item
+    let mut errors = TokenStream2::new();
+    // push generated doc and contracts if available
     if config_exists() {
+        if let Some(sig) = &sig {
+            for tag in attr_args.args.iter_mut().flat_map(|arg| arg.tags.iter_mut()) {
+                tag.fill_type_args(|arg| pointee_in_signature(sig, arg));
+            }
+        }
         for tag in &attr_args.args {
             ts.extend(tag.gen_doc());
         }
+        for tag in &attr_args.args {
+            match tag.gen_contracts(kind) {
+                Ok(contracts) => ts.extend(contracts),
+                Err(err) => errors.extend(err.to_compile_error()),
+            }
+        }
     }
 
-    // push rest tokens
-    ts.extend(input.rest);
-    ts.into()
+    // push rest tokens, with runtime checks for preconditions
+    let runtime_checks: TokenStream2 = if config_exists() && matches!(kind, ContractKind::Requires)
+    {
+        attr_args.args.iter().map(|tag| tag.gen_runtime_checks(sig.as_ref())).collect()
+    } else {
+        TokenStream2::new()
+    };
+    if runtime_checks.is_empty() {
+        ts.extend(input.rest);
+    } else {
+        ts.extend(inject_runtime_checks(input.rest, runtime_checks));
+    }
+    ts.extend(errors);
+    ts
+}
+
+/// The signature of a function item, or a trait method without a body.
+fn signature(item: &TokenStream2) -> Option<syn::Signature> {
+    if let Ok(fun) = syn::parse2::<syn::ItemFn>(item.clone()) {
+        return Some(fun.sig);
+    }
+    syn::parse2::<syn::TraitItemFn>(item.clone()).ok().map(|fun| fun.sig)
+}
+
+/// Insert runtime checks at the top of the function body. Items without a body
+/// like trait methods are left untouched.
+fn inject_runtime_checks(item: TokenStream2, checks: TokenStream2) -> TokenStream2 {
+    use syn::parse::Parser;
+
+    let Ok(mut fun) = syn::parse2::<syn::ItemFn>(item.clone()) else { return item };
+    let stmts = syn::Block::parse_within.parse2(checks).expect("Runtime checks are not statements");
+    fun.block.stmts.splice(0..0, stmts);
+    quote! { #fun }
 }
 
 /// Tag SPs on an unsafe function item, or discharge SPs on an expression.
@@ -66,7 +118,7 @@ fn tag(attr: TokenStream, item: TokenStream) -> TokenStream {
 #[proc_macro_attribute]
 #[deprecated = "Use `#[requires]` instead."]
 pub fn safety(attr: TokenStream, item: TokenStream) -> TokenStream {
-    tag(attr, item)
+    tag(attr.into(), item.into(), ContractKind::Requires).into()
 }
 
 /// Tag SPs on an unsafe function item.
@@ -84,15 +136,93 @@ pub fn safety(attr: TokenStream, item: TokenStream) -> TokenStream {
 /// #[requires { SP1 }] unsafe fn foo() {}
 /// #[requires { SP1, SP2 }] unsafe fn bar() {}
 /// ```
+///
+/// When `contract.kani` or `contract.core` is enabled in spec, tags with `kani` or
+/// `contract` templates also generate `#[cfg_attr(kani, kani::requires(...))]` or
+/// `#[core::contracts::requires(...)]` respectively.
+///
+/// Omitted trailing type args are filled with the pointee type of the first pointer arg in
+/// generated docs, e.g. `Align(p)` on `fn(p: *const T)` is rendered as `Align(p, T)`.
+///
+/// Tags with `runtime` templates insert `debug_assert!`s at the top of the function
+/// body, which are only compiled under `--cfg safety_runtime_checks`. Tags with omitted
+/// args, or with pointer args of reference or `NonNull` params, are not checked.
 #[proc_macro_attribute]
 pub fn requires(attr: TokenStream, item: TokenStream) -> TokenStream {
-    tag(attr, item)
+    tag(attr.into(), item.into(), ContractKind::Requires).into()
+}
+
+/// Tag SPs that hold after the function returns, or obligations on an unsafe trait
+/// and its methods which each `unsafe impl` must discharge through `#[checked]`.
+///
+/// Contract templates are generated as `ensures` closures on the return value `result`.
+///
+/// # Syntax Example
+///
+/// ```
+/// #![feature(stmt_expr_attributes)]
+/// #![feature(proc_macro_hygiene)]
+/// #![feature(register_tool)]
+/// #![register_tool(rapx)]
+/// # use safety_macro::{checked, ensures};
+///
+/// #[ensures { SP1 }] unsafe fn foo() -> *const u8 { std::ptr::null() }
+///
+/// #[ensures { SP1 }]
+/// unsafe trait Trait {
+///     #[ensures { SP2 }]
+///     fn method(&self);
+/// }
+///
+/// struct S;
+/// #[checked { SP1 }]
+/// unsafe impl Trait for S {
+///     #[checked { SP2 }]
+///     fn method(&self) {}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn ensures(attr: TokenStream, item: TokenStream) -> TokenStream {
+    tag(attr.into(), item.into(), ContractKind::Ensures).into()
+}
+
+/// Tag type invariants on a struct, enum, or union.
+///
+/// Constructing the type through a struct literal, or writing its fields outside
+/// its own inherent impl, must discharge the invariants through `#[checked]`.
+///
+/// # Syntax Example
+///
+/// ```
+/// #![feature(stmt_expr_attributes)]
+/// #![feature(proc_macro_hygiene)]
+/// #![feature(register_tool)]
+/// #![register_tool(rapx)]
+/// # use safety_macro::{checked, invariant};
+///
+/// #[invariant { SP1 }]
+/// struct S { len: usize }
+///
+/// impl S {
+///     fn new() -> Self {
+///         #[checked { SP1 }]
+///         let s = S { len: 0 };
+///         s
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn invariant(attr: TokenStream, item: TokenStream) -> TokenStream {
+    tag(attr.into(), item.into(), ContractKind::Invariant).into()
 }
 
 /// Discharge SPs.
 ///
 /// NOTE: there is no check on whether the annotated is an expression or not.
 ///
+/// The discharge can be named as an anchor through `name = anchor;` in front of SPs,
+/// which is shared by other sites through `#[ref(anchor)]`.
+///
 /// # Syntax Example
 ///
 /// ```
@@ -111,6 +241,9 @@ pub fn requires(attr: TokenStream, item: TokenStream) -> TokenStream {
 /// #[checked { SP1: "reason" }] unsafe { foo() };
 /// #[checked { SP1, SP2: "shared reason" }] unsafe { bar() };
 /// #[checked { SP1: "reason1"; SP2: "reason2" }] unsafe { bar() };
+///
+/// // Named anchor:
+/// #[checked { name = anchor; SP1, SP2: "shared reason" }] unsafe { bar() };
 /// ```
 #[proc_macro_attribute]
 pub fn checked(attr: TokenStream, item: TokenStream) -> TokenStream {
@@ -127,3 +260,72 @@ pub fn checked(attr: TokenStream, item: TokenStream) -> TokenStream {
     ts.extend(item);
     ts
 }
+
+/// Resolve hazards opened by earlier unsafe calls. Hazard tags with `resolved_by` in spec
+/// must be resolved on every path before the caller returns.
+///
+/// # Syntax Example
+///
+/// ```
+/// #![feature(stmt_expr_attributes)]
+/// #![feature(proc_macro_hygiene)]
+/// #![feature(register_tool)]
+/// #![register_tool(rapx)]
+/// # use safety_macro::{checked, requires, resolves};
+///
+/// #[requires { Alias }] unsafe fn as_mut(p: *mut u8) -> &'static mut u8 { unsafe { &mut *p } }
+///
+/// fn f(p: *mut u8) {
+///     #[checked { Alias }]
+///     let r = unsafe { as_mut(p) };
+///     #[resolves { Alias: "r is dead from now on" }]
+///     let _ = r;
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn resolves(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut ts = TokenStream::new();
+
+    let tool_attr: TokenStream = {
+        let attr = TokenStream2::from(attr);
+        quote! { #[rapx::resolves(#attr)] }.into()
+    };
+    ts.extend(tool_attr);
+
+    ts.extend(item);
+    ts
+}
+
+/// Refer to a named anchor in `#[checked { name = anchor; ... }]` to discharge the
+/// same SPs with the same justification.
+///
+/// `ref` is a keyword, so the raw identifier is required in the path.
+///
+/// # Syntax Example
+///
+/// ```
+/// #![feature(stmt_expr_attributes)]
+/// #![feature(proc_macro_hygiene)]
+/// #![feature(register_tool)]
+/// #![register_tool(rapx)]
+/// # use safety_macro::{checked, requires};
+/// # use safety_macro as safety;
+///
+/// #[requires { SP1 }] unsafe fn foo() {}
+///
+/// #[checked { name = anchor; SP1: "reason" }] unsafe { foo() };
+/// #[safety::r#ref(anchor)] unsafe { foo() };
+/// ```
+#[proc_macro_attribute]
+pub fn r#ref(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut ts = TokenStream::new();
+
+    let tool_attr: TokenStream = {
+        let attr = TokenStream2::from(attr);
+        quote! { #[rapx::r#ref(#attr)] }.into()
+    };
+    ts.extend(tool_attr);
+
+    ts.extend(item);
+    ts
+}
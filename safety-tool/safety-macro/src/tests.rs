@@ -0,0 +1,77 @@
+use super::*;
+use expect_test::{ExpectFile, expect_file};
+use std::sync::Once;
+
+/// Read tags with contract templates from the test spec.
+fn init() {
+    static INIT: Once = Once::new();
+    // SAFETY: no env var is read until the spec is set up by the first test.
+    INIT.call_once(|| unsafe {
+        std::env::set_var("SP_FILE", concat!(env!("CARGO_MANIFEST_DIR"), "/tests/sp-test.toml"));
+    });
+}
+
+fn expand(kind: ContractKind, attr: &str, item: &str) -> String {
+    let ts = tag(attr.parse().unwrap(), item.parse().unwrap(), kind);
+    prettyplease::unparse(&syn::parse2(ts).unwrap())
+}
+
+/// Expand each `(kind, attr, item)` and compare them with the snapshot.
+fn check(cases: &[(ContractKind, &str, &str)], expect: ExpectFile) {
+    init();
+    let expanded: Vec<_> =
+        cases.iter().map(|(kind, attr, item)| expand(*kind, attr, item)).collect();
+    expect.assert_eq(&expanded.join("\n"));
+}
+
+#[test]
+fn contracts() {
+    use ContractKind::*;
+    check(
+        &[
+            (Requires, "NonNull(p), Align(p, T)", "unsafe fn f<T>(p: *const T) {}"),
+            // the type arg is filled from the signature
+            (Requires, "Align(p)", "unsafe fn f(p: *mut u8) {}"),
+            // no contract for omitted args
+            (Requires, "Align", "unsafe fn f(p: *mut u8) {}"),
+            (Requires, "any { NonNull(p), Align(p, u8) }", "unsafe fn f(p: *mut u8) {}"),
+            (Ensures, "NonNull(result)", "unsafe fn f() -> *const u8 { loop {} }"),
+            (Invariant, "NonNull(self.p)", "struct S { p: *const u8 }"),
+        ],
+        expect_file!["../tests/snapshots/contracts.txt"],
+    );
+}
+
+#[test]
+fn invalid_contract_template() {
+    check(
+        &[
+            (ContractKind::Requires, "Invalid(p)", "unsafe fn f(p: *const u8) {}"),
+            // a block brace, and an unknown name
+            (ContractKind::Requires, "Block(p)", "unsafe fn f(p: *const u8) {}"),
+        ],
+        expect_file!["../tests/snapshots/invalid_contract_template.txt"],
+    );
+}
+
+#[test]
+fn runtime_checks() {
+    use ContractKind::*;
+    check(
+        &[
+            (Requires, "Align(p, T)", "unsafe fn f<T>(p: *const T) {}"),
+            // omitted args are skipped rather than rendered as nothing
+            (Requires, "Align, NonNull", "unsafe fn f(p: *const u8) {}"),
+            // references and NonNull are never null
+            (Requires, "NonNull(p), Align(p, u8)", "unsafe fn f(p: &u8) {}"),
+            (Requires, "NonNull(p)", "unsafe fn f(p: NonNull<u8>) {}"),
+            (Requires, "any { NonNull(p), Align(p, u8) }", "unsafe fn f(p: &u8) {}"),
+            (Requires, "any { NonNull(p), Align }", "unsafe fn f(p: *const u8) {}"),
+            (Requires, "ValidNum(n, [0, isize::MAX]), ValidNum(shift, 0..64)", "unsafe fn f() {}"),
+            (Requires, r#"NonZero(n, "'a")"#, "unsafe fn f<'a>(n: usize) {}"),
+            // no checks on postconditions
+            (Ensures, "NonNull(result)", "unsafe fn f() -> *const u8 { loop {} }"),
+        ],
+        expect_file!["../tests/snapshots/runtime_checks.txt"],
+    );
+}
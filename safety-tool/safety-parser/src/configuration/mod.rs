@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 use std::{fs, sync::LazyLock};
 
 pub mod env;
+mod overlay;
+pub use overlay::{OverlayOption, OverlayTags, read_overlay_file};
 
 pub type Str = Box<str>;
 pub type OptStr = Option<Box<str>>;
@@ -11,9 +13,16 @@ pub type OptStr = Option<Box<str>>;
 #[derive(Debug, Deserialize)]
 pub struct Configuration {
     pub package: Option<Package>,
+    #[serde(default)]
     pub tag: IndexMap<Str, Tag>,
     #[serde(default)]
     pub doc: GenDocOption,
+    #[serde(default)]
+    pub contract: GenContractOption,
+    #[serde(default)]
+    pub provenance: ProvenanceOption,
+    #[serde(default)]
+    pub overlay: OverlayOption,
 }
 
 impl Configuration {
@@ -38,11 +47,48 @@ pub struct Package {
 pub struct Tag {
     #[serde(default)]
     pub args: Box<[Str]>,
+    /// Kinds of args in order, e.g. `[ "pointer", "type" ]` for `args = [ "p", "T" ]`.
+    /// Args without a kind are values.
+    #[serde(default)]
+    pub arg_kinds: Box<[ArgKind]>,
     pub desc: OptStr,
     pub expr: OptStr,
     #[serde(default = "default_types")]
     pub types: Box<[TagType]>,
     pub url: OptStr,
+    /// Kani contract template such as `kani::mem::can_dereference({p})`.
+    pub kani: OptStr,
+    /// `core::contracts` template which must be a plain Rust expression.
+    pub contract: OptStr,
+    /// Cheap runtime check template such as `!{p}.is_null()`.
+    pub runtime: OptStr,
+    /// How a hazard is resolved: tag names in `#[resolves]`, or paths of resolving functions.
+    /// Hazards with this field are required to be resolved before the caller returns.
+    #[serde(default)]
+    pub resolved_by: Box<[Str]>,
+    /// Tags waived when the option holds at the call site, which can name option args in
+    /// braces like `{property}`.
+    #[serde(default)]
+    pub waives: Box<[Str]>,
+    /// How the tag is decided at the call site through constant evaluation, if decidable.
+    pub eval: Option<EvalKind>,
+}
+
+/// Evaluator of a tag against args of the call.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EvalKind {
+    /// The first arg is an integer expression, which must lie within the second arg, a closed
+    /// range like `[0, isize::MAX]` or a Rust range like `0..len`.
+    Range,
+}
+
+impl EvalKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EvalKind::Range => "range",
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize, Default, PartialEq, Eq)]
@@ -52,6 +98,9 @@ pub enum TagType {
     Precond,
     Hazard,
     Option,
+    /// Postcondition that holds after a call, or an obligation on implementations of
+    /// an unsafe trait.
+    Postcond,
 }
 
 impl TagType {
@@ -60,7 +109,8 @@ impl TagType {
             "precond" => Self::Precond,
             "hazard" => Self::Hazard,
             "option" => Self::Option,
-            _ => panic!("Only support: precond, hazard, and option."),
+            "postcond" => Self::Postcond,
+            _ => panic!("Only support: precond, hazard, option, and postcond."),
         }
     }
 
@@ -69,10 +119,31 @@ impl TagType {
             TagType::Precond => "precond",
             TagType::Hazard => "Hazard",
             TagType::Option => "option",
+            TagType::Postcond => "postcond",
         }
     }
 }
 
+/// Kind of a tag arg, declared through `arg_kinds` in spec.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArgKind {
+    /// A raw pointer or `NonNull`.
+    Pointer,
+    /// A type in scope.
+    Type,
+    /// An arbitrary expression.
+    #[default]
+    Value,
+}
+
+impl Tag {
+    /// Kind of the arg at the index.
+    pub fn arg_kind(&self, idx: usize) -> ArgKind {
+        self.arg_kinds.get(idx).copied().unwrap_or_default()
+    }
+}
+
 /// If types field doesn't exist, default to Precond.
 fn default_types() -> Box<[TagType]> {
     Box::new([TagType::Precond])
@@ -99,6 +170,64 @@ impl GenDocOption {
     }
 }
 
+/// Verifier contracts generated alongside `#[rapx::requires]` and `#[rapx::ensures]`.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Default)]
+pub struct GenContractOption {
+    /// Generate `#[cfg_attr(kani, kani::requires(...))]` from `kani` field of tags.
+    #[serde(default)]
+    pub kani: bool,
+    /// Generate `#[core::contracts::requires(...)]` from `contract` field of tags.
+    #[serde(default)]
+    pub core: bool,
+}
+
+impl GenContractOption {
+    fn merge(&mut self, other: &Self) {
+        if other.kani {
+            self.kani = true;
+        }
+        if other.core {
+            self.core = true;
+        }
+    }
+}
+
+/// Auto-discharge of tags on pointer args through their provenance in MIR.
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct ProvenanceOption {
+    /// Enable the analysis.
+    #[serde(default)]
+    pub enable: bool,
+    /// Tags guaranteed by pointers taken from references or places, e.g. `&x as *const T`.
+    #[serde(default)]
+    pub reference: Box<[Str]>,
+    /// Paths of functions returning pointers, and tags guaranteed by the returned pointers,
+    /// e.g. `"std::boxed::Box::into_raw" = ["NonNull", "Align"]`.
+    #[serde(default)]
+    pub producers: IndexMap<Str, Box<[Str]>>,
+}
+
+impl ProvenanceOption {
+    fn merge(&mut self, other: &Self) {
+        if other.enable {
+            self.enable = true;
+        }
+        let mut reference = self.reference.to_vec();
+        for tag in &other.reference {
+            if !reference.contains(tag) {
+                reference.push(tag.clone());
+            }
+        }
+        self.reference = reference.into();
+        for (path, tags) in &other.producers {
+            if let Some(old) = self.producers.get(path) {
+                panic!("Producer {path:?} has been defined: {old:?}");
+            }
+            self.producers.insert(path.clone(), tags.clone());
+        }
+    }
+}
+
 /// `any` tag is denied in user's spec, and special in doc generation.
 pub const ANY: &str = "any";
 
@@ -118,6 +247,14 @@ pub struct Cache {
     pub map: IndexMap<Str, Key>,
     /// Merged doc generation options: if any is true, set true.
     pub doc: GenDocOption,
+    /// Merged contract generation options: if any is true, set true.
+    pub contract: GenContractOption,
+    /// Merged provenance options: enabled if any is enabled, and tables are united.
+    pub provenance: ProvenanceOption,
+    /// Tags on def paths in overlay files of all specs.
+    pub overlay: IndexMap<Str, OverlayTags>,
+    /// Merged renaming of tags in overlay files.
+    pub overlay_rename: IndexMap<Str, Str>,
 }
 
 pub static CACHE: LazyLock<Cache> = LazyLock::new(|| {
@@ -138,9 +275,50 @@ pub static CACHE: LazyLock<Cache> = LazyLock::new(|| {
             if let Some(old) = cache.map.get(&name) {
                 panic!("Tag {name:?} has been defined: {old:?}");
             }
+            if tag.arg_kinds.len() > tag.args.len() {
+                panic!("Tag {name:?} has more arg_kinds than args.");
+            }
             _ = cache.map.insert(name, Key { tag, src: path.clone() });
         }
         cache.doc.merge(&config.doc);
+        cache.contract.merge(&config.contract);
+        cache.provenance.merge(&config.provenance);
+        for (def_path, tags) in overlay::read_overlay(&path, &config.overlay) {
+            if cache.overlay.contains_key(&def_path) {
+                panic!("Overlay of {def_path:?} has been defined before {path}.");
+            }
+            cache.overlay.insert(def_path, tags);
+        }
+        for (from, to) in &config.overlay.rename {
+            if let Some(old) = cache.overlay_rename.get(from).filter(|old| *old != to) {
+                panic!("Tag {from:?} has been renamed to {old:?}");
+            }
+            cache.overlay_rename.insert(from.clone(), to.clone());
+        }
+    }
+
+    // Options waive tags through their args, so options without args waive nothing.
+    let mut no_args = IndexMap::<&str, Vec<&str>>::new();
+    for (def_path, tags) in &cache.overlay {
+        let names = tags.vanilla.iter().chain(tags.group_of_any.iter().flatten());
+        for name in names {
+            let Some(key) = cache.map.get(name) else { continue };
+            if key.tag.types.contains(&TagType::Option)
+                && !key.tag.waives.is_empty()
+                && !tags.args.contains_key(name)
+            {
+                no_args.entry(name).or_default().push(def_path);
+            }
+        }
+    }
+    for (name, def_paths) in no_args {
+        let args = cache.map[name].tag.args.join(", ");
+        eprintln!(
+            "Warning: option {name:?} has no args on {} items in overlays, e.g. {:?}, thus waives \
+             no tags. Write `{name}({args})` to waive tags.",
+            def_paths.len(),
+            def_paths[0]
+        );
     }
 
     cache.map.sort_unstable_keys();
@@ -160,6 +338,24 @@ pub fn doc_option() -> GenDocOption {
     CACHE.doc
 }
 
+pub fn contract_option() -> GenContractOption {
+    CACHE.contract
+}
+
+pub fn provenance_option() -> &'static ProvenanceOption {
+    &CACHE.provenance
+}
+
+/// Tags on def paths in overlay files.
+pub fn overlay() -> &'static IndexMap<Str, OverlayTags> {
+    &CACHE.overlay
+}
+
+/// Renaming of tags in overlay files of all specs.
+pub fn overlay_rename() -> &'static IndexMap<Str, Str> {
+    &CACHE.overlay_rename
+}
+
 pub struct DefinedTag {
     pub name: &'static str,
     pub args: &'static Tag,
@@ -180,7 +376,13 @@ impl DefinedTag {
     pub fn hover_documentation(&self) -> String {
         use std::fmt::Write;
 
-        let DefinedTag { args: Tag { desc, expr, types, url, .. }, .. } = self;
+        let DefinedTag {
+            args:
+                Tag {
+                    desc, expr, types, url, kani, contract, runtime, resolved_by, waives, eval, ..
+                },
+            ..
+        } = self;
         let mut doc = String::new();
 
         let types_field = if types.len() == 1 { "type" } else { "types" };
@@ -193,6 +395,24 @@ impl DefinedTag {
         if let Some(expr) = expr {
             _ = writeln!(&mut doc, "**expr**: {expr}\n");
         }
+        if let Some(kani) = kani {
+            _ = writeln!(&mut doc, "**kani**: {kani}\n");
+        }
+        if let Some(contract) = contract {
+            _ = writeln!(&mut doc, "**contract**: {contract}\n");
+        }
+        if let Some(runtime) = runtime {
+            _ = writeln!(&mut doc, "**runtime**: {runtime}\n");
+        }
+        if !resolved_by.is_empty() {
+            _ = writeln!(&mut doc, "**resolved_by**: {}\n", resolved_by.join(", "));
+        }
+        if !waives.is_empty() {
+            _ = writeln!(&mut doc, "**waives**: {}\n", waives.join(", "));
+        }
+        if let Some(eval) = eval {
+            _ = writeln!(&mut doc, "**eval**: {}\n", eval.as_str());
+        }
         if let Some(url) = url {
             _ = writeln!(&mut doc, "**url**: <{url}>");
         }
@@ -0,0 +1,191 @@
+//! Overlay tags on items whose source can't be edited, e.g. std, vendored crates, and bindings.
+//!
+//! Overlay files are listed in `[overlay] files` of a spec TOML, relative to the spec file.
+//! Each file is TOML or JSON by its extension, and keyed by def paths:
+//!
+//! ```toml
+//! "core::ptr::read" = ["ValidPtr", "Align", "Init"]
+//! # Tags grouped by args like `data/std.json`, which are merged.
+//! "core::ptr::swap" = { "0" = ["ValidPtr"], "1" = ["ValidPtr"] }
+//! ```
+//!
+//! A tag ending with `|` forms `any` with the next tags, e.g. `["Size |", "ValidSlice"]` is
+//! `any(Size, ValidSlice)`. Tag names are renamed through `[overlay.rename]` of the same spec.
+//!
+//! Args are written in parentheses, e.g. `"Trait(T, Copy, Alias)"`, which options need to waive
+//! tags. Other tags are discharged by names, so their args are optional.
+use super::Str;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// Overlay files and renaming of tags in them.
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct OverlayOption {
+    /// Paths of overlay files, relative to the spec file.
+    #[serde(default)]
+    pub files: Box<[Str]>,
+    /// Tag names in overlay files to tag names in spec, e.g. `Aligned = "Align"`.
+    #[serde(default)]
+    pub rename: IndexMap<Str, Str>,
+}
+
+/// Tags on an item in overlay files.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct OverlayTags {
+    pub vanilla: Vec<Str>,
+    pub group_of_any: Vec<Vec<Str>>,
+    /// Args of tags written with them, by tag names.
+    pub args: IndexMap<Str, Vec<Str>>,
+}
+
+impl OverlayTags {
+    fn push_list(&mut self, list: &[Str], rename: &IndexMap<Str, Str>) {
+        let mut any = Vec::new();
+        for tag in list {
+            let tag = tag.trim();
+            let (name, alternative) = match tag.strip_suffix('|') {
+                Some(name) => (name.trim(), true),
+                None => (tag, false),
+            };
+            let (name, args) = split_args(name);
+            if !name.is_empty() {
+                let name = rename.get(name).cloned().unwrap_or_else(|| name.into());
+                if !args.is_empty() {
+                    self.args.insert(name.clone(), args);
+                }
+                any.push(name);
+            }
+            if !alternative {
+                self.push_group(std::mem::take(&mut any));
+            }
+        }
+        self.push_group(any);
+    }
+
+    fn push_group(&mut self, mut group: Vec<Str>) {
+        match group.len() {
+            0 => (),
+            1 => {
+                let name = group.pop().unwrap();
+                if !self.vanilla.contains(&name) {
+                    self.vanilla.push(name);
+                }
+            }
+            _ => {
+                if !self.group_of_any.contains(&group) {
+                    self.group_of_any.push(group);
+                }
+            }
+        }
+    }
+}
+
+/// Split `Name(a, b)` into the name and args, which are separated by commas out of brackets.
+fn split_args(tag: &str) -> (&str, Vec<Str>) {
+    let Some((name, rest)) = tag.split_once('(') else { return (tag, Vec::new()) };
+    let Some(rest) = rest.trim_end().strip_suffix(')') else {
+        panic!("Args of overlay tag {tag:?} are not closed.")
+    };
+    let mut args = Vec::new();
+    let (mut depth, mut start) = (0i32, 0);
+    for (idx, c) in rest.char_indices() {
+        match c {
+            '(' | '[' | '<' => depth += 1,
+            ')' | ']' | '>' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(rest[start..idx].trim().into());
+                start = idx + 1;
+            }
+            _ => (),
+        }
+    }
+    let last = rest[start..].trim();
+    if !last.is_empty() {
+        args.push(last.into());
+    }
+    (name.trim(), args)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Tags(Vec<Str>),
+    ByArg(IndexMap<Str, Vec<Str>>),
+}
+
+/// Read overlay files listed in the spec file.
+pub(super) fn read_overlay(spec: &str, option: &OverlayOption) -> IndexMap<Str, OverlayTags> {
+    let dir = Path::new(spec).parent().unwrap_or(Path::new(""));
+    let mut map = IndexMap::new();
+    for file in &option.files {
+        let path = dir.join(&**file);
+        for (def_path, tags) in read_overlay_file(&path, &option.rename) {
+            if map.contains_key(&def_path) {
+                panic!("Overlay of {def_path:?} has been defined before {}.", path.display());
+            }
+            map.insert(def_path, tags);
+        }
+    }
+    map
+}
+
+/// Read an overlay file in TOML or JSON by its extension, with tags renamed.
+pub fn read_overlay_file(path: &Path, rename: &IndexMap<Str, Str>) -> IndexMap<Str, OverlayTags> {
+    let text = fs::read_to_string(path);
+    let path = path.display();
+    let text = text.unwrap_or_else(|e| panic!("Failed to read {path}:\n{e}"));
+    let entries: IndexMap<Str, Entry> = if path.to_string().ends_with(".json") {
+        serde_json::from_str(&text).unwrap_or_else(|e| panic!("Failed to parse {path}:\n{e}"))
+    } else {
+        toml::from_str(&text).unwrap_or_else(|e| panic!("Failed to parse {path}:\n{e}"))
+    };
+
+    let mut map = IndexMap::with_capacity(entries.len());
+    for (def_path, entry) in entries {
+        let mut tags = OverlayTags::default();
+        match entry {
+            Entry::Tags(list) => tags.push_list(&list, rename),
+            Entry::ByArg(by_arg) => {
+                for list in by_arg.values() {
+                    tags.push_list(list, rename);
+                }
+            }
+        }
+        map.insert(def_path, tags);
+    }
+    map
+}
+
+#[test]
+fn any_and_rename() {
+    let rename = IndexMap::from([("Aligned".into(), "Align".into())]);
+    let mut tags = OverlayTags::default();
+    let list: Vec<Str> =
+        ["Size |", "ValidSlice |", "ValidTraitObj", "", "Aligned", "Null | ", "Init"]
+            .map(Into::into)
+            .into();
+    tags.push_list(&list, &rename);
+    tags.push_list(&["Aligned".into()], &rename);
+    assert_eq!(&*tags.vanilla, [Str::from("Align")]);
+    assert_eq!(
+        tags.group_of_any,
+        [
+            vec![Str::from("Size"), "ValidSlice".into(), "ValidTraitObj".into()],
+            vec!["Null".into(), "Init".into()]
+        ]
+    );
+}
+
+#[test]
+fn args() {
+    let rename = IndexMap::from([("CopyTrait".into(), "Trait".into())]);
+    let mut tags = OverlayTags::default();
+    let list: Vec<Str> =
+        ["CopyTrait(T, Copy, Alias)", "Size(Vec<u8, A>, 24) |", "Init"].map(Into::into).into();
+    tags.push_list(&list, &rename);
+    assert_eq!(&*tags.vanilla, [Str::from("Trait")]);
+    assert_eq!(tags.group_of_any, [vec![Str::from("Size"), "Init".into()]]);
+    assert_eq!(tags.args["Trait"], [Str::from("T"), "Copy".into(), "Alias".into()]);
+    assert_eq!(tags.args["Size"], [Str::from("Vec<u8, A>"), "24".into()]);
+}
@@ -1,15 +1,19 @@
 use crate::{
     Str,
-    configuration::{ANY, Tag, TagType, doc_option, env::need_check, get_tag, get_tag_opt},
+    configuration::{
+        ANY, ArgKind, EvalKind, Tag, TagType, contract_option, doc_option, env::need_check,
+        get_tag, get_tag_opt,
+    },
 };
 use indexmap::IndexMap;
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::quote;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use syn::{
     parse::{Parse, ParseStream},
     punctuated::Punctuated,
+    spanned::Spanned,
     token::{Brace, Paren},
     *,
 };
@@ -54,12 +58,24 @@ pub fn parse_attr_and_get_properties(attr: &str) -> Box<[PropertiesAndReason]> {
 
 #[derive(Debug)]
 pub struct SafetyAttrArgs {
+    /// Anchor name in `#[checked { name = anchor; SP1, SP2 }]`, which is referred to by
+    /// `#[ref(anchor)]` on other sites to share the same discharge.
+    pub name: Option<Ident>,
     pub args: Punctuated<PropertiesAndReason, Token![;]>,
 }
 
 impl Parse for SafetyAttrArgs {
     fn parse(input: ParseStream) -> Result<Self> {
-        Ok(SafetyAttrArgs { args: Punctuated::parse_terminated(input)? })
+        let mut name = None;
+        if input.peek(Ident) && input.peek2(Token![=]) && input.fork().parse::<Ident>()? == "name" {
+            let _: Ident = input.parse()?;
+            let _: Token![=] = input.parse()?;
+            name = Some(input.parse()?);
+            if !input.is_empty() {
+                let _: Token![;] = input.parse()?;
+            }
+        }
+        Ok(SafetyAttrArgs { name, args: Punctuated::parse_terminated(input)? })
     }
 }
 
@@ -199,6 +215,108 @@ impl PropertiesAndReason {
     pub fn need_gen_doc(&self) -> bool {
         self.desc.is_some() || !self.tags.is_empty()
     }
+
+    /// Generate verifier contracts enabled by `contract` option in spec:
+    ///
+    /// ```text
+    /// #[cfg_attr(kani, kani::requires(expr))]
+    /// #[core::contracts::requires(expr)]
+    /// ```
+    ///
+    /// Postconditions are generated as closures on the return value named `result`.
+    ///
+    /// Templates not rendered as expressions are combined into an error spanned on args.
+    pub fn gen_contracts(&self, kind: ContractKind) -> Result<TokenStream> {
+        type Attr = fn(&Expr) -> TokenStream;
+        let (kani, core): (Attr, Attr) = match kind {
+            ContractKind::Requires => (
+                |expr| quote! { #[cfg_attr(kani, kani::requires(#expr))] },
+                |expr| quote! { #[core::contracts::requires(#expr)] },
+            ),
+            ContractKind::Ensures => (
+                |expr| quote! { #[cfg_attr(kani, kani::ensures(|result| #expr))] },
+                |expr| quote! { #[core::contracts::ensures(|result| #expr)] },
+            ),
+            ContractKind::Invariant => return Ok(TokenStream::default()),
+        };
+        let option = contract_option();
+        let verifiers = [(Verifier::Kani, option.kani, kani), (Verifier::Core, option.core, core)];
+
+        let mut ts = TokenStream::default();
+        let mut errors: Option<Error> = None;
+        for tag in &self.tags {
+            for (verifier, enabled, attr) in verifiers {
+                match enabled.then(|| tag.gen_contract_expr(verifier)).flatten() {
+                    Some(Ok(expr)) => ts.extend(attr(&expr)),
+                    Some(Err(err)) => match &mut errors {
+                        Some(errors) => errors.combine(err),
+                        None => errors = Some(err),
+                    },
+                    None => (),
+                }
+            }
+        }
+        match errors {
+            Some(errors) => Err(errors),
+            None => Ok(ts),
+        }
+    }
+
+    /// Generate statements to check tags at runtime:
+    ///
+    /// ```text
+    /// #[cfg(safety_runtime_checks)]
+    /// ::core::debug_assert!(expr, "{}", "rendered desc");
+    /// ```
+    ///
+    /// Tags with omitted args referred to by the template are skipped, and so are tags whose
+    /// pointer args are params of other types than raw pointers in the signature, because
+    /// references and `NonNull` are never null, and don't have raw pointer methods.
+    /// A template not rendered as an expression is a compile error under the same cfg.
+    pub fn gen_runtime_checks(&self, sig: Option<&Signature>) -> TokenStream {
+        let mut ts = TokenStream::default();
+        for tag in &self.tags {
+            if sig.is_some_and(|sig| tag.has_non_raw_pointer_param(sig)) {
+                continue;
+            }
+            match tag.gen_runtime_expr() {
+                Some(Ok(expr)) => {
+                    let msg = match tag.gen_doc() {
+                        Some(desc) => desc.trim().to_owned(),
+                        None => format!("{} is violated", tag.tag.name()),
+                    };
+                    ts.extend(quote! {
+                        #[cfg(safety_runtime_checks)]
+                        ::core::debug_assert!(#expr, "{}", #msg);
+                    });
+                }
+                Some(Err(err)) => {
+                    let err = err.to_compile_error();
+                    ts.extend(quote! { #[cfg(safety_runtime_checks)] #err });
+                }
+                None => (),
+            }
+        }
+        ts
+    }
+}
+
+/// Which verifier a contract is generated for.
+#[derive(Clone, Copy, Debug)]
+pub enum Verifier {
+    /// `kani` field in spec.
+    Kani,
+    /// `contract` field in spec, used by `core::contracts`.
+    Core,
+}
+
+/// Precondition, postcondition, or type invariant.
+#[derive(Clone, Copy, Debug)]
+pub enum ContractKind {
+    Requires,
+    Ensures,
+    /// No contract is generated for type invariants.
+    Invariant,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -238,22 +356,155 @@ impl Property {
             return Some(doc);
         }
 
-        let defined_tag = get_tag_opt(name)?;
+        self.gen_doc_in(get_tag_opt(name)?)
+    }
+
+    /// Like [`Self::gen_doc`], but with the tag defined in the given spec rather than in the
+    /// cache, e.g. a spec recorded in stat of a crate. `any` tag isn't expanded.
+    pub fn gen_doc_in(&self, defined_tag: &Tag) -> Option<String> {
+        let map_defined_arg_input_arg = self.map_defined_arg_input_arg(defined_tag);
+        defined_tag.desc.as_deref().map(|desc| utils::template(desc, &map_defined_arg_input_arg))
+    }
+
+    /// Like [`Self::gen_doc_in`], but as plain text without HTML escaping of args and extra
+    /// newlines, which is escaped as a whole by the caller if needed.
+    pub fn gen_desc_in(&self, defined_tag: &Tag) -> Option<String> {
+        let map_defined_arg_input_arg = self.map_defined_arg_input_arg(defined_tag);
+        let desc = defined_tag.desc.as_deref()?;
+        utils::template_unescaped(desc, &map_defined_arg_input_arg).ok()
+    }
+
+    /// Args as strings, e.g. `SP(a.b, "c")` gives `["a . b", "c"]`.
+    pub fn args_as_str(&self) -> Vec<String> {
+        self.args.iter().map(utils::expr_to_string).collect()
+    }
+
+    /// Map defined arg names to user inputs.
+    fn map_defined_arg_input_arg<'a>(&self, defined_tag: &'a Tag) -> IndexMap<&'a str, String> {
         // NOTE: this tolerates missing args, but position matters.
         let args_len = self.args.len().min(defined_tag.args.len());
 
-        // map defined arg names to user inputs
         let defined_args = defined_tag.args[..args_len].iter().map(|s| &**s);
         let input_args = self.args[..args_len].iter().map(utils::expr_to_string);
-        let mut map_defined_arg_input_arg: IndexMap<_, _> = defined_args.zip(input_args).collect();
+        let mut map: IndexMap<_, _> = defined_args.zip(input_args).collect();
         // if input arg is missing, defined arg will be an empty string
         for defined_arg in &defined_tag.args {
-            if !map_defined_arg_input_arg.contains_key(&**defined_arg) {
-                map_defined_arg_input_arg.insert(defined_arg, String::new());
+            if !map.contains_key(&**defined_arg) {
+                map.insert(defined_arg, String::new());
             }
         }
+        map
+    }
 
-        defined_tag.desc.as_deref().map(|desc| utils::template(desc, &map_defined_arg_input_arg))
+    /// Render the contract expression of the tag from its template in spec.
+    /// None means the tag has no such template, or the template refers to omitted args.
+    pub fn gen_contract_expr(&self, verifier: Verifier) -> Option<Result<Expr>> {
+        self.gen_code_expr(&|tag| match verifier {
+            Verifier::Kani => tag.kani.as_deref(),
+            Verifier::Core => tag.contract.as_deref(),
+        })
+    }
+
+    /// Render the runtime check expression of the tag from `runtime` template in spec.
+    /// None means the tag has no such template, or the template refers to omitted args.
+    pub fn gen_runtime_expr(&self) -> Option<Result<Expr>> {
+        self.gen_code_expr(&|tag| tag.runtime.as_deref())
+    }
+
+    /// Render a code template chosen from the tag definition. For `any` tag,
+    /// all of SPs in it must have the template to form a disjunction. The range arg of
+    /// `eval = "range"` tags is rendered as a range of `i128`.
+    ///
+    /// An error spanned on args is returned if the rendered text isn't an expression.
+    fn gen_code_expr(
+        &self,
+        template: &dyn Fn(&'static Tag) -> Option<&'static str>,
+    ) -> Option<Result<Expr>> {
+        let name = self.tag.name();
+
+        if name == ANY {
+            let mut disjunction = Vec::new();
+            for prop in self.args_in_any_tag()? {
+                for tag in &prop.tags {
+                    match tag.gen_code_expr(template)? {
+                        Ok(expr) => disjunction.push(expr),
+                        Err(err) => return Some(Err(err)),
+                    }
+                }
+            }
+            return Some(Ok(parse_quote! { #( (#disjunction) )||* }));
+        }
+
+        let defined_tag = get_tag_opt(name)?;
+        let template = template(defined_tag)?;
+        // Omitted args are rendered as empty strings, which make no sense in code.
+        let omitted = defined_tag.args.get(self.args.len()..).unwrap_or_default();
+        if omitted.iter().any(|arg| template.contains(&format!("{{{arg}}}"))) {
+            return None;
+        }
+        let mut map = self.map_defined_arg_input_arg(defined_tag);
+        if defined_tag.eval == Some(EvalKind::Range) {
+            let range = self.args.get(1).and_then(utils::range_as_i128);
+            if let (Some(defined), Some(range)) = (defined_tag.args.get(1), range) {
+                map.insert(defined, utils::expr_to_string(&range));
+            }
+        }
+        let span = self.args.first().map_or_else(Span::call_site, |arg| arg.span());
+        let expr = match utils::template_unescaped(template, &map) {
+            Ok(expr) => expr,
+            Err(err) => {
+                let msg = format!("Template of {name} is not rendered: `{template}`\n{err}");
+                return Some(Err(Error::new(span, msg)));
+            }
+        };
+        Some(parse_str(&expr).map_err(|err| {
+            let msg =
+                format!("Template of {name} is not rendered as an expression: `{expr}`\n{err}");
+            Error::new(span, msg)
+        }))
+    }
+
+    /// Fill omitted trailing type args with the pointee type of the first pointer arg, e.g.
+    /// `Align(p)` becomes `Align(p, T)` if `p` is `*const T`. Returns true if any is filled.
+    pub fn fill_type_args(&mut self, pointee: impl Fn(&Expr) -> Option<String>) -> bool {
+        let Some(defined_tag) = get_tag_opt(self.tag.name()) else { return false };
+        let defined = &defined_tag.args;
+        if self.args.len() >= defined.len() {
+            return false;
+        }
+        let pointer = self.args.iter().enumerate().find_map(|(idx, arg)| {
+            (defined_tag.arg_kind(idx) == ArgKind::Pointer).then(|| pointee(arg)).flatten()
+        });
+        let Some(pointee) = pointer else { return false };
+
+        let mut args = self.args.to_vec();
+        // Args are positional, so stop at the first omitted arg that isn't a type.
+        for idx in args.len()..defined.len() {
+            if defined_tag.arg_kind(idx) != ArgKind::Type {
+                break;
+            }
+            match parse_str(&pointee) {
+                Ok(ty) => args.push(ty),
+                Err(_) => break,
+            }
+        }
+        let filled = args.len() != self.args.len();
+        self.args = args.into();
+        filled
+    }
+
+    /// Whether any pointer arg, including those of SPs in `any` tag, names a param in the
+    /// signature which isn't a raw pointer.
+    pub fn has_non_raw_pointer_param(&self, sig: &Signature) -> bool {
+        if let Some(props) = self.args_in_any_tag() {
+            let mut tags = props.iter().flat_map(|prop| &prop.tags);
+            return tags.any(|tag| tag.has_non_raw_pointer_param(sig));
+        }
+        let Some(defined_tag) = get_tag_opt(self.tag.name()) else { return false };
+        self.args.iter().enumerate().any(|(idx, arg)| {
+            defined_tag.arg_kind(idx) == ArgKind::Pointer
+                && param_in_signature(sig, arg).is_some_and(|ty| !matches!(ty, Type::Ptr(_)))
+        })
     }
 
     /// SPs in `any` tag. None means the tag is not `any` or empty args.
@@ -263,6 +514,39 @@ impl Property {
     }
 }
 
+/// The pointee type of a raw pointer or `NonNull` param named by the arg in the signature.
+pub fn pointee_in_signature(sig: &Signature, arg: &Expr) -> Option<String> {
+    let elem = match param_in_signature(sig, arg)? {
+        Type::Ptr(ptr) => &*ptr.elem,
+        Type::Path(path) => {
+            let last = path.path.segments.last()?;
+            if last.ident != "NonNull" {
+                return None;
+            }
+            let PathArguments::AngleBracketed(generics) = &last.arguments else { return None };
+            match generics.args.first()? {
+                GenericArgument::Type(ty) => ty,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    Some(quote!(#elem).to_string())
+}
+
+/// The type of the param named by the arg in the signature.
+fn param_in_signature<'a>(sig: &'a Signature, arg: &Expr) -> Option<&'a Type> {
+    let Expr::Path(path) = arg else { return None };
+    let name = path.path.get_ident()?;
+    sig.inputs.iter().find_map(|input| match input {
+        FnArg::Typed(PatType { pat, ty, .. }) => match &**pat {
+            Pat::Ident(pat) if pat.ident == *name => Some(&**ty),
+            _ => None,
+        },
+        FnArg::Receiver(_) => None,
+    })
+}
+
 /// Typed SP: `type.SP`
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TagNameType {
@@ -305,6 +589,23 @@ impl TagNameType {
         (&self.name, self.typ)
     }
 
+    /// Whether the tag is a hazard, through `hazard.SP` or the single defined type.
+    pub fn is_hazard(&self) -> bool {
+        match self.typ {
+            Some(typ) => typ == TagType::Hazard,
+            None => self.get_spec().is_some_and(|tag| *tag.types == [TagType::Hazard]),
+        }
+    }
+
+    /// Whether the tag is an optional precondition, through `option.SP` or the single defined
+    /// type.
+    pub fn is_option(&self) -> bool {
+        match self.typ {
+            Some(typ) => typ == TagType::Option,
+            None => self.get_spec().is_some_and(|tag| *tag.types == [TagType::Option]),
+        }
+    }
+
     /// Check if the tag in macro is wrongly specified.
     pub fn check_type(&self) {
         let (name, typ) = self.name_type();
@@ -67,6 +67,20 @@ fn parse_safety_args() {
     _ = parse_args(r#" SP(a, b): "reason"; SP1, SP2: "reason"; SP3, SP4 "#).unwrap();
 }
 
+#[test]
+fn parse_safety_anchor_name() {
+    let args = parse_args(r#" name = try_fold; SP1, SP2: "reason"; SP3 "#).unwrap();
+    assert_eq!(args.name.unwrap(), "try_fold");
+    assert_eq!(args.args.len(), 2);
+
+    let args = parse_args(r#" name = try_fold "#).unwrap();
+    assert_eq!(args.name.unwrap(), "try_fold");
+    assert!(args.args.is_empty());
+
+    _ = parse_args(r#" name = try_fold SP1 "#).unwrap_err();
+    assert!(parse_args("SP1").unwrap().name.is_none());
+}
+
 #[test]
 fn parse_safety_complex_args() {
     // SP path prefix
@@ -75,3 +89,15 @@ fn parse_safety_complex_args() {
     // complex expressions in arguments
     _ = parse_args(r#" hazard.Alias(A {a: self.a}, a::b(c![])) : "" "#).unwrap();
 }
+
+#[test]
+fn pointee_type_in_signature() {
+    let sig: Signature =
+        parse_quote! { unsafe fn f<T>(src: *const T, dst: NonNull<[u8]>, len: usize, p: &T) };
+    let pointee = |arg: &str| pointee_in_signature(&sig, &parse_str(arg).unwrap());
+    assert_eq!(pointee("src").unwrap(), "T");
+    assert_eq!(pointee("dst").unwrap(), "[u8]");
+    assert!(pointee("len").is_none());
+    assert!(pointee("p").is_none());
+    assert!(pointee("self.ptr").is_none());
+}
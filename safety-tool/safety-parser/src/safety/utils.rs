@@ -62,6 +62,50 @@ pub fn template(desc: &str, map: &IndexMap<&str, String>) -> String {
     doc
 }
 
+/// Like [`template`], but without HTML escaping and extra newlines, which is
+/// used to render code rather than doc. Unlike doc, code can contain braces of blocks or
+/// closures, which are invalid in templates, thus an error is returned instead of panicking.
+pub fn template_unescaped(
+    code: &str,
+    map: &IndexMap<&str, String>,
+) -> Result<String, tinytemplate::error::Error> {
+    let mut template = tinytemplate::TinyTemplate::new();
+    template.set_default_formatter(&tinytemplate::format_unescaped);
+    template.add_template("", code)?;
+    template.render("", map)
+}
+
+/// Rewrite a range arg of `eval = "range"` tags as a Rust range of `i128`, which contains
+/// integers of any type through `as` casts: `[a, b]` becomes `a as i128..=b as i128`, and
+/// bounds of `a..b` are cast in the same way.
+pub fn range_as_i128(expr: &Expr) -> Option<Expr> {
+    let cast = |expr: &Expr| -> Box<Expr> { Box::new(syn::parse_quote!((#expr) as i128)) };
+    match expr {
+        Expr::Array(array) if array.elems.len() == 2 => {
+            let (lo, hi) = (cast(&array.elems[0]), cast(&array.elems[1]));
+            Some(syn::parse_quote!(#lo..=#hi))
+        }
+        Expr::Range(range) => {
+            let mut range = range.clone();
+            range.start = range.start.as_deref().map(cast);
+            range.end = range.end.as_deref().map(cast);
+            Some(Expr::Range(range))
+        }
+        Expr::Paren(paren) => range_as_i128(&paren.expr),
+        _ => None,
+    }
+}
+
+#[test]
+fn code_interpolation() {
+    let map = IndexMap::from([("p", "self.ptr".to_owned()), ("T", "Vec<u8>".to_owned())]);
+    let code = template_unescaped("kani::mem::can_dereference({p} as *const {T})", &map);
+    assert_eq!(code.unwrap(), "kani::mem::can_dereference(self.ptr as *const Vec<u8>)");
+    // a block brace and an unknown name are errors rather than panics
+    assert!(template_unescaped("{ let x = {p}; x }", &map).is_err());
+    assert!(template_unescaped("{q}.is_null()", &map).is_err());
+}
+
 #[test]
 fn string_interpolation() {
     #[derive(serde::Serialize)]
@@ -88,3 +132,12 @@ fn failed_to_find_value_c_from_path_c() {
     template.add_template("", s).unwrap();
     println!("rendered: {}", template.render("", &Val { a: 123, b: "hi" }).unwrap());
 }
+
+#[test]
+fn range_interpolation() {
+    let range = |s: &str| expr_to_string(&range_as_i128(&syn::parse_str(s).unwrap()).unwrap());
+    assert_eq!(range("[0, isize::MAX]"), "(0) as i128 ..= (isize :: MAX) as i128");
+    assert_eq!(range("(1..64)"), "(1) as i128 .. (64) as i128");
+    assert_eq!(range("1.."), "(1) as i128 ..");
+    assert!(range_as_i128(&syn::parse_str("len").unwrap()).is_none());
+}
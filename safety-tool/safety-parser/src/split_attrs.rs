@@ -24,6 +24,14 @@ fn parse_item(mut item: Item) -> Input {
         Item::Fn(fun) => take(&mut fun.attrs),
         Item::Impl(imp) => take(&mut imp.attrs),
         Item::Trait(trait_) => take(&mut trait_.attrs),
+        Item::Struct(struct_) => take(&mut struct_.attrs),
+        Item::Enum(enum_) => take(&mut enum_.attrs),
+        Item::Union(union_) => take(&mut union_.attrs),
+        // A function without body like a method declared in a trait.
+        Item::Verbatim(ts) => match parse2::<TraitItemFn>(ts.clone()) {
+            Ok(mut fun) => return Input::new(take(&mut fun.attrs), fun).set_gen_doc(),
+            Err(_) => Vec::new(),
+        },
         _ => Vec::new(),
     };
     Input::new(attrs, item).set_gen_doc()
@@ -100,3 +108,14 @@ fn split_attrs_on_expr() {
     };
     dbg!(split_attrs_and_rest(ts));
 }
+
+#[test]
+fn split_attrs_on_trait_method() {
+    let ts = quote::quote! {
+        #[a]
+        unsafe fn f();
+    };
+    let input = dbg!(split_attrs_and_rest(ts));
+    assert!(!input.attrs.is_empty());
+    assert!(input.gen_doc);
+}
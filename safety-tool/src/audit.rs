@@ -0,0 +1,66 @@
+//! Discrepancies between tags of exported unsafe APIs and tags required by unsafe calls in
+//! their bodies, written to `SP_OUT_DIR/<crate>.audit.json` and `SP_OUT_DIR/<crate>.audit.md`.
+//!
+//! Tags of an unsafe call that aren't discharged inside the body are forwarded to the caller,
+//! so they should be among tags declared by the caller. When some are missing, the safety doc
+//! of the caller likely misses these SPs, e.g. `Allocated` of `Arc::from_raw` in
+//! `data/track_record.md`.
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write};
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Audit {
+    /// Overlay paths to unsafe APIs, e.g. `alloc::sync::from_raw`. Such paths drop impl blocks,
+    /// so a path may be shared by several APIs.
+    pub map: IndexMap<String, Vec<Discrepancy>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Discrepancy {
+    /// The path visible to users, e.g. `Arc::<T>::from_raw`.
+    pub api: String,
+    /// Names of tags declared by the API.
+    pub declared: Vec<String>,
+    /// Tags required by calls but not declared.
+    pub missing: Vec<Missing>,
+    /// `file:line:col`
+    pub location: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Missing {
+    /// A tag name, or alternatives in `any`.
+    pub tags: Vec<String>,
+    /// Callees requiring the tag.
+    pub callees: Vec<String>,
+    /// Whether the doc of the API mentions the tag, in which case only the tool attr is missing.
+    pub in_doc: bool,
+}
+
+impl Audit {
+    /// Render the table like `data/track_record.md`.
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        _ = self.write_markdown(&mut md);
+        md
+    }
+
+    fn write_markdown(&self, md: &mut String) -> fmt::Result {
+        writeln!(md, "|Num| API | Missing SP | Required By | In Doc | Declared |")?;
+        writeln!(md, "|---|-----|------------|-------------|--------|----------|")?;
+        let rows = self.map.values().flatten().flat_map(|d| d.missing.iter().map(move |m| (d, m)));
+        for (idx, (discrepancy, missing)) in rows.enumerate() {
+            let num = idx + 1;
+            let api = &discrepancy.api;
+            let tags = missing.tags.join(" \\| ");
+            let callees = missing.callees.join(", ");
+            let doc = if missing.in_doc { "yes" } else { "no" };
+            let declared = &discrepancy.declared;
+            let declared = if declared.is_empty() { "-".to_owned() } else { declared.join(", ") };
+            writeln!(md, "|{num}| {api} | {tags} | {callees} | {doc} | {declared} |")?;
+        }
+        Ok(())
+    }
+}
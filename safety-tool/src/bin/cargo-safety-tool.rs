@@ -1,3 +1,4 @@
+use safety_tool::{diff, graph, report, workspace};
 use std::{env::var, process::Command};
 
 fn main() {
@@ -24,17 +25,68 @@ fn main() {
         // Entry for cargo-safety-tool: all arguments after `cargo safety-tool`
         // will be passed to `cargo build`.
         let mut args = args;
+        let mut vars = vec![("RUSTC", cargo_safe_tool.to_owned()), ("WRAPPER", "1".to_owned())];
         if args[0].ends_with("cargo-safety-tool") {
             if args.get(1).map(|arg| arg == "safety-tool").unwrap_or(false) {
                 // [cargo, safety-tool, args...]
                 args.remove(0);
             }
+            if args.get(1).map(|arg| arg == "aggregate").unwrap_or(false) {
+                // [cargo-safety-tool, aggregate, dirs...]: merge outputs of crates in the dirs
+                // or SP_OUT_DIR into a workspace report, without building.
+                let dirs = workspace::dirs_or_out_dir(&args[2..]).unwrap();
+                let [json, txt] = workspace::aggregate(&dirs).unwrap();
+                println!("Aggregated into {json} and {txt}");
+                return;
+            }
+            if args.get(1).map(|arg| arg == "diff").unwrap_or(false) {
+                // [cargo-safety-tool, diff, old_dir, new_dir, format]: compare outputs of two
+                // runs, and exit with 1 when discharge coverage regresses.
+                let [old, new] = [2, 3].map(|idx| {
+                    args.get(idx).expect("diff needs old and new dirs.").as_str().into()
+                });
+                let format = diff::Format::from_args(&args[4..]).unwrap();
+                let diff = diff::diff(&old, &new).unwrap();
+                print!("{}", diff.render(format).unwrap());
+                if diff.regressed {
+                    std::process::exit(1);
+                }
+                return;
+            }
+            if args.get(1).map(|arg| arg == "graph").unwrap_or(false) {
+                // [cargo-safety-tool, graph, stat.json, options...]: print the call graph in
+                // the stat JSON as DOT or Mermaid, without building.
+                let path = args.get(2).expect("graph needs a path to the stat JSON.");
+                let opts = graph::GraphOptions::from_args(&args[3..]).unwrap();
+                print!("{}", graph::render_file(path.as_ref(), &opts).unwrap());
+                return;
+            }
+            if args.get(1).map(|arg| arg == "report").unwrap_or(false) {
+                // [cargo-safety-tool, report, stat.json, options...]: write an HTML report of
+                // the stat JSON and source files, without building.
+                let path = args.get(2).expect("report needs a path to the stat JSON.");
+                let opts = report::ReportOptions::from_args(&args[3..]).unwrap();
+                let html = report::write_report(path.as_ref(), &opts).unwrap();
+                println!("Reported to {html}");
+                return;
+            }
+            if args.get(1).map(|arg| arg == "apply-tags").unwrap_or(false) {
+                // [cargo-safety-tool, apply-tags, mapping, args...]: rewrite the source of
+                // workspace members with tags in the mapping.
+                args.remove(1);
+                let mapping = args.get(1).expect("apply-tags needs a path to the mapping.");
+                let mapping = std::fs::canonicalize(mapping)
+                    .unwrap_or_else(|e| panic!("Failed to find {mapping}:\n{e}"));
+                vars.push(("SP_APPLY", mapping.to_str().unwrap().to_owned()));
+                args.remove(1);
+            }
             args[0] = "build".to_owned();
         } else {
             unimplemented!("Need to support this case: {args:#?}")
         }
         // cargo build args...
-        run("cargo", &args, &[("RUSTC", cargo_safe_tool), ("WRAPPER", "1")]);
+        let vars: Vec<_> = vars.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        run("cargo", &args, &vars);
     }
 }
 
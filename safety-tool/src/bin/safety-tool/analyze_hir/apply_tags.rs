@@ -0,0 +1,471 @@
+//! Apply tags in a `data/std.json` style mapping to the source of the crate as
+//! `#[safety::requires { ... }]`, when `SP_APPLY=path/to/mapping.json` is set.
+//!
+//! Keys of the mapping are resolved to local functions by their overlay paths or visible paths,
+//! the same as overlay files. Tags are renamed through `[overlay.rename]` of specs, and
+//! alternatives like `["Size |", "ValidSlice"]` are written as `any { Size, ValidSlice }`.
+//!
+//! Source files are edited in place, and the rest of the text is untouched:
+//! * a new attribute is inserted above the item with the same indentation;
+//! * an existing `#[safety::requires]` on the item is updated by appending missing tags before
+//!   its closing delimiter, one per line if it spans multiple lines;
+//! * tags already in the attribute are kept, so applying the mapping again changes nothing. Tags
+//!   are compared by top-level tag names in the parsed attribute, and an `any` group is present
+//!   if any tag in it is present at the top level, or the same group is present;
+//! * entries on the same item, e.g. by its overlay path and visible path, are merged.
+//!
+//! Entries on the local crate that aren't found, or are defined in macro expansions, are
+//! reported, while entries on other crates are counted and skipped.
+use super::overlay_path;
+use crate::Result;
+use eyre::Context;
+use rustc_data_structures::fx::FxIndexMap;
+use rustc_hir::{def::DefKind, def_id::LocalDefId};
+use rustc_middle::ty::TyCtxt;
+use rustc_span::{FileName, RealFileName, def_id::LOCAL_CRATE};
+use safety_parser::{
+    configuration::{ANY, OverlayTags, overlay_rename, read_overlay_file},
+    proc_macro2::{Delimiter, TokenStream, TokenTree},
+    syn::{Attribute, Meta, parse::Parser},
+};
+use std::{fs, path::PathBuf};
+
+/// Path to the mapping to apply.
+const ENV_SP_APPLY: &str = "SP_APPLY";
+
+/// The path of the attribute to insert or update.
+const ATTR: &str = "safety::requires";
+
+/// The mapping to apply, if `SP_APPLY` is set.
+pub fn mapping() -> Option<String> {
+    std::env::var(ENV_SP_APPLY).ok()
+}
+
+/// Whether the crate is to be rewritten: it's not a dependency built by cargo.
+pub fn is_target_crate() -> bool {
+    std::env::var("CARGO_PRIMARY_PACKAGE").is_ok() || std::env::var("CARGO_PKG_NAME").is_err()
+}
+
+pub fn apply(tcx: TyCtxt, mapping: &str) -> Result<()> {
+    let mapping = read_overlay_file(mapping.as_ref(), overlay_rename());
+    let krate = tcx.crate_name(LOCAL_CRATE);
+
+    // Local functions by overlay paths and visible paths.
+    let mut items = FxIndexMap::<String, Vec<_>>::default();
+    for local in tcx.hir_crate_items(()).definitions() {
+        if !matches!(tcx.def_kind(local), DefKind::Fn | DefKind::AssocFn) {
+            continue;
+        }
+        let def_id = local.to_def_id();
+        let overlay = overlay_path(tcx, def_id);
+        // Local paths are printed without the crate name.
+        let visible = format!("{krate}::{}", tcx.def_path_str(def_id));
+        if visible != overlay {
+            items.entry(visible).or_default().push(local);
+        }
+        items.entry(overlay).or_default().push(local);
+    }
+
+    let mut report = Report::default();
+    // Tags on each item, merged from entries on it.
+    let mut item_tags = FxIndexMap::<LocalDefId, Vec<String>>::default();
+    for (path, tags) in &mapping {
+        let Some(locals) = items.get(&**path) else {
+            if path.split("::").next() == Some(krate.as_str()) {
+                report.not_found.push(path.to_string());
+            } else {
+                report.other_crates += 1;
+            }
+            continue;
+        };
+        let tags = render_tags(tags, path, &mut report.invalid);
+        if tags.is_empty() {
+            continue;
+        }
+        for &local in locals {
+            if tcx.hir_span(tcx.local_def_id_to_hir_id(local)).from_expansion() {
+                report.in_macro.push(path.to_string());
+                continue;
+            }
+            let merged = item_tags.entry(local).or_default();
+            for tag in &tags {
+                if !merged.contains(tag) {
+                    merged.push(tag.clone());
+                }
+            }
+        }
+    }
+
+    let mut edits = FxIndexMap::<PathBuf, Vec<Edit>>::default();
+    let sm = tcx.sess.source_map();
+    for (local, tags) in item_tags {
+        let span = tcx.hir_span(tcx.local_def_id_to_hir_id(local));
+        let pos = sm.lookup_byte_offset(span.lo());
+        let FileName::Real(RealFileName::LocalPath(file)) = &pos.sf.name else { continue };
+        let src = pos.sf.src.clone();
+        edits.entry(file.clone()).or_default().push(Edit { offset: pos.pos.0 as usize, tags, src });
+    }
+
+    for (file, mut edits) in edits {
+        let mut text =
+            fs::read_to_string(&file).with_context(|| format!("Failed to read {file:?}"))?;
+        // Positions are in the source normalized by rustc, e.g. without BOM or CRLF, or the
+        // file has changed since compilation started.
+        if edits[0].src.as_deref() != Some(&text) {
+            report.skipped_files.push(file.display().to_string());
+            continue;
+        }
+        // Edit from the end to keep offsets of former items valid.
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.offset));
+        for edit in &edits {
+            match apply_to_item(&mut text, edit.offset, &edit.tags) {
+                Outcome::Inserted => report.inserted += 1,
+                Outcome::Updated => report.updated += 1,
+                Outcome::UpToDate => report.up_to_date += 1,
+            }
+        }
+        fs::write(&file, text).with_context(|| format!("Failed to write {file:?}"))?;
+    }
+
+    report.print(krate.as_str());
+    Ok(())
+}
+
+struct Edit {
+    /// Byte offset of the item in the file.
+    offset: usize,
+    tags: Vec<String>,
+    /// Source text seen by rustc.
+    src: Option<std::sync::Arc<String>>,
+}
+
+#[derive(Default)]
+struct Report {
+    inserted: usize,
+    updated: usize,
+    up_to_date: usize,
+    other_crates: usize,
+    not_found: Vec<String>,
+    in_macro: Vec<String>,
+    invalid: Vec<String>,
+    skipped_files: Vec<String>,
+}
+
+impl Report {
+    fn print(&self, krate: &str) {
+        let Report { inserted, updated, up_to_date, other_crates, .. } = self;
+        eprintln!(
+            "[{krate}] Tags are applied: {inserted} inserted, {updated} updated, \
+             {up_to_date} up to date; {other_crates} entries on other crates are skipped."
+        );
+        let lists = [
+            ("Entries not found", &self.not_found),
+            ("Entries in macro expansions", &self.in_macro),
+            ("Invalid tags", &self.invalid),
+            (
+                "Files skipped since their text differs from the compiled source",
+                &self.skipped_files,
+            ),
+        ];
+        for (title, list) in lists {
+            if !list.is_empty() {
+                eprintln!("[{krate}] {title}:");
+                for item in list {
+                    eprintln!("  {item}");
+                }
+            }
+        }
+    }
+}
+
+/// Tags in attribute syntax. Tag names that aren't identifiers, e.g. `!Null` without renaming,
+/// are dropped and recorded.
+fn render_tags(tags: &OverlayTags, path: &str, invalid: &mut Vec<String>) -> Vec<String> {
+    let mut valid = |name: &str| {
+        let ok = is_ident(name);
+        if !ok {
+            invalid.push(format!("`{name}` on {path}"));
+        }
+        ok
+    };
+    let with_args = |name: &str| match tags.args.get(name) {
+        Some(args) => format!("{name}({})", args.join(", ")),
+        None => name.to_owned(),
+    };
+    let mut v: Vec<String> =
+        tags.vanilla.iter().filter(|name| valid(name)).map(|name| with_args(name)).collect();
+    for group in &tags.group_of_any {
+        let mut group: Vec<_> =
+            group.iter().filter(|name| valid(name)).map(|n| with_args(n)).collect();
+        match group.len() {
+            0 => (),
+            1 => v.push(group.pop().unwrap()),
+            _ => v.push(format!("any {{ {} }}", group.join(", "))),
+        }
+    }
+    v
+}
+
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Inserted,
+    Updated,
+    UpToDate,
+}
+
+/// Insert or update the attribute on the item starting at the offset.
+fn apply_to_item(text: &mut String, item: usize, tags: &[String]) -> Outcome {
+    let start = attrs_start(text, item);
+    let Some((attr_start, attr_end)) = find_attr(text, start, item) else {
+        let line_start = line_start(text, item);
+        let indent: String =
+            text[line_start..].chars().take_while(|c| *c == ' ' || *c == '\t').collect();
+        let attr = format!("{indent}#[{ATTR} {{ {} }}]\n", tags.join(", "));
+        text.insert_str(line_start, &attr);
+        return Outcome::Inserted;
+    };
+
+    let attr = &text[attr_start..attr_end];
+    let present = TagNames::parse(attr);
+    let missing: Vec<_> = tags
+        .iter()
+        .filter(|tag| {
+            let tag = TagNames::parse(&format!("#[{ATTR} {{ {tag} }}]"));
+            let names = tag.vanilla.iter().chain(tag.any.iter().flatten());
+            !names.clone().any(|name| present.vanilla.contains(name))
+                && !tag.any.iter().any(|group| present.any.contains(group))
+        })
+        .collect();
+    if missing.is_empty() {
+        return Outcome::UpToDate;
+    }
+
+    // The closing delimiter of args, i.e. `}` or `)` before `]`.
+    let inner = &attr[..attr.len() - 1];
+    let close = inner.trim_end().len() - 1;
+    let open = attr.find(['{', '(']).unwrap_or(close);
+    let body = &attr[open + 1..close];
+    let content_end = open + 1 + body.trim_end().len();
+    let needs_comma = !body.trim().is_empty() && !body.trim_end().ends_with(',');
+    let comma = if needs_comma { "," } else { "" };
+
+    let insertion = if body.contains('\n') {
+        // One tag per line with the indentation of the last tag.
+        let last_line = body.trim_end().rsplit('\n').next().unwrap_or_default();
+        let indent: String = last_line.chars().take_while(|c| *c == ' ' || *c == '\t').collect();
+        let lines: String = missing.iter().map(|tag| format!("\n{indent}{tag},")).collect();
+        format!("{comma}{lines}")
+    } else {
+        let sep = if body.trim().is_empty() { "" } else { " " };
+        let tags = missing.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", ");
+        format!("{comma}{sep}{tags}")
+    };
+    text.insert_str(attr_start + content_end, &insertion);
+    Outcome::Updated
+}
+
+fn line_start(text: &str, pos: usize) -> usize {
+    text[..pos].rfind('\n').map_or(0, |idx| idx + 1)
+}
+
+/// The start of attributes and comments above the item, including lines inside a multi-line
+/// attribute.
+fn attrs_start(text: &str, item: usize) -> usize {
+    let mut start = line_start(text, item);
+    // `[` minus `]` from `start` to the item, which is negative inside an attribute.
+    let mut balance = bracket_balance(&text[start..item]);
+    while start > 0 {
+        let prev = line_start(text, start - 1);
+        let line = text[prev..start].trim();
+        let line_balance = balance + bracket_balance(line);
+        if line_balance < 0 || line.starts_with("#[") || line.starts_with("//") {
+            balance = line_balance;
+            start = prev;
+        } else {
+            break;
+        }
+    }
+    start
+}
+
+fn bracket_balance(s: &str) -> i32 {
+    s.chars().map(|c| (c == '[') as i32 - (c == ']') as i32).sum()
+}
+
+/// The range of the first `#[safety::requires]` between `start` and the item.
+fn find_attr(text: &str, start: usize, item: usize) -> Option<(usize, usize)> {
+    let mut pos = start;
+    while let Some(idx) = text[pos..item].find("#[") {
+        let attr_start = pos + idx;
+        let attr_end = attr_end(text, attr_start)?;
+        // Attributes in comments, e.g. doc examples, aren't preceded by only whitespace.
+        let before = &text[line_start(text, attr_start)..attr_start];
+        let is_attr = before.trim().is_empty() || before.trim_end().ends_with(']');
+        if is_attr && text[attr_start + 2..].trim_start().starts_with(ATTR) {
+            return Some((attr_start, attr_end));
+        }
+        pos = attr_start + 2;
+    }
+    None
+}
+
+/// The end of the attribute starting at `#[`, skipping brackets in string literals.
+fn attr_end(text: &str, attr_start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_str = false;
+    let mut escaped = false;
+    for (idx, c) in text[attr_start..].char_indices() {
+        if in_str {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_str = false,
+                _ => (),
+            }
+            continue;
+        }
+        match c {
+            '"' => in_str = true,
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(attr_start + idx + 1);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Top-level tag names in an attribute, and sorted names in each `any` group.
+///
+/// Tags are scanned from tokens rather than parsed as properties, because the latter panics on
+/// tags that are not defined in spec TOML.
+struct TagNames {
+    vanilla: Vec<String>,
+    any: Vec<Vec<String>>,
+}
+
+impl TagNames {
+    fn parse(attr: &str) -> Self {
+        let mut names = TagNames { vanilla: Vec::new(), any: Vec::new() };
+        let Ok(attrs) = Attribute::parse_outer.parse_str(attr) else { return names };
+        let Some(Meta::List(list)) = attrs.into_iter().next().map(|attr| attr.meta) else {
+            return names;
+        };
+        for (name, group) in scan_tags(list.tokens) {
+            match group {
+                Some(group) if name == ANY => {
+                    let mut group: Vec<_> =
+                        scan_tags(group).into_iter().map(|(name, _)| name).collect();
+                    group.sort_unstable();
+                    names.any.push(group);
+                }
+                _ => names.vanilla.push(name),
+            }
+        }
+        names
+    }
+}
+
+/// Tag names separated by `,` or `;`, with the braced args following a name, if any. A typed
+/// tag like `precond.Align` is named by its last segment, and `name = ..;` is skipped.
+fn scan_tags(tokens: TokenStream) -> Vec<(String, Option<TokenStream>)> {
+    let mut tags = Vec::<(String, Option<TokenStream>)>::new();
+    let mut expect_name = true;
+    for tt in tokens {
+        match tt {
+            TokenTree::Punct(p) if matches!(p.as_char(), ',' | ';') => expect_name = true,
+            TokenTree::Punct(p) if p.as_char() == '.' => {
+                tags.pop();
+                expect_name = true;
+            }
+            TokenTree::Punct(p) if p.as_char() == '=' => {
+                tags.pop();
+                expect_name = false;
+            }
+            TokenTree::Ident(ident) if expect_name => {
+                tags.push((ident.to_string(), None));
+                expect_name = false;
+            }
+            TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+                if let Some((_, args @ None)) = tags.last_mut() {
+                    *args = Some(group.stream());
+                }
+            }
+            _ => (),
+        }
+    }
+    tags
+}
+
+#[test]
+fn test_apply_to_item() {
+    let tags = ["ValidPtr".to_owned(), "any { Size, ValidSlice }".to_owned()];
+    let apply = |src: &str| {
+        let mut text = src.to_owned();
+        let item = text.find("pub").unwrap();
+        let outcome = apply_to_item(&mut text, item, &tags);
+        (outcome, text)
+    };
+
+    let (outcome, text) =
+        apply("mod m {\n    /// Doc [link].\n    #[inline]\n    pub unsafe fn f() {}\n}\n");
+    assert_eq!(outcome, Outcome::Inserted);
+    assert_eq!(
+        text,
+        "mod m {\n    /// Doc [link].\n    #[inline]\n    \
+         #[safety::requires { ValidPtr, any { Size, ValidSlice } }]\n    pub unsafe fn f() {}\n}\n"
+    );
+
+    let (outcome, text) = apply("#[safety::requires { Align(p, T) }]\npub unsafe fn f() {}\n");
+    assert_eq!(outcome, Outcome::Updated);
+    assert_eq!(
+        text,
+        "#[safety::requires { Align(p, T), ValidPtr, any { Size, ValidSlice } }]\n\
+         pub unsafe fn f() {}\n"
+    );
+
+    let src = "#[safety::requires {\n    ValidPtr(p, T, 1),\n    RustdocLinkToItem(\"[x]\")\n}]\n\
+               #[inline]\npub unsafe fn f() {}\n";
+    let (outcome, text) = apply(src);
+    assert_eq!(outcome, Outcome::Updated);
+    assert_eq!(
+        text,
+        "#[safety::requires {\n    ValidPtr(p, T, 1),\n    RustdocLinkToItem(\"[x]\"),\n    \
+         any { Size, ValidSlice },\n}]\n#[inline]\npub unsafe fn f() {}\n"
+    );
+    // Applying again changes nothing.
+    let (outcome, again) = {
+        let mut again = text.clone();
+        let item = again.find("pub").unwrap();
+        (apply_to_item(&mut again, item, &tags), again)
+    };
+    assert_eq!(outcome, Outcome::UpToDate);
+    assert_eq!(again, text);
+
+    // Arg names and tags in other `any` groups aren't present at the top level.
+    let (outcome, text) = apply(
+        "#[safety::requires { Align(ValidPtr, T), any { Size, Init } }]\npub unsafe fn f() {}\n",
+    );
+    assert_eq!(outcome, Outcome::Updated);
+    assert_eq!(
+        text,
+        "#[safety::requires { Align(ValidPtr, T), any { Size, Init }, ValidPtr, \
+         any { Size, ValidSlice } }]\npub unsafe fn f() {}\n"
+    );
+    // The same group in another order is present, and so is a group with a top-level tag.
+    for attr in ["ValidPtr, any { ValidSlice, Size }", "ValidPtr(p, T, 1), Size(T, any)"] {
+        let (outcome, _) =
+            apply(&format!("#[safety::requires {{ {attr} }}]\npub unsafe fn f() {{}}\n"));
+        assert_eq!(outcome, Outcome::UpToDate);
+    }
+}
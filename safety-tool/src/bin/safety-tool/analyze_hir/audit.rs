@@ -0,0 +1,109 @@
+//! Audit exported unsafe functions against tags forwarded from unsafe calls in their bodies.
+use super::{
+    db::TagState,
+    inventory::{doc, location},
+    overlay_path,
+    stat::{Func, is_unsafe},
+};
+use rustc_data_structures::fx::FxIndexMap;
+use rustc_hir::def_id::{DefId, LocalDefId};
+use rustc_middle::ty::TyCtxt;
+use safety_tool::{
+    audit::{Audit, Discrepancy, Missing},
+    stat::{Krate, TagType},
+};
+
+/// Tags of calls in a body which aren't discharged inside the body, and the callees requiring
+/// them. Each is a group of alternatives, and a vanilla tag is a group of itself.
+#[derive(Default)]
+pub struct Forwarded {
+    map: FxIndexMap<Vec<Box<str>>, Vec<DefId>>,
+}
+
+impl Forwarded {
+    pub fn record(&mut self, callee: DefId, state: &TagState) {
+        for group in state.pending() {
+            let group = group.iter().map(|sp| sp.name().into()).collect();
+            let callees = self.map.entry(group).or_default();
+            if !callees.contains(&callee) {
+                callees.push(callee);
+            }
+        }
+    }
+}
+
+/// Record the caller if it's an exported unsafe function missing forwarded tags.
+pub fn check(
+    tcx: TyCtxt,
+    local: LocalDefId,
+    caller: &Func,
+    forwarded: Forwarded,
+    audit: &mut Audit,
+) {
+    let def_id = local.to_def_id();
+    if forwarded.map.is_empty()
+        || !is_unsafe(def_id, tcx)
+        || !tcx.effective_visibilities(()).is_exported(local)
+    {
+        return;
+    }
+
+    let mut declared = Vec::<String>::new();
+    for tag in &caller.tags {
+        let names = match &tag.tag {
+            TagType::Vanilla(prop) => vec![prop.tag.name()],
+            TagType::Any(props) => {
+                props.iter().flat_map(|p| &p.tags).map(|p| p.tag.name()).collect()
+            }
+        };
+        for name in names {
+            if !declared.iter().any(|d| d == name) {
+                declared.push(name.to_owned());
+            }
+        }
+    }
+
+    let hir_id = tcx.local_def_id_to_hir_id(local);
+    let doc = doc(tcx, hir_id);
+    let missing: Vec<_> = forwarded
+        .map
+        .into_iter()
+        .filter(|(group, _)| !group.iter().any(|name| declared.iter().any(|d| **d == **name)))
+        .map(|(group, callees)| Missing {
+            in_doc: group.iter().any(|name| mentions(&doc, name)),
+            tags: group.into_iter().map(Into::into).collect(),
+            callees: callees.into_iter().map(|callee| tcx.def_path_str(callee)).collect(),
+        })
+        .collect();
+    if missing.is_empty() {
+        return;
+    }
+
+    let discrepancy = Discrepancy {
+        api: tcx.def_path_str(def_id),
+        declared,
+        missing,
+        location: location(tcx, hir_id),
+    };
+    audit.map.entry(overlay_path(tcx, def_id)).or_default().push(discrepancy);
+}
+
+/// Whether the doc mentions the tag name as a word.
+fn mentions(doc: &str, name: &str) -> bool {
+    doc.split(|c: char| !c.is_alphanumeric() && c != '_').any(|word| word == name)
+}
+
+/// Write the audit when `SP_OUT_DIR` is set and there is any discrepancy.
+pub fn write_to_file(audit: &Audit, krate: &Krate) {
+    if audit.map.is_empty() {
+        return;
+    }
+    if let Some(path) = krate.output_file_path("audit.json")
+        && let Ok(file) = std::fs::File::create(path)
+    {
+        _ = serde_json::to_writer_pretty(file, audit);
+    }
+    if let Some(path) = krate.output_file_path("audit.md") {
+        _ = std::fs::write(path, audit.to_markdown());
+    }
+}
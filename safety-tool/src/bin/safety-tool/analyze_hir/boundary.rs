@@ -0,0 +1,175 @@
+//! Collect safety boundaries of safe public functions through functions reached in MIR.
+//!
+//! Functions are reached through monomorphized instances, so a generic function is a root
+//! only if it's instantiated in the crate, e.g. called by a non-generic function.
+//!
+//! Closures are walked through as part of the function defining them: unsafe calls in a closure
+//! are discharged by the enclosing function in HIR, so a closure is never a boundary.
+use super::{Analysis, Callees, db::ToolAttrs, inventory::location, stat::is_unsafe};
+use rustc_data_structures::fx::{FxHashMap, FxHashSet};
+use rustc_hir::{def::DefKind, def_id::DefId};
+use rustc_middle::ty::TyCtxt;
+use safety_tool::{
+    boundary::{Boundaries, Boundary, Obligation, TaggedCallee},
+    stat::{Func, TagType},
+};
+
+/// Write boundaries when `SP_OUT_DIR` is set and any safe public function reaches tagged
+/// unsafe functions.
+pub fn write_to_file(tcx: TyCtxt, analysis: &mut Analysis, callees: &Callees) {
+    let krate = &analysis.stat.krate;
+    let Some(json) = krate.output_file_path("boundary.json") else { return };
+    let Some(txt) = krate.output_file_path("boundary.txt") else { return };
+    let boundaries = collect(tcx, analysis, callees);
+    if boundaries.map.is_empty() {
+        return;
+    }
+    if let Ok(file) = std::fs::File::create(json) {
+        _ = serde_json::to_writer_pretty(file, &boundaries);
+    }
+    _ = std::fs::write(txt, boundaries.to_trees());
+}
+
+fn collect(tcx: TyCtxt, analysis: &mut Analysis, callees: &Callees) -> Boundaries {
+    let funcs: FxHashMap<_, _> = analysis.stat.funcs.iter().map(|f| (&*f.name, f)).collect();
+    let tool_attrs = &mut analysis.tool_attrs;
+    let mut walk = Walk { tcx, callees, funcs, tool_attrs, required: FxHashMap::default() };
+
+    let mut boundaries = Boundaries::default();
+    for &root in callees.keys() {
+        let Some(local) = root.as_local() else { continue };
+        if !is_fn(tcx, root) || is_unsafe(root, tcx) || !tcx.visibility(root).is_public() {
+            continue;
+        }
+        let mut found = Vec::new();
+        let mut visited = FxHashSet::from_iter([root]);
+        walk.walk(&mut vec![root], &mut visited, &mut found);
+        if !found.is_empty() {
+            let location = location(tcx, tcx.local_def_id_to_hir_id(local));
+            boundaries.map.insert(tcx.def_path_str(root), Boundary { location, callees: found });
+        }
+    }
+    boundaries.map.sort_unstable_by(|_, a, _, b| a.location.cmp(&b.location));
+    boundaries
+}
+
+fn is_fn(tcx: TyCtxt, def_id: DefId) -> bool {
+    matches!(tcx.def_kind(def_id), DefKind::Fn | DefKind::AssocFn)
+}
+
+fn is_closure(tcx: TyCtxt, def_id: DefId) -> bool {
+    tcx.def_kind(def_id) == DefKind::Closure
+}
+
+struct Walk<'a, 'tcx> {
+    tcx: TyCtxt<'tcx>,
+    callees: &'a Callees,
+    /// Local functions with discharges at their unsafe calls.
+    funcs: FxHashMap<&'a str, &'a Func>,
+    tool_attrs: &'a mut ToolAttrs,
+    /// Cache of tags required by functions.
+    required: FxHashMap<DefId, Vec<Vec<Box<str>>>>,
+}
+
+impl<'a> Walk<'a, '_> {
+    /// Record tagged unsafe callees reached by the last function in the path, and go down
+    /// local functions not visited.
+    fn walk(
+        &mut self,
+        path: &mut Vec<DefId>,
+        visited: &mut FxHashSet<DefId>,
+        found: &mut Vec<TaggedCallee>,
+    ) {
+        let callees = self.callees;
+        let Some(reached) = callees.get(path.last().unwrap()) else { return };
+        for &callee in reached {
+            let is_fn = is_fn(self.tcx, callee);
+            if path.contains(&callee) || !(is_fn || is_closure(self.tcx, callee)) {
+                continue;
+            }
+            if is_fn && is_unsafe(callee, self.tcx) && !self.required(callee).is_empty() {
+                let tagged = self.tagged_callee(path, callee);
+                let is_recorded = found
+                    .iter()
+                    .any(|t| t.callee == tagged.callee && t.boundary == tagged.boundary);
+                if !is_recorded {
+                    found.push(tagged);
+                }
+            }
+            if callee.is_local() && visited.insert(callee) {
+                path.push(callee);
+                self.walk(path, visited, found);
+                path.pop();
+            }
+        }
+    }
+
+    fn required(&mut self, def_id: DefId) -> &[Vec<Box<str>>] {
+        let (tcx, tool_attrs) = (self.tcx, &mut *self.tool_attrs);
+        self.required.entry(def_id).or_insert_with(|| tool_attrs.required(def_id, tcx))
+    }
+
+    /// Tags of the callee are discharged by the nearest safe caller in the path, at the call
+    /// to the callee, or to the first unsafe function forwarding them through its own tags.
+    /// Closures in the path are skipped, since their calls belong to the enclosing function.
+    fn tagged_callee(&mut self, path: &[DefId], callee: DefId) -> TaggedCallee {
+        let tcx = self.tcx;
+        let fns: Vec<_> = path.iter().copied().filter(|&f| !is_closure(tcx, f)).collect();
+        // The root is safe, so there is always a boundary.
+        let idx = fns.iter().rposition(|&f| !is_unsafe(f, tcx)).unwrap();
+        let boundary = tcx.def_path_str(fns[idx]);
+        let forwarding = &fns[idx + 1..];
+        let next = tcx.def_path_str(forwarding.first().copied().unwrap_or(callee));
+
+        let discharged = self.discharged_at(&boundary, &next);
+        let mut declared = Vec::with_capacity(forwarding.len());
+        for &f in forwarding {
+            declared.push(self.required(f).iter().flatten().cloned().collect());
+        }
+        let declared: Vec<FxHashSet<Box<str>>> = declared;
+
+        let obligations = self
+            .required(callee)
+            .iter()
+            .map(|group| Obligation {
+                tags: group.iter().map(|tag| tag.to_string()).collect(),
+                discharged: group.iter().any(|tag| {
+                    discharged.contains(&**tag) && declared.iter().all(|d| d.contains(tag))
+                }),
+            })
+            .collect();
+
+        TaggedCallee {
+            callee: tcx.def_path_str(callee),
+            via: path[1..].iter().map(|&f| tcx.def_path_str(f)).collect(),
+            boundary,
+            obligations,
+        }
+    }
+
+    /// Tags discharged at every call from the caller to the callee.
+    fn discharged_at(&self, caller: &str, callee: &str) -> FxHashSet<&'a str> {
+        let Some(caller) = self.funcs.get(caller) else { return FxHashSet::default() };
+        let mut calls = caller.unsafe_calls.iter().filter(|call| call.name == callee);
+        let Some(first) = calls.next() else { return FxHashSet::default() };
+        let mut discharged = tag_names(first);
+        for call in calls {
+            let names = tag_names(call);
+            discharged.retain(|name| names.contains(name));
+        }
+        discharged
+    }
+}
+
+fn tag_names(call: &Func) -> FxHashSet<&str> {
+    let mut names = FxHashSet::default();
+    for tag in &call.tags {
+        match &tag.tag {
+            TagType::Vanilla(prop) => _ = names.insert(prop.tag.name()),
+            TagType::Any(props) => {
+                names.extend(props.iter().flat_map(|p| &p.tags).map(|p| p.tag.name()))
+            }
+        }
+    }
+    names
+}
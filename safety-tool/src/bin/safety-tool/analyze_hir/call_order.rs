@@ -0,0 +1,327 @@
+//! Call-order tags are checked through the call graph of the local crate built from MIR:
+//! * `PostToFunc(f)`: the tagged function can only be called after `f` has been called.
+//! * `NotPostToFunc(f)`: the tagged function must not be called after `f` has been called.
+//! * `CallOnce`: the tagged function can only be called once, i.e. from a single site out of loops.
+//! * `CalledBy(env)`: the tagged function can only be called by `env`, a function or a macro.
+//!
+//! Calls through closures, function pointers, or trait objects are not in the call graph, thus
+//! a function without direct callers is treated as an entry point.
+use super::{def_path_matches, diagnostics::EmitDiagnostics, required_properties};
+use rustc_data_structures::fx::{FxHashMap, FxHashSet};
+use rustc_hir::{
+    def::DefKind,
+    def_id::{DefId, LocalDefId},
+};
+use rustc_middle::{
+    mir::{BasicBlock, Body, TerminatorKind},
+    ty::TyCtxt,
+};
+use rustc_span::{ExpnKind, Span};
+
+const POST_TO_FUNC: &str = "PostToFunc";
+const NOT_POST_TO_FUNC: &str = "NotPostToFunc";
+const CALL_ONCE: &str = "CallOnce";
+const CALLED_BY: &str = "CalledBy";
+
+pub fn check_call_order(tcx: TyCtxt, diagnostics: &mut EmitDiagnostics) {
+    let mut graph = CallGraph::new(tcx);
+    let mut call_once_checked = FxHashSet::default();
+
+    for idx in 0..graph.sites.len() {
+        let site = graph.sites[idx];
+        for (name, arg) in graph.order_tags(site.callee) {
+            match &*name {
+                POST_TO_FUNC => graph.check_post_to_func(site, &arg, diagnostics),
+                NOT_POST_TO_FUNC => graph.check_not_post_to_func(site, &arg, diagnostics),
+                CALLED_BY => graph.check_called_by(site, &arg, diagnostics),
+                CALL_ONCE if call_once_checked.insert(site.callee) => {
+                    graph.check_call_once(site.callee, diagnostics)
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct CallSite {
+    caller: LocalDefId,
+    /// The block whose terminator is the call.
+    bb: BasicBlock,
+    callee: DefId,
+    span: Span,
+}
+
+struct CallGraph<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    bodies: FxHashMap<LocalDefId, &'tcx Body<'tcx>>,
+    /// Call sites in the order of bodies and blocks.
+    sites: Vec<CallSite>,
+    /// Call-order tags as (name, first arg) on callees.
+    tags: FxHashMap<DefId, Vec<(String, String)>>,
+    /// Whether a function matching the path has been called before each block.
+    called_before: FxHashMap<(LocalDefId, String, bool), Vec<bool>>,
+    /// Whether a function matching the path is called on all paths (true) or some path (false)
+    /// to return of the function.
+    summaries: FxHashMap<(DefId, String, bool), bool>,
+}
+
+impl<'tcx> CallGraph<'tcx> {
+    fn new(tcx: TyCtxt<'tcx>) -> Self {
+        let mut bodies = FxHashMap::default();
+        let mut sites = Vec::new();
+
+        for local in tcx.hir_body_owners() {
+            if !matches!(tcx.def_kind(local), DefKind::Fn | DefKind::AssocFn)
+                || !tcx.is_mir_available(local)
+            {
+                continue;
+            }
+            // MIR inlining is disabled in `main`, so calls are preserved at any opt-level.
+            let body = tcx.optimized_mir(local);
+            for (bb, data) in body.basic_blocks.iter_enumerated() {
+                if let TerminatorKind::Call { func, fn_span, .. } = &data.terminator().kind
+                    && let Some((callee, _)) = func.const_fn_def()
+                {
+                    sites.push(CallSite { caller: local, bb, callee, span: *fn_span });
+                }
+            }
+            bodies.insert(local, body);
+        }
+
+        CallGraph {
+            tcx,
+            bodies,
+            sites,
+            tags: Default::default(),
+            called_before: Default::default(),
+            summaries: Default::default(),
+        }
+    }
+
+    /// Call-order tags required by the function.
+    fn order_tags(&mut self, def_id: DefId) -> Vec<(String, String)> {
+        let tcx = self.tcx;
+        self.tags
+            .entry(def_id)
+            .or_insert_with(|| {
+                let mut v = Vec::new();
+                for tag in required_properties(tcx, def_id) {
+                    let name = tag.tag.name();
+                    if [POST_TO_FUNC, NOT_POST_TO_FUNC, CALL_ONCE, CALLED_BY].contains(&name) {
+                        let arg = tag.args_as_str().into_iter().next().unwrap_or_default();
+                        v.push((name.to_owned(), arg.replace(' ', "")));
+                    }
+                }
+                v
+            })
+            .clone()
+    }
+
+    fn has_tag(&mut self, def_id: DefId, name: &str, arg: &str) -> bool {
+        self.order_tags(def_id).iter().any(|(n, a)| n == name && a == arg)
+    }
+
+    fn name(&self, def_id: impl Into<DefId>) -> String {
+        self.tcx.def_path_str(def_id.into())
+    }
+
+    fn matches_path(&self, def_id: DefId, path: &str) -> bool {
+        def_path_matches(self.tcx, def_id, path)
+    }
+
+    /// Whether the call to the callee calls a function matching the path, directly or
+    /// through the callee's body on all paths (must) or some path (may).
+    fn calls(&mut self, callee: DefId, path: &str, must: bool) -> bool {
+        if self.matches_path(callee, path) {
+            return true;
+        }
+        let Some(local) = callee.as_local().filter(|local| self.bodies.contains_key(local)) else {
+            return false;
+        };
+        let key = (callee, path.to_owned(), must);
+        if let Some(&called) = self.summaries.get(&key) {
+            return called;
+        }
+        // Assume not called for recursive calls.
+        self.summaries.insert(key.clone(), false);
+
+        let called = if must {
+            let before = self.compute_called_before(local, path, true);
+            let body = self.bodies[&local];
+            let returns: Vec<_> = body
+                .basic_blocks
+                .iter_enumerated()
+                .filter(|(_, data)| matches!(data.terminator().kind, TerminatorKind::Return))
+                .map(|(bb, _)| bb)
+                .collect();
+            !returns.is_empty() && returns.iter().all(|bb| before[bb.as_usize()])
+        } else {
+            self.sites_in(local).iter().any(|site| self.calls(site.callee, path, false))
+        };
+        self.summaries.insert(key, called);
+        called
+    }
+
+    fn sites_in(&self, caller: LocalDefId) -> Vec<CallSite> {
+        self.sites.iter().filter(|site| site.caller == caller).copied().collect()
+    }
+
+    /// Whether a function matching the path has been called before the terminator of the block
+    /// on all paths (must) or some path (may) from the entry.
+    fn called_before(&mut self, site: CallSite, path: &str, must: bool) -> bool {
+        self.compute_called_before(site.caller, path, must)[site.bb.as_usize()]
+    }
+
+    fn compute_called_before(&mut self, caller: LocalDefId, path: &str, must: bool) -> Vec<bool> {
+        let key = (caller, path.to_owned(), must);
+        if let Some(v) = self.called_before.get(&key) {
+            return v.clone();
+        }
+
+        let body = self.bodies[&caller];
+        let blocks = &body.basic_blocks;
+        // Whether each block calls a matching function at its terminator.
+        let gen_: Vec<bool> = blocks
+            .iter()
+            .map(|data| match &data.terminator().kind {
+                TerminatorKind::Call { func, .. } => {
+                    func.const_fn_def().is_some_and(|(callee, _)| {
+                        callee != caller.to_def_id() && self.calls(callee, path, must)
+                    })
+                }
+                _ => false,
+            })
+            .collect();
+
+        // Must analysis starts from top except the entry, and may analysis starts from bottom.
+        let mut state = vec![must; blocks.len()];
+        state[0] = false;
+        let preds = blocks.predecessors();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for bb in blocks.indices().skip(1) {
+                let mut ins = preds[bb].iter().map(|p| state[p.as_usize()] || gen_[p.as_usize()]);
+                let new = if must { ins.all(|b| b) } else { ins.any(|b| b) };
+                if new != state[bb.as_usize()] {
+                    state[bb.as_usize()] = new;
+                    changed = true;
+                }
+            }
+        }
+
+        self.called_before.insert(key, state.clone());
+        state
+    }
+
+    /// Find a chain of callers from an entry point to the function, on which the function
+    /// can be entered without calling `path` first.
+    fn unguarded_path(
+        &mut self,
+        fun: LocalDefId,
+        path: &str,
+        visiting: &mut FxHashSet<LocalDefId>,
+    ) -> Option<Vec<LocalDefId>> {
+        if !visiting.insert(fun) || self.has_tag(fun.to_def_id(), POST_TO_FUNC, path) {
+            // The requirement is propagated to callers of the function.
+            return None;
+        }
+        let callers: Vec<_> =
+            self.sites.iter().filter(|site| site.callee == fun.to_def_id()).copied().collect();
+        if callers.is_empty() {
+            return Some(vec![fun]);
+        }
+        for site in callers {
+            if !self.called_before(site, path, true)
+                && let Some(mut chain) = self.unguarded_path(site.caller, path, visiting)
+            {
+                chain.push(fun);
+                return Some(chain);
+            }
+        }
+        None
+    }
+
+    fn check_post_to_func(&mut self, site: CallSite, path: &str, diag: &mut EmitDiagnostics) {
+        if self.called_before(site, path, true) {
+            return;
+        }
+        if let Some(chain) = self.unguarded_path(site.caller, path, &mut FxHashSet::default()) {
+            let title = format!(
+                "`{}` can only be called after `{path}` has been called ({POST_TO_FUNC})",
+                self.name(site.callee)
+            );
+            let chain =
+                chain.into_iter().map(|f| format!("`{}`", self.name(f))).collect::<Vec<_>>();
+            let info =
+                [format!("Reachable without calling `{path}` through {}.", chain.join(" -> "))];
+            diag.push_call_order(site.caller, site.span, &title, &info);
+        }
+    }
+
+    fn check_not_post_to_func(&mut self, site: CallSite, path: &str, diag: &mut EmitDiagnostics) {
+        if self.called_before(site, path, false) {
+            let title = format!(
+                "`{}` must not be called after `{path}` has been called ({NOT_POST_TO_FUNC})",
+                self.name(site.callee)
+            );
+            let info = [format!("`{path}` may be called before this call.")];
+            diag.push_call_order(site.caller, site.span, &title, &info);
+        }
+    }
+
+    fn check_called_by(&mut self, site: CallSite, env: &str, diag: &mut EmitDiagnostics) {
+        let by_macro = site.span.macro_backtrace().any(|expn| {
+            matches!(expn.kind, ExpnKind::Macro(_, name) if env.rsplit("::").next() == Some(name.as_str()))
+        });
+        let caller = site.caller.to_def_id();
+        if by_macro || self.matches_path(caller, env) || self.has_tag(caller, CALLED_BY, env) {
+            return;
+        }
+        let title =
+            format!("`{}` can only be called by `{env}` ({CALLED_BY})", self.name(site.callee));
+        let info = [format!("It's called by `{}`.", self.name(caller))];
+        diag.push_call_order(site.caller, site.span, &title, &info);
+    }
+
+    fn check_call_once(&mut self, callee: DefId, diag: &mut EmitDiagnostics) {
+        let sites: Vec<_> =
+            self.sites.iter().filter(|site| site.callee == callee).copied().collect();
+        let name = self.name(callee);
+        let src_map = self.tcx.sess.source_map();
+
+        for (idx, site) in sites.iter().enumerate() {
+            if in_loop(self.bodies[&site.caller], site.bb) {
+                let title = format!(
+                    "`{name}` can only be called once, but it's called in a loop ({CALL_ONCE})"
+                );
+                diag.push_call_order(site.caller, site.span, &title, &[]);
+            } else if idx != 0 {
+                let title = format!(
+                    "`{name}` can only be called once, but it's called at {} sites ({CALL_ONCE})",
+                    sites.len()
+                );
+                let first = src_map.span_to_diagnostic_string(sites[0].span);
+                let info = [format!("It's first called at {first}.")];
+                diag.push_call_order(site.caller, site.span, &title, &info);
+            }
+        }
+    }
+}
+
+/// Whether the block is in a cycle of the control flow graph.
+fn in_loop(body: &Body, bb: BasicBlock) -> bool {
+    let blocks = &body.basic_blocks;
+    let mut visited = FxHashSet::default();
+    let mut stack: Vec<_> = blocks[bb].terminator().successors().collect();
+    while let Some(next) = stack.pop() {
+        if next == bb {
+            return true;
+        }
+        if visited.insert(next) {
+            stack.extend(blocks[next].terminator().successors());
+        }
+    }
+    false
+}
@@ -0,0 +1,404 @@
+//! Tags with `eval` in spec are decided at the call site through constant evaluation of args in
+//! MIR, e.g. `ValidNum(count * sizeof(T), [0, isize::MAX])` with `eval = "range"`:
+//! * a param is the value passed to it, traced through locals, integer casts, and `+`, `-`, `*`
+//!   in the caller, and otherwise the range of its integer type. Locals borrowed mutably are not
+//!   traced, since they may be written through the borrow;
+//! * a const generic param is its value, or the range of its type;
+//! * `T::MAX`, `T::MIN`, and `sizeof(T)` are computed for the substituted type;
+//! * `+`, `-`, and `*` are computed over ranges.
+//!
+//! The tag is proven and auto-discharged if the value always lies within the range, and
+//! violated if never. Other cases require a discharge as usual.
+use super::{MirCall, mir_call, mutably_borrowed, required_properties};
+use rustc_hir::{HirId, def_id::DefId};
+use rustc_middle::{
+    mir::{
+        self, Body, CastKind, Const, Local, Operand, ProjectionElem, Rvalue, StatementKind,
+        TerminatorKind,
+    },
+    ty::{self, GenericArgsRef, ScalarInt, Ty, TyCtxt, TypingEnv},
+};
+use safety_parser::{
+    configuration::EvalKind,
+    safety::Property as SP,
+    syn::{BinOp, Expr, ExprLit, Lit, RangeLimits, UnOp},
+};
+use std::fmt;
+
+/// Traced locals are at most this deep.
+const MAX_DEPTH: usize = 16;
+
+/// A tag decided at the call site.
+pub struct Evaluated {
+    pub sp: SP,
+    pub proven: bool,
+    /// Why it's proven or violated.
+    pub reason: String,
+}
+
+impl Evaluated {
+    /// The tag with args at the call site and its description rendered with them.
+    pub fn info(&self) -> Vec<String> {
+        let tag = format!("{}({})", self.sp.tag.name(), self.sp.args_as_str().join(", "));
+        let desc = self.sp.gen_doc();
+        let desc = desc.as_deref().map_or("This SP has no description.", str::trim);
+        vec![format!("`{tag}`: {desc}")]
+    }
+}
+
+/// Evaluate tags with `eval` in spec on the call to the callee, and return decided ones.
+pub fn evaluate_tags(tcx: TyCtxt, hir_id: HirId, callee: DefId) -> Vec<Evaluated> {
+    let mut v = Vec::new();
+    let props: Vec<_> = required_properties(tcx, callee)
+        .into_iter()
+        .filter(|sp| sp.tag.get_spec().is_some_and(|spec| spec.eval.is_some()))
+        .collect();
+    if props.is_empty() {
+        return v;
+    }
+    let Some(MirCall { body, args, generic_args }) = mir_call(tcx, hir_id, callee) else {
+        return v;
+    };
+    let body_owner = tcx.hir_enclosing_body_owner(hir_id);
+    let typing_env = TypingEnv::post_analysis(tcx, body_owner);
+    let eval = Evaluator { tcx, typing_env, body, args, callee, generic_args };
+
+    for sp in props {
+        let Some(spec) = sp.tag.get_spec() else { continue };
+        match spec.eval {
+            Some(EvalKind::Range) => {
+                let [exp, range, ..] = &*sp.args else { continue };
+                let (Some(value), Some(range)) = (eval.expr(exp), eval.range(range)) else {
+                    continue;
+                };
+                let [exp, vrange, ..] = &sp.args_as_str()[..] else { continue };
+                if range.contains(value) {
+                    let reason = format!("because `{exp}` {value}, within `{vrange}`.");
+                    v.push(Evaluated { sp, proven: true, reason });
+                } else if range.disjoint(value) {
+                    let reason = format!("`{exp}` {value}, out of `{vrange}`");
+                    v.push(Evaluated { sp, proven: false, reason });
+                }
+            }
+            None => (),
+        }
+    }
+    v
+}
+
+/// A closed range of integers.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Interval {
+    lo: i128,
+    hi: i128,
+}
+
+impl Interval {
+    fn exact(v: i128) -> Self {
+        Interval { lo: v, hi: v }
+    }
+
+    fn contains(self, other: Self) -> bool {
+        self.lo <= other.lo && other.hi <= self.hi
+    }
+
+    fn disjoint(self, other: Self) -> bool {
+        other.hi < self.lo || self.hi < other.lo
+    }
+
+    fn binary(self, op: &BinOp, rhs: Self) -> Option<Self> {
+        self.arith(Arith::from_syn(op)?, rhs)
+    }
+
+    fn arith(self, op: Arith, rhs: Self) -> Option<Self> {
+        let (a, b, c, d) = (self.lo, self.hi, rhs.lo, rhs.hi);
+        match op {
+            Arith::Add => Some(Interval { lo: a.checked_add(c)?, hi: b.checked_add(d)? }),
+            Arith::Sub => Some(Interval { lo: a.checked_sub(d)?, hi: b.checked_sub(c)? }),
+            Arith::Mul => {
+                let v =
+                    [a.checked_mul(c)?, a.checked_mul(d)?, b.checked_mul(c)?, b.checked_mul(d)?];
+                Some(Interval { lo: *v.iter().min()?, hi: *v.iter().max()? })
+            }
+        }
+    }
+}
+
+/// Arithmetic supported over intervals, both in tag args and in MIR of the caller.
+#[derive(Clone, Copy)]
+enum Arith {
+    Add,
+    Sub,
+    Mul,
+}
+
+impl Arith {
+    fn from_syn(op: &BinOp) -> Option<Self> {
+        match op {
+            BinOp::Add(_) => Some(Arith::Add),
+            BinOp::Sub(_) => Some(Arith::Sub),
+            BinOp::Mul(_) => Some(Arith::Mul),
+            _ => None,
+        }
+    }
+
+    fn from_mir(op: mir::BinOp) -> Option<Self> {
+        use mir::BinOp::*;
+        match op {
+            Add | AddUnchecked | AddWithOverflow => Some(Arith::Add),
+            Sub | SubUnchecked | SubWithOverflow => Some(Arith::Sub),
+            Mul | MulUnchecked | MulWithOverflow => Some(Arith::Mul),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.lo == self.hi {
+            write!(f, "is {}", self.lo)
+        } else {
+            write!(f, "is in [{}, {}]", self.lo, self.hi)
+        }
+    }
+}
+
+struct Evaluator<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    typing_env: TypingEnv<'tcx>,
+    body: &'tcx Body<'tcx>,
+    args: Vec<&'tcx Operand<'tcx>>,
+    callee: DefId,
+    generic_args: GenericArgsRef<'tcx>,
+}
+
+impl<'tcx> Evaluator<'tcx> {
+    fn expr(&self, expr: &Expr) -> Option<Interval> {
+        match expr {
+            Expr::Lit(ExprLit { lit: Lit::Int(int), .. }) => {
+                int.base10_parse().ok().map(Interval::exact)
+            }
+            Expr::Paren(paren) => self.expr(&paren.expr),
+            Expr::Group(group) => self.expr(&group.expr),
+            Expr::Unary(unary) if matches!(unary.op, UnOp::Neg(_)) => {
+                let v = self.expr(&unary.expr)?;
+                Some(Interval { lo: v.hi.checked_neg()?, hi: v.lo.checked_neg()? })
+            }
+            Expr::Binary(binary) => {
+                let lhs = self.expr(&binary.left)?;
+                lhs.binary(&binary.op, self.expr(&binary.right)?)
+            }
+            Expr::Path(path) if path.qself.is_none() => {
+                let segments: Vec<_> =
+                    path.path.segments.iter().map(|seg| seg.ident.to_string()).collect();
+                match &segments[..] {
+                    [name] => self.param(name).or_else(|| self.const_param(name)),
+                    [ty, bound] => {
+                        let range = int_range(self.tcx, self.ty(ty)?)?;
+                        match &**bound {
+                            "MAX" => Some(Interval::exact(range.hi)),
+                            "MIN" => Some(Interval::exact(range.lo)),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                }
+            }
+            Expr::Call(call) if call.args.len() == 1 => {
+                let Expr::Path(func) = &*call.func else { return None };
+                let Expr::Path(ty) = &call.args[0] else { return None };
+                if !func.path.is_ident("sizeof") {
+                    return None;
+                }
+                let ty = self.ty(&ty.path.get_ident()?.to_string())?;
+                let layout = self.tcx.layout_of(self.typing_env.as_query_input(ty)).ok()?;
+                Some(Interval::exact(layout.size.bytes().into()))
+            }
+            _ => None,
+        }
+    }
+
+    /// A closed range like `[0, isize::MAX]`, or a Rust range like `0..len`.
+    fn range(&self, expr: &Expr) -> Option<Interval> {
+        let exact = |expr: &Expr| self.expr(expr).filter(|v| v.lo == v.hi).map(|v| v.lo);
+        match expr {
+            Expr::Array(array) if array.elems.len() == 2 => {
+                Some(Interval { lo: exact(&array.elems[0])?, hi: exact(&array.elems[1])? })
+            }
+            Expr::Range(range) => {
+                let lo = match &range.start {
+                    Some(start) => exact(start)?,
+                    None => i128::MIN,
+                };
+                let hi = match (&range.end, range.limits) {
+                    (Some(end), RangeLimits::Closed(_)) => exact(end)?,
+                    (Some(end), RangeLimits::HalfOpen(_)) => exact(end)?.checked_sub(1)?,
+                    (None, _) => i128::MAX,
+                };
+                Some(Interval { lo, hi })
+            }
+            Expr::Paren(paren) => self.range(&paren.expr),
+            _ => None,
+        }
+    }
+
+    /// The value of the arg passed to the param of the callee.
+    fn param(&self, name: &str) -> Option<Interval> {
+        let idx = self
+            .tcx
+            .fn_arg_idents(self.callee)
+            .iter()
+            .position(|ident| ident.is_some_and(|ident| ident.name.as_str() == name))?;
+        self.operand(self.args.get(idx)?, 0)
+    }
+
+    fn operand(&self, operand: &Operand<'tcx>, depth: usize) -> Option<Interval> {
+        let ty = operand.ty(&self.body.local_decls, self.tcx);
+        let traced = match operand {
+            Operand::Constant(constant) => self.constant(constant.const_),
+            Operand::Copy(place) | Operand::Move(place) if depth < MAX_DEPTH => {
+                let rvalue = self.unique_def(place.local);
+                match (place.projection.as_slice(), rvalue) {
+                    ([], Some(rvalue)) => self.rvalue(rvalue, depth + 1),
+                    // The value of a checked operation, which panics on overflow.
+                    ([ProjectionElem::Field(field, _)], Some(rvalue @ Rvalue::BinaryOp(..)))
+                        if field.as_u32() == 0 =>
+                    {
+                        self.rvalue(rvalue, depth + 1)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        // Values are always within the integer type, and fall back to its range.
+        let range = int_range(self.tcx, ty);
+        match (traced, range) {
+            (Some(traced), Some(range)) if range.contains(traced) => Some(traced),
+            _ => range,
+        }
+    }
+
+    /// Integer values computed by the rvalue.
+    fn rvalue(&self, rvalue: &'tcx Rvalue<'tcx>, depth: usize) -> Option<Interval> {
+        match rvalue {
+            Rvalue::Use(operand) | Rvalue::Cast(CastKind::IntToInt, operand, _) => {
+                self.operand(operand, depth)
+            }
+            Rvalue::BinaryOp(op, operands) => {
+                let lhs = self.operand(&operands.0, depth)?;
+                lhs.arith(Arith::from_mir(*op)?, self.operand(&operands.1, depth)?)
+            }
+            _ => None,
+        }
+    }
+
+    /// The rvalue of the unique assignment to the local in the body.
+    fn unique_def(&self, local: Local) -> Option<&'tcx Rvalue<'tcx>> {
+        if local.as_usize() <= self.body.arg_count || mutably_borrowed(self.body, local) {
+            return None;
+        }
+        let mut found = None;
+        let mut defs = 0;
+        for data in self.body.basic_blocks.iter() {
+            for stmt in &data.statements {
+                if let StatementKind::Assign(assign) = &stmt.kind
+                    && assign.0.local == local
+                {
+                    defs += 1;
+                    found = assign.0.projection.is_empty().then_some(&assign.1);
+                }
+            }
+            if let TerminatorKind::Call { destination, .. } = &data.terminator().kind
+                && destination.local == local
+            {
+                defs += 1;
+            }
+        }
+        if defs == 1 { found } else { None }
+    }
+
+    fn constant(&self, constant: Const<'tcx>) -> Option<Interval> {
+        let scalar = constant.try_eval_scalar_int(self.tcx, self.typing_env)?;
+        scalar_to_interval(scalar, constant.ty())
+    }
+
+    /// The value of a const generic param of the callee.
+    fn const_param(&self, name: &str) -> Option<Interval> {
+        let tcx = self.tcx;
+        let mut generics = tcx.generics_of(self.callee);
+        loop {
+            if let Some(param) = generics.own_params.iter().find(|p| p.name.as_str() == name) {
+                let ct = self.generic_args.get(param.index as usize)?.as_const()?;
+                let ct = tcx.try_normalize_erasing_regions(self.typing_env, ct).unwrap_or(ct);
+                if let Some(value) = ct.try_to_value()
+                    && let Some(scalar) = value.valtree.try_to_scalar_int()
+                {
+                    return scalar_to_interval(scalar, value.ty);
+                }
+                return int_range(tcx, tcx.type_of(param.def_id).instantiate_identity());
+            }
+            generics = tcx.generics_of(generics.parent?);
+        }
+    }
+
+    /// A generic param of the callee substituted by the call, or a primitive integer type.
+    fn ty(&self, name: &str) -> Option<Ty<'tcx>> {
+        let tcx = self.tcx;
+        let types = &tcx.types;
+        let prim = match name {
+            "u8" => types.u8,
+            "u16" => types.u16,
+            "u32" => types.u32,
+            "u64" => types.u64,
+            "u128" => types.u128,
+            "usize" => types.usize,
+            "i8" => types.i8,
+            "i16" => types.i16,
+            "i32" => types.i32,
+            "i64" => types.i64,
+            "i128" => types.i128,
+            "isize" => types.isize,
+            _ => {
+                let mut generics = tcx.generics_of(self.callee);
+                loop {
+                    if let Some(param) =
+                        generics.own_params.iter().find(|p| p.name.as_str() == name)
+                    {
+                        let ty = self.generic_args.get(param.index as usize)?.as_type()?;
+                        return tcx.try_normalize_erasing_regions(self.typing_env, ty).ok();
+                    }
+                    generics = tcx.generics_of(generics.parent?);
+                }
+            }
+        };
+        Some(prim)
+    }
+}
+
+fn scalar_to_interval(scalar: ScalarInt, ty: Ty) -> Option<Interval> {
+    let size = scalar.size();
+    match ty.kind() {
+        ty::Int(_) => Some(Interval::exact(scalar.to_int(size))),
+        ty::Uint(_) => scalar.to_uint(size).try_into().ok().map(Interval::exact),
+        _ => None,
+    }
+}
+
+/// The range of values of an integer type.
+fn int_range(tcx: TyCtxt, ty: Ty) -> Option<Interval> {
+    let pointer_bits = tcx.data_layout.pointer_size().bits();
+    match ty.kind() {
+        ty::Int(int) => {
+            let bits = int.bit_width().unwrap_or(pointer_bits);
+            let hi = (1i128 << (bits - 1)).wrapping_sub(1);
+            Some(Interval { lo: -hi - 1, hi })
+        }
+        ty::Uint(uint) => {
+            let bits = uint.bit_width().unwrap_or(pointer_bits);
+            let hi = if bits >= 128 { i128::MAX } else { (1i128 << bits) - 1 };
+            Some(Interval { lo: 0, hi })
+        }
+        _ => None,
+    }
+}
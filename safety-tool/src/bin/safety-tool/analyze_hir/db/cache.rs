@@ -0,0 +1,83 @@
+//! Tags of items in the sqlite3 file, looked up by [`PrimaryKey`] on demand instead of loading
+//! all rows, and cached in memory with LRU eviction.
+use super::{Database, PrimaryKey, data::Tags};
+use crate::Result;
+use rustc_data_structures::fx::FxHashMap;
+use std::{collections::BTreeMap, rc::Rc};
+
+/// Entries in the cache at most.
+const CAPACITY: usize = 4096;
+
+pub struct CachedDatabase {
+    db: Database,
+    capacity: usize,
+    /// Entries with the generation of their last use. Items without tags are cached as None,
+    /// since most callees are untagged.
+    entries: FxHashMap<PrimaryKey, (u64, Option<Rc<Tags>>)>,
+    /// Keys by the generation of their last use, so the least recently used one is the first.
+    lru: BTreeMap<u64, PrimaryKey>,
+    /// Increased on each use.
+    generation: u64,
+}
+
+impl CachedDatabase {
+    pub fn new(db: Database) -> Self {
+        Self::with_capacity(db, CAPACITY)
+    }
+
+    fn with_capacity(db: Database, capacity: usize) -> Self {
+        CachedDatabase {
+            db,
+            capacity,
+            entries: FxHashMap::default(),
+            lru: BTreeMap::new(),
+            generation: 0,
+        }
+    }
+
+    pub(super) fn get(&mut self, key: PrimaryKey) -> Result<Option<Rc<Tags>>> {
+        self.generation += 1;
+        let generation = self.generation;
+        if let Some((last, tags)) = self.entries.get_mut(&key) {
+            self.lru.remove(last);
+            self.lru.insert(generation, key);
+            *last = generation;
+            return Ok(tags.clone());
+        }
+
+        let tags = self.db.get_tags(key)?.map(|parsed| Rc::new(Tags::new(&parsed)));
+        if self.entries.len() == self.capacity
+            && let Some((_, evicted)) = self.lru.pop_first()
+        {
+            self.entries.remove(&evicted);
+        }
+        self.entries.insert(key, (generation, tags.clone()));
+        self.lru.insert(generation, key);
+        Ok(tags)
+    }
+}
+
+#[test]
+fn test_lru() -> Result<()> {
+    use super::storage::{CrateScope, data, key};
+
+    let path = "c.sqlite3";
+    let mut db = Database::new(path)?;
+    let scope = CrateScope { id: 1, name: "c".to_owned(), version: "0.1.0".to_owned() };
+    db.save_data(&scope, &[data(1, "c::a"), data(2, "c::b"), data(3, "c::c")])?;
+
+    let mut cache = CachedDatabase::with_capacity(db, 2);
+    assert!(cache.get(key(1))?.is_some());
+    assert!(cache.get(key(2))?.is_some());
+    // `1` is used again, so `2` is the least recently used one, and evicted by `3`.
+    assert!(cache.get(key(1))?.is_some());
+    assert!(cache.get(key(3))?.is_some());
+    assert!(cache.entries.contains_key(&key(1)) && cache.entries.contains_key(&key(3)));
+    assert!(!cache.entries.contains_key(&key(2)));
+    assert_eq!(cache.lru.len(), 2);
+
+    for suffix in ["", "-wal", "-shm"] {
+        _ = std::fs::remove_file(format!("{path}{suffix}"));
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+//! Binary form of [`ParsedTags`] in the `props` column of the database.
+//!
+//! The layout is a format version byte, followed by `requires` and `ensures`, each of which is
+//! a list of vanilla tag names, a list of `any` groups of tag names, and a list of options with
+//! their args. A list is a `u32` length followed by its elements, and a string is a `u32` length
+//! followed by UTF-8 bytes. Integers are little endian.
+use super::{ParsedProperties, ParsedTags};
+
+/// Bumped when the layout changes; rows in other versions are parsed from attrs again.
+const FORMAT: u8 = 1;
+
+impl ParsedTags {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![FORMAT];
+        for props in [&self.requires, &self.ensures] {
+            write_list(&mut buf, &props.vanilla, |buf, name| write_str(buf, name));
+            write_list(&mut buf, &props.group_of_any, |buf, group| {
+                write_list(buf, group, |buf, name| write_str(buf, name))
+            });
+            write_list(&mut buf, &props.options, |buf, (name, args)| {
+                write_str(buf, name);
+                write_list(buf, args, |buf, arg| write_str(buf, arg));
+            });
+        }
+        buf
+    }
+
+    /// None if the bytes are in another format version or malformed.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&format, bytes) = bytes.split_first()?;
+        if format != FORMAT {
+            return None;
+        }
+        let mut reader = Reader { bytes };
+        let mut read_props = || {
+            Some(ParsedProperties {
+                vanilla: reader.list(|r| r.str().map(Into::into))?,
+                group_of_any: reader.list(|r| r.list(|r| r.str().map(Into::into)))?,
+                options: reader.list(|r| Some((r.str()?.into(), r.list(|r| r.str())?)))?,
+            })
+        };
+        let tags = ParsedTags { requires: read_props()?, ensures: read_props()? };
+        reader.bytes.is_empty().then_some(tags)
+    }
+}
+
+fn write_len(buf: &mut Vec<u8>, len: usize) {
+    let len = u32::try_from(len).expect("Length overflows u32.");
+    buf.extend_from_slice(&len.to_le_bytes());
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+    write_len(buf, s.len());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn write_list<T>(buf: &mut Vec<u8>, list: &[T], mut write: impl FnMut(&mut Vec<u8>, &T)) {
+    write_len(buf, list.len());
+    for elem in list {
+        write(buf, elem);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Option<&[u8]> {
+        let (taken, rest) = self.bytes.split_at_checked(len)?;
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn len(&mut self) -> Option<usize> {
+        let bytes = self.take(4)?.try_into().ok()?;
+        Some(u32::from_le_bytes(bytes) as usize)
+    }
+
+    fn str(&mut self) -> Option<String> {
+        let len = self.len()?;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    fn list<T>(&mut self, mut read: impl FnMut(&mut Self) -> Option<T>) -> Option<Vec<T>> {
+        let len = self.len()?;
+        // Don't trust the length for allocation, since bytes may be malformed.
+        let mut v = Vec::with_capacity(len.min(self.bytes.len()));
+        for _ in 0..len {
+            v.push(read(self)?);
+        }
+        Some(v)
+    }
+}
+
+#[test]
+fn test_codec() {
+    let tags = ParsedTags {
+        requires: ParsedProperties {
+            vanilla: vec!["ValidPtr".into(), "Align".into()],
+            group_of_any: vec![vec!["Init".into(), "Typed".into()], vec![]],
+            options: vec![("Size".into(), vec!["T".to_owned(), "any".to_owned()])],
+        },
+        ensures: ParsedProperties { vanilla: vec!["Send".into()], ..Default::default() },
+    };
+    let bytes = tags.to_bytes();
+    assert_eq!(ParsedTags::from_bytes(&bytes), Some(tags));
+    assert_eq!(ParsedTags::from_bytes(&bytes[..bytes.len() - 1]), None);
+    assert_eq!(ParsedTags::from_bytes(&[FORMAT + 1]), None);
+    assert_eq!(ParsedTags::from_bytes(&ParsedTags::default().to_bytes()), Some(Default::default()));
+}
@@ -1,15 +1,21 @@
-use super::super::{HirFn, is_tool_attr};
+use super::CachedDatabase;
+use crate::analyze_hir::overlay_path;
+use crate::{is_tool_attr, is_tool_attr_named};
 use itertools::Itertools;
-use rustc_data_structures::fx::FxIndexMap;
-use rustc_hir::{Attribute, HirId, def_id::DefId};
+use rustc_data_structures::fx::{FxHashMap, FxIndexMap, FxIndexSet};
+use rustc_hir::{
+    Attribute, HirId,
+    def_id::{CrateNum, DefId, LocalDefId},
+};
 use rustc_middle::ty::TyCtxt;
 use safety_parser::{
-    configuration::Tag,
+    configuration::{OverlayTags, Tag, TagType, get_tag_opt, overlay},
     safety::{Property as SP, parse_attr_and_get_properties},
 };
-use std::{borrow::Cow, fmt};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, fmt, rc::Rc};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct PrimaryKey {
     pub hash1: u64,
     pub hash2: u64,
@@ -22,7 +28,7 @@ impl PrimaryKey {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Func {
     /// Safety tool attributes
     pub tool_attrs: Vec<String>,
@@ -32,19 +38,20 @@ pub struct Func {
     pub function: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Data {
     pub hash: PrimaryKey,
     pub func: Func,
+    /// Tags parsed from `func.tool_attrs`.
+    pub tags: ParsedTags,
 }
 
 impl Data {
-    pub fn new(hir_fn: &HirFn, tcx: TyCtxt) -> Self {
-        let def_id = hir_fn.local.to_def_id();
+    /// Data of a tagged item, such as a function, or an unsafe trait and its methods.
+    pub fn new(local: LocalDefId, hid: HirId, tcx: TyCtxt) -> Self {
+        let def_id = local.to_def_id();
         let hash = PrimaryKey::new(def_id, tcx);
 
-        let hid = hir_fn.hir_id;
-
         crossfig::switch! {
             crate::asterinas => {
                 let function = rustc_hir_pretty::id_to_string(&tcx.hir(), hid);
@@ -61,8 +68,80 @@ impl Data {
             def_path: tcx.def_path_debug_str(def_id),
             function,
         };
+        let tags = ParsedTags::new(&func.tool_attrs);
+
+        Data { hash, func, tags }
+    }
+}
+
+/// Tags parsed from tool attrs of an item, which are stored in sidecars and the database in
+/// place of attrs, so downstream crates don't parse attrs again.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ParsedTags {
+    /// Tags required to be discharged by callers.
+    pub requires: ParsedProperties,
+    /// Tags in `ensures` required to be discharged by unsafe impls.
+    pub ensures: ParsedProperties,
+}
+
+impl ParsedTags {
+    pub fn new(tool_attrs: &[String]) -> Self {
+        let mut tags = ParsedTags::default();
+        for attr in tool_attrs {
+            let props = if is_ensures(attr) { &mut tags.ensures } else { &mut tags.requires };
+            props.push_attr(attr);
+        }
+        tags
+    }
+
+    /// Tags in overlay files, which are all required to be discharged by callers except options.
+    fn from_overlay(overlay: &OverlayTags) -> Self {
+        let mut requires = ParsedProperties::default();
+        for name in &overlay.vanilla {
+            if get_tag_opt(name).is_some_and(|tag| *tag.types == [TagType::Option]) {
+                let args = overlay.args.get(name).into_iter().flatten();
+                requires.options.push((name.clone(), args.map(|arg| arg.to_string()).collect()));
+            } else {
+                requires.vanilla.push(name.clone());
+            }
+        }
+        requires.group_of_any = overlay.group_of_any.clone();
+        ParsedTags { requires, ensures: ParsedProperties::default() }
+    }
+}
+
+/// Tag names, which are resolved to specs when looked up.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ParsedProperties {
+    pub vanilla: Vec<Box<str>>,
+    pub group_of_any: Vec<Vec<Box<str>>>,
+    /// Options with their args.
+    pub options: Vec<(Box<str>, Vec<String>)>,
+}
 
-        Data { hash, func }
+impl ParsedProperties {
+    fn push_attr(&mut self, attr: &str) {
+        let props = &*parse_attr_and_get_properties(attr);
+
+        // Usually tags are vanilla, so reserve enough sapce.
+        let cap = props.iter().map(|prop| prop.tags.len()).sum();
+        self.vanilla.reserve(cap);
+
+        for prop in props {
+            for tag in &*prop.tags {
+                if let Some(v_sp) = tag.args_in_any_tag() {
+                    // Push SPs in `any`
+                    let iter =
+                        v_sp.iter().flat_map(|p| p.tags.iter().map(|sp| sp.tag.name().into()));
+                    self.group_of_any.push(iter.collect());
+                } else if tag.tag.is_option() {
+                    // Options are not mandatory, thus need no discharge.
+                    self.options.push((tag.tag.name().into(), tag.args_as_str()));
+                } else {
+                    self.vanilla.push(tag.tag.name().into());
+                }
+            }
+        }
     }
 }
 
@@ -75,9 +154,17 @@ fn get_attrs(tcx: TyCtxt<'_>, hid: HirId) -> impl Iterator<Item = &'_ Attribute>
     }
 }
 
-/// Convert the tool attribute to string.
+/// Convert the tool attribute to string. `#[rapx::ref]` and `#[rapx::resolves]` are skipped,
+/// because they are neither requirements nor discharges, and are handled by [`EntityRefs`]
+/// and [`hazard`] respectively.
+///
+/// [`EntityRefs`]: crate::analyze_hir::entity_ref::EntityRefs
+/// [`hazard`]: crate::analyze_hir::hazard
 fn opt_attribute_to_string(tcx: TyCtxt<'_>, attr: &rustc_hir::Attribute) -> Option<String> {
-    is_tool_attr(attr).then(|| attribute_to_string(tcx, attr))
+    (is_tool_attr(attr)
+        && !is_tool_attr_named(attr, "ref")
+        && !is_tool_attr_named(attr, "resolves"))
+    .then(|| attribute_to_string(tcx, attr))
 }
 
 fn attribute_to_string(tcx: TyCtxt<'_>, attr: &rustc_hir::Attribute) -> String {
@@ -93,6 +180,12 @@ pub struct TagState {
     ///
     /// [`args_in_any_tag`]: safety_parser::safety::Property::args_in_any_tag
     group_of_any: Vec<FxIndexMap<Property, bool>>,
+    /// Optional preconditions, which waive other tags when they hold at the call site.
+    options: Vec<OptionTag>,
+    /// Tags waived by options or auto-discharged with the reasons.
+    waived: FxIndexMap<Property, String>,
+    /// Tags auto-discharged by provenance or constant evaluation, which are part of `waived`.
+    auto: FxIndexSet<Property>,
     /// If undischarged is called once. This ensures undischarged diagnostics are emitted only once.
     undischarged: bool,
 }
@@ -101,17 +194,62 @@ impl TagState {
     fn clear(&mut self) {
         self.vanilla.clear();
         self.group_of_any.clear();
+        self.options.clear();
+        self.waived.clear();
+        self.auto.clear();
     }
 
     fn refresh(&mut self, props: &Properties) {
         self.clear();
+        // The state of a trait is shared by all of its impls, each of which reports its own
+        // undischarged tags.
+        self.undischarged = false;
         self.vanilla.extend(props.vanilla.iter().map(|p| (p.clone(), false)));
         self.group_of_any.extend(
             props.group_of_any.iter().map(|v| v.iter().map(|p| (p.clone(), false)).collect()),
         );
+        self.options.extend(props.options.iter().cloned());
+    }
+
+    pub fn options(&self) -> &[OptionTag] {
+        &self.options
+    }
+
+    /// Auto-discharge the tag because an option holds, without complaining about later
+    /// discharges of it.
+    pub fn waive(&mut self, name: &str, reason: String) {
+        let groups = self.group_of_any.iter_mut();
+        for (prop, state) in self.vanilla.iter_mut().chain(groups.flatten()) {
+            if &*prop.name == name && !*state {
+                *state = true;
+                self.waived.entry(prop.clone()).or_insert_with(|| reason.clone());
+            }
+        }
+    }
+
+    /// Auto-discharge the tag because it's guaranteed by args at the call site, e.g. pointers
+    /// from references or constants within the range.
+    pub fn auto_discharge(&mut self, name: &str, reason: String) {
+        let len = self.waived.len();
+        self.waive(name, reason);
+        // Newly waived tags are appended.
+        self.auto.extend(self.waived.keys().skip(len).cloned());
+    }
+
+    /// The reason why the tag is auto-discharged.
+    pub fn auto_reason(&self, prop: &Property) -> Option<&str> {
+        self.auto.contains(prop).then(|| self.waived[prop].as_str())
+    }
+
+    /// Whether the tag is required to be discharged, individually or in `any`.
+    pub fn requires(&self, prop: &Property) -> bool {
+        self.vanilla.contains_key(prop) || self.group_of_any.iter().any(|g| g.contains_key(prop))
     }
 
     pub fn discharge(&mut self, prop: &Property) -> Result<(), String> {
+        if self.waived.contains_key(prop) {
+            return Ok(());
+        }
         if let Some(state) = self.vanilla.get_mut(prop) {
             if *state {
                 return Err(format!("{prop:?} has already been discharged"));
@@ -139,6 +277,18 @@ impl TagState {
     //         && self.group_of_any.iter().all(|g| g.values().any(|b| *b))
     // }
 
+    /// Tags not discharged yet, without reporting them. Each is a group of alternatives, and a
+    /// vanilla tag is a group of itself.
+    pub fn pending(&self) -> Vec<Vec<&Property>> {
+        let vanilla = self.vanilla.iter().filter(|(_, state)| !**state).map(|(sp, _)| vec![sp]);
+        let any = self
+            .group_of_any
+            .iter()
+            .filter(|group| !group.values().any(|state| *state))
+            .map(|group| group.keys().collect());
+        vanilla.chain(any).collect()
+    }
+
     pub fn undischarged(&mut self) -> Undischarged {
         let mut undischarged = Undischarged::default();
         if self.undischarged {
@@ -175,6 +325,9 @@ impl TagState {
                 undischarged.v_any_sp.push(v_any_sp);
             }
         }
+
+        undischarged.v_waived =
+            self.waived.iter().map(|(sp, reason)| format!("`{sp}` is waived, {reason}")).collect();
         undischarged
     }
 }
@@ -187,6 +340,8 @@ pub struct Undischarged {
     pub v_sp: Vec<Property>,
     /// Each element is a group of tags in `any` tag.
     pub v_any_sp: Vec<Vec<Property>>,
+    /// Why tags are waived by options.
+    pub v_waived: Vec<String>,
 }
 
 impl Undischarged {
@@ -203,7 +358,9 @@ impl Undischarged {
     }
 
     pub fn info(&self) -> Vec<String> {
-        let capacity = self.v_sp.len() + self.v_any_sp.iter().map(|v| v.len()).sum::<usize>();
+        let capacity = self.v_sp.len()
+            + self.v_any_sp.iter().map(|v| v.len()).sum::<usize>()
+            + self.v_waived.len();
         let mut v = Vec::with_capacity(capacity);
 
         for sp in &self.v_sp {
@@ -216,6 +373,8 @@ impl Undischarged {
             }
         }
 
+        v.extend(self.v_waived.iter().cloned());
+
         v
     }
 }
@@ -224,65 +383,148 @@ impl Undischarged {
 struct Properties {
     vanilla: Vec<Property>,
     group_of_any: Vec<Box<[Property]>>,
+    options: Vec<OptionTag>,
 }
 
 impl Properties {
-    fn push_attr(&mut self, attr: &str) {
-        let props = &*parse_attr_and_get_properties(attr);
+    fn new(parsed: &ParsedProperties) -> Self {
+        Properties {
+            vanilla: parsed.vanilla.iter().map(|name| Property::named(name)).collect(),
+            group_of_any: parsed
+                .group_of_any
+                .iter()
+                .map(|group| group.iter().map(|name| Property::named(name)).collect())
+                .collect(),
+            options: parsed
+                .options
+                .iter()
+                .map(|(name, args)| OptionTag::with_args(name, args.clone()))
+                .collect(),
+        }
+    }
 
-        // Usually tags are vanilla, so reserve enough sapce.
-        let cap = props.iter().map(|prop| prop.tags.len()).sum();
-        self.vanilla.reserve(cap);
+    fn is_empty(&self) -> bool {
+        self.vanilla.is_empty() && self.group_of_any.is_empty() && self.options.is_empty()
+    }
+}
 
-        for prop in props {
-            for tag in &*prop.tags {
-                if let Some(v_sp) = tag.args_in_any_tag() {
-                    // Push SPs in `any`
-                    let iter = v_sp.iter().flat_map(|p| p.tags.iter().map(to_prop));
-                    self.group_of_any.push(iter.collect());
-                } else {
-                    self.vanilla.push(to_prop(tag));
-                }
-            }
+/// Tags of an item with specs resolved.
+#[derive(Debug, Default)]
+pub(super) struct Tags {
+    requires: Properties,
+    ensures: Properties,
+}
+
+impl Tags {
+    pub(super) fn new(parsed: &ParsedTags) -> Self {
+        Tags {
+            requires: Properties::new(&parsed.requires),
+            ensures: Properties::new(&parsed.ensures),
         }
     }
 }
 
-#[derive(Debug, Default)]
+/// Versions of dependencies by crate numbers.
+pub type CrateVersions = FxHashMap<CrateNum, String>;
+
+#[derive(Default)]
 pub struct ToolAttrs {
-    map: FxIndexMap<PrimaryKey, Properties>,
+    /// Tags of local items and dependencies with sidecars, which take precedence.
+    map: FxHashMap<PrimaryKey, Rc<Tags>>,
+    /// Tags looked up in the sqlite3 file on demand.
+    db: Option<CachedDatabase>,
+    /// Tags in overlay files for items without tool attrs, keyed by def paths.
+    overlay: FxHashMap<&'static str, Rc<Tags>>,
+    /// Versions of dependencies recorded in their sidecars.
+    versions: CrateVersions,
     /// State of safety tags shows if thet are discharged.
     tagged: TagState,
 }
 
 impl ToolAttrs {
     pub fn new(data: &[Data]) -> Self {
-        Self {
-            map: data
-                .iter()
-                .filter(|d| !d.func.tool_attrs.is_empty())
-                .map(|d| {
-                    let mut props = Properties::default();
-                    d.func.tool_attrs.iter().for_each(|s| props.push_attr(s));
-                    (d.hash, props)
-                })
-                .collect(),
-            tagged: Default::default(),
+        let map = data.iter().map(|d| (d.hash, Rc::new(Tags::new(&d.tags)))).collect();
+        let overlay = overlay()
+            .iter()
+            .map(|(path, tags)| (&**path, Rc::new(Tags::new(&ParsedTags::from_overlay(tags)))))
+            .collect();
+        Self { map, overlay, ..Default::default() }
+    }
+
+    pub fn set_crate_versions(&mut self, versions: CrateVersions) {
+        self.versions = versions;
+    }
+
+    /// The version of a dependency with a sidecar.
+    pub fn crate_version(&self, krate: CrateNum) -> Option<&str> {
+        self.versions.get(&krate).map(|v| v.as_str())
+    }
+
+    /// Look up tags missing in sidecars in the database.
+    pub fn set_database(&mut self, db: CachedDatabase) {
+        self.db = Some(db);
+    }
+
+    fn tags(&mut self, def_id: DefId, tcx: TyCtxt) -> Option<Rc<Tags>> {
+        let key = PrimaryKey::new(def_id, tcx);
+        if let Some(tags) = self.map.get(&key) {
+            return Some(tags.clone());
+        }
+        if let Some(db) = &mut self.db
+            && let Some(tags) = db
+                .get(key)
+                .unwrap_or_else(|err| panic!("Failed to look up tags of {key:?}:\n{err:?}"))
+        {
+            return Some(tags);
+        }
+        self.overlay_tags(def_id, tcx)
+    }
+
+    /// Tags in overlay files on the real path or the visible path of the item.
+    fn overlay_tags(&self, def_id: DefId, tcx: TyCtxt) -> Option<Rc<Tags>> {
+        if self.overlay.is_empty() {
+            return None;
         }
+        [overlay_path(tcx, def_id), tcx.def_path_str(def_id)]
+            .iter()
+            .find_map(|path| self.overlay.get(path.as_str()))
+            .cloned()
     }
 
     pub fn get_tags(&mut self, def_id: DefId, tcx: TyCtxt) -> Option<&mut TagState> {
-        let key = PrimaryKey::new(def_id, tcx);
-        self.get_tags_via_key(key)
+        let tags = self.tags(def_id, tcx)?;
+        if tags.requires.is_empty() {
+            return None;
+        }
+        self.tagged.refresh(&tags.requires);
+        Some(&mut self.tagged)
+    }
+
+    /// Names of tags required by the item. Each is a group of alternatives, and a vanilla tag
+    /// is a group of itself.
+    pub fn required(&mut self, def_id: DefId, tcx: TyCtxt) -> Vec<Vec<Box<str>>> {
+        let Some(state) = self.get_tags(def_id, tcx) else { return Vec::new() };
+        let groups = state.pending().into_iter();
+        groups.map(|group| group.iter().map(|sp| sp.name().into()).collect()).collect()
     }
 
-    fn get_tags_via_key(&mut self, key: PrimaryKey) -> Option<&mut TagState> {
-        let props = self.map.get(&key)?;
-        self.tagged.refresh(props);
+    /// Get tags in `ensures` on an unsafe trait or its method, which must be
+    /// discharged by the unsafe impl.
+    pub fn get_ensures(&mut self, def_id: DefId, tcx: TyCtxt) -> Option<&mut TagState> {
+        let tags = self.tags(def_id, tcx)?;
+        if tags.ensures.is_empty() {
+            return None;
+        }
+        self.tagged.refresh(&tags.ensures);
         Some(&mut self.tagged)
     }
 }
 
+/// Postconditions are obligations of the implementor rather than the caller.
+fn is_ensures(attr: &str) -> bool {
+    attr.starts_with("#[rapx::ensures")
+}
+
 #[derive(Clone)]
 pub struct Property {
     // SP name. This represents a unique property, so spec is not involved
@@ -330,6 +572,10 @@ impl fmt::Debug for Property {
 }
 
 impl Property {
+    fn named(name: &str) -> Self {
+        Property { name: name.into(), spec: get_tag_opt(name) }
+    }
+
     pub fn new_with_hir_id(hir_id: HirId, tcx: TyCtxt) -> Vec<Self> {
         let mut v = Vec::new();
 
@@ -340,6 +586,12 @@ impl Property {
         v
     }
 
+    pub fn new_with_attr(attr: &str) -> Vec<Self> {
+        let mut v = Vec::new();
+        push_properties(attr, &mut v);
+        v
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -368,6 +620,51 @@ impl Property {
     }
 }
 
+/// An optional precondition with its args, e.g. `Trait(T, Copy, Alias)`.
+#[derive(Clone, Debug)]
+pub struct OptionTag {
+    pub name: Box<str>,
+    pub args: Vec<String>,
+    pub spec: Option<&'static Tag>,
+}
+
+impl OptionTag {
+    fn with_args(name: &str, args: Vec<String>) -> Self {
+        OptionTag { name: name.into(), args, spec: get_tag_opt(name) }
+    }
+
+    /// The arg named in spec, or at the index if spec doesn't define args.
+    pub fn arg(&self, name: &str, idx: usize) -> Option<&str> {
+        let idx = match self.spec {
+            Some(tag) if !tag.args.is_empty() => tag.args.iter().position(|arg| **arg == *name)?,
+            _ => idx,
+        };
+        self.args.get(idx).map(|arg| arg.as_str())
+    }
+
+    /// Tag names waived by the option, with option args substituted for `{arg}`. An option
+    /// without args waives nothing, e.g. `CopyTrait` in overlays.
+    pub fn waives(&self) -> Vec<String> {
+        let Some(tag) = self.spec.filter(|_| !self.args.is_empty()) else { return Vec::new() };
+        tag.waives
+            .iter()
+            .map(|waived| {
+                let mut waived = waived.to_string();
+                for (name, arg) in tag.args.iter().zip(&self.args) {
+                    waived = waived.replace(&format!("{{{name}}}"), arg);
+                }
+                waived
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for OptionTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({})", self.name, self.args.join(", "))
+    }
+}
+
 fn push_properties(s: &str, v: &mut Vec<Property>) {
     let properties = &*parse_attr_and_get_properties(s);
     let cap = properties.iter().map(|prop| prop.tags.len()).sum();
@@ -389,3 +686,9 @@ pub fn tool_attr_on_hir<'tcx>(
 ) -> impl 'tcx + Iterator<Item = String> {
     get_attrs(tcx, hir_id).filter_map(move |attr| opt_attribute_to_string(tcx, attr))
 }
+
+/// The tool attr as a string, which is None for other attrs, `#[rapx::ref]` and
+/// `#[rapx::resolves]`.
+pub fn tool_attr_to_string(tcx: TyCtxt<'_>, attr: &rustc_hir::Attribute) -> Option<String> {
+    opt_attribute_to_string(tcx, attr)
+}
@@ -1,21 +1,46 @@
+mod cache;
+mod codec;
+mod sidecar;
 mod storage;
-pub use storage::Database;
+pub use cache::CachedDatabase;
+pub use storage::{CrateScope, Database};
 
 mod data;
-pub use data::{Data, Func, PrimaryKey, Property, TagState, ToolAttrs, tool_attr_on_hir};
+pub use data::{
+    Data, OptionTag, ParsedProperties, ParsedTags, PrimaryKey, Property, TagState, ToolAttrs,
+    tool_attr_on_hir, tool_attr_to_string,
+};
 
-pub fn get_all_tool_attrs(iter: impl IntoIterator<Item = Data>) -> crate::Result<ToolAttrs> {
-    // Recommend setting the DATA_SQLITE3 environment variable to an absolute path.
-    // The default path is relative to the folder where the crate is being compiled,
-    // leading to a sqlite3 file in each crate folder.
-    const DATA_SQLITE3: &str = "data.sqlite3";
-    let path = std::env::var("DATA_SQLITE3");
-    let path = path.as_deref().unwrap_or(DATA_SQLITE3);
+use rustc_hir::def_id::LOCAL_CRATE;
+use rustc_middle::ty::TyCtxt;
 
-    let mut db = Database::new(path)?;
+/// Tool attrs of tagged items in the local crate and its dependencies.
+///
+/// Items of dependencies are loaded from their sidecars, see [`sidecar`]. When the env var
+/// `DATA_SQLITE3` is set, items are also saved into and loaded from the sqlite3 file shared by
+/// all crates, which is useful when dependencies aren't located through rustc, e.g. in builds
+/// driven by other build systems. An absolute path is recommended, because a relative path
+/// is relative to the folder where each crate is compiled. Rows in the file are scoped by the
+/// stable crate id, and tags in it are looked up on demand.
+pub fn get_all_tool_attrs(tcx: TyCtxt, local: Vec<Data>) -> crate::Result<ToolAttrs> {
+    sidecar::write(tcx, &local)?;
+    let (mut v_data, versions) = sidecar::read_deps(tcx)?;
 
-    db.save_data(iter)?;
-    let v_data = db.get_all_data()?;
+    // Local items come last to take precedence.
+    v_data.extend_from_slice(&local);
+    let mut tool_attrs = ToolAttrs::new(&v_data);
+    tool_attrs.set_crate_versions(versions);
 
-    Ok(ToolAttrs::new(&v_data))
+    if let Ok(path) = std::env::var("DATA_SQLITE3") {
+        let mut db = Database::new(&path)?;
+        let scope = CrateScope {
+            id: tcx.stable_crate_id(LOCAL_CRATE).as_u64(),
+            name: tcx.crate_name(LOCAL_CRATE).to_string(),
+            version: std::env::var("CARGO_PKG_VERSION").unwrap_or_default(),
+        };
+        db.save_data(&scope, &local)?;
+        tool_attrs.set_database(CachedDatabase::new(db));
+    }
+
+    Ok(tool_attrs)
 }
@@ -0,0 +1,112 @@
+//! Per-crate sidecar of tagged items, i.e. `lib<crate><extra>.safety.json` next to the rlib.
+//!
+//! A library crate writes its own tagged items into the sidecar, and downstream crates load
+//! sidecars of dependencies from the paths rustc resolves for them through `--extern` and `-L`,
+//! in the same way rmeta files are loaded. The sidecar also records `CARGO_PKG_VERSION` of the
+//! crate, so calls into a dependency are attributed to its version in stats.
+//!
+//! Only what downstream crates look up is stored for each item, i.e. the def path hash, tool
+//! attrs and parsed tags, while the source text of the item is left out.
+use super::{
+    Data, ParsedTags, PrimaryKey,
+    data::{CrateVersions, Func},
+};
+use crate::Result;
+use eyre::Context;
+use rustc_hir::def_id::LOCAL_CRATE;
+use rustc_middle::ty::TyCtxt;
+use rustc_session::{
+    config::{CrateType, OutFileName},
+    output::out_filename,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const EXTENSION: &str = "safety.json";
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Sidecar {
+    #[serde(rename = "crate")]
+    krate: String,
+    #[serde(default)]
+    version: String,
+    items: Vec<Item>,
+}
+
+/// A tagged item in the sidecar.
+#[derive(Debug, Deserialize, Serialize)]
+struct Item {
+    hash: PrimaryKey,
+    def_path: String,
+    tool_attrs: Vec<String>,
+    tags: ParsedTags,
+}
+
+impl From<&Data> for Item {
+    fn from(data: &Data) -> Self {
+        let Data { hash, func, tags } = data;
+        let (def_path, tool_attrs) = (func.def_path.clone(), func.tool_attrs.clone());
+        Item { hash: *hash, def_path, tool_attrs, tags: tags.clone() }
+    }
+}
+
+impl From<Item> for Data {
+    fn from(item: Item) -> Self {
+        let Item { hash, def_path, tool_attrs, tags } = item;
+        let func = Func { tool_attrs, def_path, function: String::new() };
+        Data { hash, func, tags }
+    }
+}
+
+/// Write tagged items of the local crate into the sidecar, if it's a library whose artifacts
+/// are emitted.
+pub fn write(tcx: TyCtxt, data: &[Data]) -> Result<()> {
+    let is_lib =
+        tcx.crate_types().iter().any(|typ| matches!(typ, CrateType::Rlib | CrateType::Dylib));
+    if !is_lib || crate::stops_compilation() {
+        return Ok(());
+    }
+    let krate = tcx.crate_name(LOCAL_CRATE);
+    let OutFileName::Real(rlib) =
+        out_filename(tcx.sess, CrateType::Rlib, tcx.output_filenames(()), krate)
+    else {
+        return Ok(());
+    };
+    let path = sidecar_path(&rlib);
+
+    // Items are already in definition order, so the output is deterministic.
+    let version = std::env::var("CARGO_PKG_VERSION").unwrap_or_default();
+    let sidecar =
+        Sidecar { krate: krate.to_string(), version, items: data.iter().map(Item::from).collect() };
+    let json = serde_json::to_string_pretty(&sidecar)?;
+    fs::write(&path, json).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Tagged items in sidecars of all dependencies, and versions of these dependencies.
+/// Dependencies not analyzed by safety-tool have no sidecar, and are skipped.
+pub fn read_deps(tcx: TyCtxt) -> Result<(Vec<Data>, CrateVersions)> {
+    let mut v = Vec::new();
+    let mut versions = CrateVersions::default();
+    for &cnum in tcx.crates(()) {
+        let source = tcx.used_crate_source(cnum);
+        let Some(path) = source.paths().map(|path| sidecar_path(path)).find(|p| p.exists()) else {
+            continue;
+        };
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let sidecar: Sidecar = serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        debug!(krate = sidecar.krate, items = sidecar.items.len(), path = %path.display());
+        v.extend(sidecar.items.into_iter().map(Data::from));
+        versions.insert(cnum, sidecar.version);
+    }
+    Ok((v, versions))
+}
+
+/// `libfoo-hash.rlib` or `libfoo-hash.rmeta` to `libfoo-hash.safety.json`.
+fn sidecar_path(artifact: &Path) -> PathBuf {
+    artifact.with_extension(EXTENSION)
+}
@@ -1,13 +1,38 @@
-use super::{Data, Func, PrimaryKey};
+//! The sqlite3 file shared by crates compiled concurrently, e.g. by `cargo build -j`.
+//!
+//! * The schema is versioned in `schema_version`, and migrated in order on open.
+//! * WAL mode lets readers run along with a writer, and writers wait for the lock up to
+//!   [`BUSY_TIMEOUT`] instead of failing at once.
+//! * Rows are scoped by the stable crate id, which differs for a lib and its test harness, or
+//!   builds with different features or targets, through `-C metadata`. Each compile session of
+//!   a crate replaces its own rows, and sweeps rows it no longer produces, e.g. of removed or
+//!   renamed functions. Sessions are ordered by the increasing `generation` of their rows.
+//! * Parsed tags are stored in binary form in `props`, and looked up by [`PrimaryKey`].
+use super::{Data, ParsedTags, PrimaryKey};
 use crate::Result;
 use eyre::Context;
-use rusqlite::{Connection, named_params};
+use rusqlite::{Connection, OptionalExtension, TransactionBehavior, named_params};
+use std::time::Duration;
 
 pub struct Database {
     conn: Connection,
 }
 
-const CREATE: &str = "
+/// How long a connection waits for the lock held by another one.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(60);
+
+const CREATE_SCHEMA_VERSION: &str = "
+CREATE TABLE IF NOT EXISTS schema_version (
+  version INTEGER NOT NULL
+) STRICT;
+";
+
+/// Migrations from version `i` to `i + 1` are at index `i`.
+///
+/// Version 1 is the unversioned table in old files, thus it's created if absent.
+const MIGRATIONS: &[&str] = &[
+    // 0 -> 1
+    "
 CREATE TABLE IF NOT EXISTS hir (
   hash1 INTEGER NOT NULL,
   hash2 INTEGER NOT NULL,
@@ -17,77 +42,265 @@ CREATE TABLE IF NOT EXISTS hir (
   timestamp TEXT NOT NULL,
   PRIMARY KEY (hash1, hash2)
 ) STRICT;
-";
+",
+    // 1 -> 2: rows are scoped by crates; old rows belong to no crate.
+    "
+CREATE TABLE hir_v2 (
+  crate_name TEXT NOT NULL,
+  crate_version TEXT NOT NULL,
+  hash1 INTEGER NOT NULL,
+  hash2 INTEGER NOT NULL,
+  tool_attrs TEXT NOT NULL,
+  def_path TEXT NOT NULL,
+  function TEXT NOT NULL,
+  timestamp TEXT NOT NULL,
+  PRIMARY KEY (crate_name, crate_version, hash1, hash2)
+) STRICT;
+INSERT INTO hir_v2
+  SELECT '', '', hash1, hash2, tool_attrs, def_path, function, timestamp FROM hir;
+DROP TABLE hir;
+ALTER TABLE hir_v2 RENAME TO hir;
+",
+    // 2 -> 3: parsed tags, which are NULL in old rows, and the index of lookup.
+    "
+ALTER TABLE hir ADD COLUMN props BLOB;
+CREATE INDEX hir_hash ON hir (hash1, hash2);
+",
+    // 3 -> 4: old rows belonging to no crate are never swept by any crate, so drop them.
+    // Crates write their rows again when compiled.
+    "
+DELETE FROM hir WHERE crate_name = '' AND crate_version = '';
+",
+    // 4 -> 5: rows are scoped by stable crate ids, and sessions are ordered by generations
+    // instead of timestamps. Old rows have no crate id, so they're dropped in the same way.
+    "
+DROP TABLE hir;
+CREATE TABLE hir (
+  crate_id INTEGER NOT NULL,
+  crate_name TEXT NOT NULL,
+  crate_version TEXT NOT NULL,
+  hash1 INTEGER NOT NULL,
+  hash2 INTEGER NOT NULL,
+  tool_attrs TEXT NOT NULL,
+  def_path TEXT NOT NULL,
+  function TEXT NOT NULL,
+  generation INTEGER NOT NULL,
+  props BLOB,
+  PRIMARY KEY (crate_id, hash1, hash2)
+) STRICT;
+CREATE INDEX hir_hash ON hir (hash1, hash2);
+",
+];
+
+/// The crate whose rows are saved.
+#[derive(Debug)]
+pub struct CrateScope {
+    /// Stable crate id, which identifies rows of the crate.
+    pub id: u64,
+    pub name: String,
+    pub version: String,
+}
 
 impl Database {
     pub fn new(path: &str) -> Result<Database> {
-        let conn = Connection::open(path).with_context(|| format!("Failed to open {path}"))?;
-        conn.execute(CREATE, ()).with_context(|| format!("Failed to execute sql:\n{CREATE}"))?;
+        let mut conn = Connection::open(path).with_context(|| format!("Failed to open {path}"))?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        let mode: String =
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+        debug!(path, mode);
+        migrate(&mut conn).with_context(|| format!("Failed to migrate {path}"))?;
         Ok(Database { conn })
     }
 
-    pub fn save_data(&mut self, iter: impl IntoIterator<Item = Data>) -> Result<()> {
+    /// Save rows of the crate produced in this compile session, and sweep its other rows.
+    pub fn save_data(&mut self, scope: &CrateScope, data: &[Data]) -> Result<()> {
         const UPSERT: &str = "
-INSERT OR REPLACE INTO hir (hash1, hash2, tool_attrs, def_path, function, timestamp)
-VALUES (:hash1, :hash2, :tool_attrs, :def_path, :function, :timestamp)
+INSERT OR REPLACE INTO hir
+  (crate_id, crate_name, crate_version, hash1, hash2, tool_attrs, def_path, function,
+   generation, props)
+VALUES
+  (:crate_id, :crate_name, :crate_version, :hash1, :hash2, :tool_attrs, :def_path, :function,
+   :generation, :props)
+";
+        const SWEEP: &str = "
+DELETE FROM hir WHERE crate_id = :crate_id AND generation != :generation
 ";
-        let mut stmt = self.conn.prepare(UPSERT)?;
+        const NEXT_GENERATION: &str = "SELECT coalesce(max(generation), 0) + 1 FROM hir";
+        // The write lock is taken at once to not be upgraded from a read lock, which fails
+        // immediately regardless of the busy timeout when another connection writes.
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
 
-        let timestamp = jiff::Timestamp::now();
-        for data in iter {
+        // The generation identifies the session, which is newer than all sessions before.
+        let generation: i64 = tx.query_row(NEXT_GENERATION, [], |row| row.get(0))?;
+        let crate_id = scope.id.cast_signed();
+        let mut stmt = tx.prepare(UPSERT)?;
+        for data in data {
             let tool_attrs = serde_json::to_string_pretty(data.func.tool_attrs.as_slice())?;
             stmt.execute(named_params! {
+                ":crate_id": crate_id,
+                ":crate_name": scope.name,
+                ":crate_version": scope.version,
                 ":hash1": data.hash.hash1.cast_signed(),
                 ":hash2": data.hash.hash2.cast_signed(),
                 ":tool_attrs": tool_attrs,
                 ":def_path": data.func.def_path,
                 ":function": data.func.function,
-                ":timestamp": timestamp,
+                ":generation": generation,
+                ":props": data.tags.to_bytes(),
             })?;
         }
-
         stmt.finalize()?;
 
+        let swept =
+            tx.execute(SWEEP, named_params! { ":crate_id": crate_id, ":generation": generation })?;
+        debug!(?scope, swept);
+
+        tx.commit()?;
         Ok(())
     }
 
-    pub fn get_all_data(&mut self) -> Result<Vec<Data>> {
+    /// Parsed tags of the item, or None if it's not in the database. Tags of the latest crate
+    /// are returned if several crates define the item.
+    pub fn get_tags(&mut self, key: PrimaryKey) -> Result<Option<ParsedTags>> {
         const QUERY: &str = "
-SELECT hash1, hash2, tool_attrs, def_path, function FROM hir
+SELECT props, tool_attrs FROM hir
+WHERE hash1 = :hash1 AND hash2 = :hash2
+ORDER BY generation DESC
+LIMIT 1
 ";
-        let mut stmt = self.conn.prepare(QUERY)?;
-        stmt.query_and_then([], |row| {
-            eyre::Ok(Data {
-                hash: PrimaryKey {
-                    hash1: row.get::<_, i64>(0)?.cast_unsigned(),
-                    hash2: row.get::<_, i64>(1)?.cast_unsigned(),
-                },
-                func: Func {
-                    tool_attrs: serde_json::from_str(row.get_ref(2)?.as_str()?)?,
-                    def_path: row.get(3)?,
-                    function: row.get(4)?,
-                },
-            })
-        })?
-        .collect()
+        let mut stmt = self.conn.prepare_cached(QUERY)?;
+        let params = named_params! {
+            ":hash1": key.hash1.cast_signed(),
+            ":hash2": key.hash2.cast_signed(),
+        };
+        let mut rows = stmt.query(params)?;
+        let Some(row) = rows.next()? else { return Ok(None) };
+
+        if let Some(props) = row.get_ref(0)?.as_blob_or_null()?
+            && let Some(tags) = ParsedTags::from_bytes(props)
+        {
+            return Ok(Some(tags));
+        }
+        // Rows saved by older safety-tool have no parsed tags.
+        let tool_attrs: Vec<String> = serde_json::from_str(row.get_ref(1)?.as_str()?)?;
+        Ok(Some(ParsedTags::new(&tool_attrs)))
     }
 }
 
+/// Apply pending migrations in a transaction, so concurrent connections migrate only once.
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    tx.execute_batch(CREATE_SCHEMA_VERSION)?;
+    let current: Option<i64> = tx
+        .query_row("SELECT max(version) FROM schema_version", [], |row| row.get(0))
+        .optional()?
+        .flatten();
+    let current = current.unwrap_or(0) as usize;
+    eyre::ensure!(
+        current <= MIGRATIONS.len(),
+        "Schema version {current} is newer than {} supported by this safety-tool.",
+        MIGRATIONS.len()
+    );
+
+    for (version, sql) in MIGRATIONS.iter().enumerate().skip(current) {
+        let version = version + 1;
+        tx.execute_batch(sql).with_context(|| format!("Failed to migrate to {version}:\n{sql}"))?;
+        tx.execute("INSERT INTO schema_version (version) VALUES (?1)", [version as i64])?;
+        debug!(version, "migrated");
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub(super) fn data(hash1: u64, def_path: &str) -> Data {
+    let tool_attrs = vec!["#[rapx::requires(ValidPtr)]".to_owned()];
+    Data {
+        hash: PrimaryKey { hash1, hash2: 2 },
+        tags: ParsedTags::new(&tool_attrs),
+        func: super::data::Func {
+            tool_attrs,
+            def_path: def_path.to_owned(),
+            function: "fn f() {}".to_owned(),
+        },
+    }
+}
+
+#[cfg(test)]
+pub(super) fn key(hash1: u64) -> PrimaryKey {
+    PrimaryKey { hash1, hash2: 2 }
+}
+
 #[test]
 fn test_db() -> Result<()> {
     safety_tool::logger::init();
     let path = "a.sqlite3";
     let _span = error_span!("test_db", path).entered();
     let mut db = Database::new(path)?;
-    db.save_data([Data {
-        hash: PrimaryKey { hash1: 1, hash2: 2 },
-        func: Func {
-            tool_attrs: vec!["Safety".to_owned()],
-            def_path: "a::b".to_owned(),
-            function: "fn f() {}".to_owned(),
-        },
-    }])?;
-    dbg!(db.get_all_data()?);
-    std::fs::remove_file(path)?;
+    let a = CrateScope { id: 1, name: "a".to_owned(), version: "0.1.0".to_owned() };
+    let a2 = CrateScope { id: 2, name: "a".to_owned(), version: "0.2.0".to_owned() };
+    // The test harness of a 0.1.0, which is the same crate name and version.
+    let a_test = CrateScope { id: 3, name: "a".to_owned(), version: "0.1.0".to_owned() };
+
+    db.save_data(&a, &[data(1, "a::b"), data(2, "a::c")])?;
+    db.save_data(&a2, &[data(1, "a::b")])?;
+    db.save_data(&a_test, &[data(1, "a::b")])?;
+    let tags = db.get_tags(key(2))?.unwrap();
+    assert_eq!(&*tags.requires.vanilla, [Box::from("ValidPtr")]);
+
+    // `a::c` is removed from a 0.1.0, and swept.
+    db.save_data(&a, &[data(1, "a::b")])?;
+    assert!(db.get_tags(key(1))?.is_some());
+    assert!(db.get_tags(key(2))?.is_none());
+
+    // Reopening doesn't migrate again.
+    drop(db);
+    assert!(Database::new(path)?.get_tags(key(1))?.is_some());
+
+    for suffix in ["", "-wal", "-shm"] {
+        _ = std::fs::remove_file(format!("{path}{suffix}"));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_db_migrate_unversioned() -> Result<()> {
+    let path = "b.sqlite3";
+    let _span = error_span!("test_db_migrate_unversioned", path).entered();
+    {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(MIGRATIONS[0])?;
+        conn.execute(
+            "INSERT INTO hir VALUES (1, 2, '[\"#[rapx::requires(Align)]\"]', 'old::f', \
+             'fn f() {}', '2025-01-01T00:00:00Z')",
+            [],
+        )?;
+        // A row of a crate saved in version 2, which has no parsed tags.
+        conn.execute_batch(CREATE_SCHEMA_VERSION)?;
+        conn.execute_batch(MIGRATIONS[1])?;
+        conn.execute_batch("INSERT INTO schema_version (version) VALUES (1), (2)")?;
+        conn.execute(
+            "INSERT INTO hir VALUES ('a', '0.1.0', 3, 2, '[\"#[rapx::requires(Align)]\"]', \
+             'a::f', 'fn f() {}', '2025-01-01T00:00:00Z')",
+            [],
+        )?;
+    }
+
+    let mut db = Database::new(path)?;
+    // Rows of no crate are dropped, and so are rows without crate ids.
+    assert!(db.get_tags(key(1))?.is_none());
+    assert!(db.get_tags(key(3))?.is_none());
+    // Tags of rows without parsed tags are parsed from attrs.
+    db.conn.execute(
+        "INSERT INTO hir VALUES (1, 'a', '0.1.0', 4, 2, '[\"#[rapx::requires(Align)]\"]', \
+         'a::g', 'fn g() {}', 1, NULL)",
+        [],
+    )?;
+    let tags = db.get_tags(key(4))?.unwrap();
+    assert_eq!(&*tags.requires.vanilla, [Box::from("Align")]);
+
+    for suffix in ["", "-wal", "-shm"] {
+        _ = std::fs::remove_file(format!("{path}{suffix}"));
+    }
     Ok(())
 }
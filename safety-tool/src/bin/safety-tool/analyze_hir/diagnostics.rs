@@ -1,5 +1,5 @@
 use annotate_snippets::{Level, Renderer, Snippet};
-use rustc_hir::HirId;
+use rustc_hir::{HirId, def_id::LocalDefId};
 use rustc_middle::ty::TyCtxt;
 use rustc_span::{
     Span,
@@ -8,7 +8,7 @@ use rustc_span::{
 use std::{ops::Range, sync::Arc};
 
 /// A report / diagnostic to display.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum DiagnosticKind {
     // A non-existent tag is specified.
     // InvaidTag,
@@ -16,6 +16,34 @@ enum DiagnosticKind {
     MissingDischarge,
     /// The tag has been discharged.
     DuplicatedDischarge,
+    /// `#[ref]` refers to a non-existent anchor.
+    DanglingRef,
+    /// `#[ref]` has no anchor name.
+    UnnamedRef,
+    /// An anchor is named the same as another one.
+    DuplicatedAnchor,
+    /// Call-order tags like `CallOnce` or `PostToFunc` are violated.
+    CallOrder,
+    /// A hazard is not resolved on some path before return.
+    UnresolvedHazard,
+    /// A call may unwind with a hazard open, and the cleanup path doesn't resolve it.
+    UnwindingHazard,
+    /// A tag arg doesn't match the signature of the tagged function.
+    InvalidTagArg,
+    /// A type arg of a tag isn't found in the scope of the tagged function, but may be visible
+    /// through glob imports or the prelude. This is a warning, which doesn't abort.
+    UnresolvedTagArg,
+    /// The tag is discharged explicitly, but auto-discharged for every call it covers. This is a
+    /// warning, which doesn't abort.
+    RedundantDischarge,
+    /// A tag evaluated at the call site never holds, e.g. a constant out of `ValidNum` range.
+    ViolatedTag,
+}
+
+impl DiagnosticKind {
+    fn is_warning(self) -> bool {
+        matches!(self, DiagnosticKind::RedundantDischarge | DiagnosticKind::UnresolvedTagArg)
+    }
 }
 
 struct Diagnostic {
@@ -31,6 +59,46 @@ impl Diagnostic {
     pub fn duplicated_discharge(render: Box<str>) -> Self {
         Diagnostic { render, kind: DiagnosticKind::DuplicatedDischarge }
     }
+
+    fn dangling_ref(render: Box<str>) -> Self {
+        Diagnostic { render, kind: DiagnosticKind::DanglingRef }
+    }
+
+    fn unnamed_ref(render: Box<str>) -> Self {
+        Diagnostic { render, kind: DiagnosticKind::UnnamedRef }
+    }
+
+    fn duplicated_anchor(render: Box<str>) -> Self {
+        Diagnostic { render, kind: DiagnosticKind::DuplicatedAnchor }
+    }
+
+    fn call_order(render: Box<str>) -> Self {
+        Diagnostic { render, kind: DiagnosticKind::CallOrder }
+    }
+
+    fn unresolved_hazard(render: Box<str>) -> Self {
+        Diagnostic { render, kind: DiagnosticKind::UnresolvedHazard }
+    }
+
+    fn unwinding_hazard(render: Box<str>) -> Self {
+        Diagnostic { render, kind: DiagnosticKind::UnwindingHazard }
+    }
+
+    fn invalid_tag_arg(render: Box<str>) -> Self {
+        Diagnostic { render, kind: DiagnosticKind::InvalidTagArg }
+    }
+
+    fn unresolved_tag_arg(render: Box<str>) -> Self {
+        Diagnostic { render, kind: DiagnosticKind::UnresolvedTagArg }
+    }
+
+    fn redundant_discharge(render: Box<str>) -> Self {
+        Diagnostic { render, kind: DiagnosticKind::RedundantDischarge }
+    }
+
+    fn violated_tag(render: Box<str>) -> Self {
+        Diagnostic { render, kind: DiagnosticKind::ViolatedTag }
+    }
 }
 
 /// How to emit diagnostics.
@@ -79,9 +147,13 @@ impl ExitAndEmit {
 
 fn total(diagnostics: &[Diagnostic]) {
     use annotate_snippets::renderer::{AnsiColor, Style};
-    use itertools::Itertools;
+    use std::collections::BTreeMap;
 
-    let counts = diagnostics.iter().counts_by(|d| d.kind);
+    // Sort kinds for stable output.
+    let mut counts = BTreeMap::<DiagnosticKind, usize>::new();
+    for diagnostic in diagnostics {
+        *counts.entry(diagnostic.kind).or_default() += 1;
+    }
     let style = Style::new().bold().fg_color(Some(AnsiColor::Red.into()));
     eprintln!("{style}Total counts of diagnostics from safety-tool: {counts:?}{style:#}\n");
 }
@@ -109,10 +181,59 @@ impl<'tcx> EmitDiagnostics<'tcx> {
 
     #[must_use]
     fn generate(&mut self, hir_id: HirId, title: &str, info: &[String]) -> Box<str> {
+        self.generate_with_label(hir_id, title, "For this unsafe call.", info)
+    }
+
+    #[must_use]
+    fn generate_with_label(
+        &mut self,
+        hir_id: HirId,
+        title: &str,
+        label: &str,
+        info: &[String],
+    ) -> Box<str> {
         let span_node = hir_span(hir_id, self.tcx);
         // error!(span_node = %self.src_map.span_to_snippet(span_node).unwrap());
-        let span_body = self.tcx.source_span(hir_id.owner);
+        self.generate_with_span(hir_id.owner.def_id, span_node, title, label, info)
+    }
+
+    /// Point out the span in the body of the owner, e.g. a span from MIR.
+    #[must_use]
+    fn generate_with_span(
+        &mut self,
+        owner: LocalDefId,
+        span_node: Span,
+        title: &str,
+        label: &str,
+        info: &[String],
+    ) -> Box<str> {
+        let span_body = self.tcx.source_span(owner);
+        self.generate_in_snippet(span_body, span_node, title, label, info)
+    }
 
+    /// Point out the span in the snippet of span_body.
+    #[must_use]
+    fn generate_in_snippet(
+        &mut self,
+        span_body: Span,
+        span_node: Span,
+        title: &str,
+        label: &str,
+        info: &[String],
+    ) -> Box<str> {
+        self.generate_with_level(Level::Error, span_body, span_node, title, label, info)
+    }
+
+    #[must_use]
+    fn generate_with_level(
+        &mut self,
+        level: Level,
+        span_body: Span,
+        span_node: Span,
+        title: &str,
+        label: &str,
+        info: &[String],
+    ) -> Box<str> {
         // Point out an unsafe call with underlines.
         let range = match range_of_call(span_body, span_node) {
             Ok(range) => range,
@@ -120,7 +241,7 @@ impl<'tcx> EmitDiagnostics<'tcx> {
             // Currently, only the macro callsite is reported.
             Err(range) => range,
         };
-        let anno_call = Level::Error.span(range).label("For this unsafe call.");
+        let anno_call = level.span(range).label(label);
 
         let src_body = self.src_map.span_to_snippet(span_body).unwrap();
         let file_and_line = self.src_map.lookup_line(span_body.lo()).unwrap();
@@ -129,16 +250,28 @@ impl<'tcx> EmitDiagnostics<'tcx> {
         let snippet = Snippet::source(&src_body).line_start(line_start).origin(&origin).fold(true);
 
         // Point out the problematic snippet.
-        let msg = Level::Error
+        let msg = level
             .title(title)
             .snippet(snippet.annotation(anno_call))
             .footers(info.iter().map(|info| Level::Info.title(info)));
         Renderer::styled().render(msg).to_string().into()
     }
 
-    /// Add a diagnostic based on an unsafe call. Title is the first line of error msg.
-    pub fn push_missing_discharge(&mut self, hir_id: HirId, title: &str, info: &[String]) {
-        let render = self.generate(hir_id, title, info);
+    /// Add a diagnostic based on an unsafe call or operation. Title is the first line of error msg.
+    pub fn push_missing_discharge(
+        &mut self,
+        hir_id: HirId,
+        title: &str,
+        label: &str,
+        info: &[String],
+    ) {
+        let render = self.generate_with_label(hir_id, title, label, info);
+        self.diagnostics.push(Diagnostic::missing_discharge(render));
+    }
+
+    /// Add a diagnostic based on an unsafe impl or its method.
+    pub fn push_missing_discharge_on_impl(&mut self, hir_id: HirId, title: &str, info: &[String]) {
+        let render = self.generate_with_label(hir_id, title, "For this unsafe impl.", info);
         self.diagnostics.push(Diagnostic::missing_discharge(render));
     }
 
@@ -147,7 +280,109 @@ impl<'tcx> EmitDiagnostics<'tcx> {
         self.diagnostics.push(Diagnostic::duplicated_discharge(render));
     }
 
-    /// Emit diagnostics, respecting EXIT_AND_EMIT.
+    pub fn push_dangling_ref(&mut self, hir_id: HirId, name: &str) {
+        let title = format!("Anchor `{name}` is not found");
+        let info = [format!("Name the discharge through `#[checked {{ name = {name}; ... }}]`.")];
+        let render = self.generate_with_label(hir_id, &title, "For this reference.", &info);
+        self.diagnostics.push(Diagnostic::dangling_ref(render));
+    }
+
+    pub fn push_unnamed_ref(&mut self, hir_id: HirId) {
+        let title = "`#[ref]` requires an anchor name";
+        let info = ["Refer to a named discharge through `#[ref(anchor)]`.".to_owned()];
+        let render = self.generate_with_label(hir_id, title, "For this reference.", &info);
+        self.diagnostics.push(Diagnostic::unnamed_ref(render));
+    }
+
+    /// Add a diagnostic on an anchor named the same as the first one at the location.
+    pub fn push_duplicated_anchor(&mut self, hir_id: HirId, name: &str, first: &str) {
+        let title = format!("Anchor `{name}` is named more than once");
+        let info = [format!("`{name}` is first named at {first}.")];
+        let render = self.generate_with_label(hir_id, &title, "For this discharge.", &info);
+        self.diagnostics.push(Diagnostic::duplicated_anchor(render));
+    }
+
+    /// Add a diagnostic on a call in MIR of the owner, which violates call-order tags.
+    pub fn push_call_order(&mut self, owner: LocalDefId, span: Span, title: &str, info: &[String]) {
+        let owner = self.tcx.local_def_id_to_hir_id(owner).owner.def_id;
+        let render = self.generate_with_span(owner, span, title, "For this call.", info);
+        self.diagnostics.push(Diagnostic::call_order(render));
+    }
+
+    /// Add a diagnostic on a hazardous call in MIR of the owner.
+    pub fn push_unresolved_hazard(
+        &mut self,
+        owner: LocalDefId,
+        span: Span,
+        title: &str,
+        info: &[String],
+    ) {
+        let owner = self.tcx.local_def_id_to_hir_id(owner).owner.def_id;
+        let render = self.generate_with_span(owner, span, title, "For this hazardous call.", info);
+        self.diagnostics.push(Diagnostic::unresolved_hazard(render));
+    }
+
+    /// Add a diagnostic on a call in MIR of the owner, which may unwind with a hazard open.
+    pub fn push_unwinding_hazard(
+        &mut self,
+        owner: LocalDefId,
+        span: Span,
+        title: &str,
+        info: &[String],
+    ) {
+        let owner = self.tcx.local_def_id_to_hir_id(owner).owner.def_id;
+        let render =
+            self.generate_with_span(owner, span, title, "For this call which may unwind.", info);
+        self.diagnostics.push(Diagnostic::unwinding_hazard(render));
+    }
+
+    /// Add a diagnostic on a tool attr of the function, whose tag args are invalid.
+    pub fn push_invalid_tag_arg(
+        &mut self,
+        owner: LocalDefId,
+        span: Span,
+        title: &str,
+        info: &[String],
+    ) {
+        // Attributes are out of the item span, so extend the snippet to cover them.
+        let span = span.source_callsite();
+        let span_item = self.tcx.source_span(owner).to(span);
+        let render = self.generate_in_snippet(span_item, span, title, "For this tag.", info);
+        self.diagnostics.push(Diagnostic::invalid_tag_arg(render));
+    }
+
+    /// Add a warning on a tool attr of the function, whose type arg isn't found in scope.
+    pub fn push_unresolved_tag_arg(
+        &mut self,
+        owner: LocalDefId,
+        span: Span,
+        title: &str,
+        info: &[String],
+    ) {
+        let span = span.source_callsite();
+        let span_item = self.tcx.source_span(owner).to(span);
+        let label = "For this tag.";
+        let render = self.generate_with_level(Level::Warning, span_item, span, title, label, info);
+        self.diagnostics.push(Diagnostic::unresolved_tag_arg(render));
+    }
+
+    /// Add a warning on a node with a tool attr, whose discharge is unnecessary.
+    pub fn push_redundant_discharge(&mut self, hir_id: HirId, title: &str, info: &[String]) {
+        let span_node = hir_span(hir_id, self.tcx);
+        let span_body = self.tcx.source_span(hir_id.owner.def_id);
+        let label = "For this discharge.";
+        let render =
+            self.generate_with_level(Level::Warning, span_body, span_node, title, label, info);
+        self.diagnostics.push(Diagnostic::redundant_discharge(render));
+    }
+
+    /// Add a diagnostic on an unsafe call, which violates a tag regardless of discharges.
+    pub fn push_violated_tag(&mut self, hir_id: HirId, title: &str, info: &[String]) {
+        let render = self.generate(hir_id, title, info);
+        self.diagnostics.push(Diagnostic::violated_tag(render));
+    }
+
+    /// Emit diagnostics, respecting EXIT_AND_EMIT. Warnings alone don't abort.
     pub fn emit(self) {
         let Self { diagnostics, exit_and_emit, .. } = self;
         if !diagnostics.is_empty() {
@@ -157,7 +392,7 @@ impl<'tcx> EmitDiagnostics<'tcx> {
                 }
                 total(&diagnostics);
             }
-            if exit_and_emit.should_abort() {
+            if exit_and_emit.should_abort() && diagnostics.iter().any(|d| !d.kind.is_warning()) {
                 std::process::abort()
             }
         }
@@ -165,7 +400,7 @@ impl<'tcx> EmitDiagnostics<'tcx> {
 }
 
 /// Get HIR node span.
-fn hir_span(hir_id: HirId, tcx: TyCtxt) -> Span {
+pub fn hir_span(hir_id: HirId, tcx: TyCtxt) -> Span {
     crossfig::switch! {
         crate::asterinas => { tcx.hir().span(hir_id) }
         _ => { tcx.hir_span(hir_id) }
@@ -0,0 +1,174 @@
+//! Entity references: `#[ref(anchor)]` on a site discharges the same tags as the
+//! named anchor `#[checked { name = anchor; ... }]`.
+use super::{
+    db::{Property, tool_attr_on_hir, tool_attr_to_string},
+    diagnostics::{EmitDiagnostics, hir_span},
+    stat::Stat,
+};
+use crate::{is_tool_attr, is_tool_attr_named};
+use rustc_data_structures::fx::{FxHashMap, FxHashSet, FxIndexMap};
+use rustc_hir::HirId;
+use rustc_middle::ty::TyCtxt;
+use rustc_span::Span;
+use safety_parser::{safety::SafetyAttr, syn};
+use safety_tool::entity_ref::{self, Linked, Site};
+
+struct Anchor {
+    hir_id: HirId,
+    /// The full `#[rapx::checked { name = anchor; ... }]` attribute.
+    attr: String,
+}
+
+pub struct EntityRefs {
+    anchors: FxIndexMap<String, Anchor>,
+    /// Referencing site to the anchor name.
+    refs: FxHashMap<HirId, String>,
+    /// `#[ref]` sites without an anchor name.
+    unnamed: Vec<HirId>,
+    /// Anchors named the same as an earlier one, which are ignored.
+    duplicated: Vec<(HirId, String)>,
+}
+
+impl EntityRefs {
+    /// Collect named anchors and references in the local crate.
+    pub fn new(tcx: TyCtxt) -> Self {
+        let mut anchors = FxIndexMap::default();
+        let mut refs = FxHashMap::default();
+        let mut unnamed = Vec::new();
+        let mut duplicated = Vec::new();
+        // Attributes on a let statement are found on both the statement and the local.
+        let mut seen = FxHashSet::default();
+
+        for owner in tcx.hir_crate_items(()).owners() {
+            crossfig::switch! {
+                crate::asterinas => { let map = &tcx.hir_attrs(owner).map; }
+                _ => { let map = &tcx.hir_attr_map(owner).map; }
+            }
+            for &(local_id, attrs) in map.iter() {
+                let hir_id = HirId { owner, local_id };
+                for attr in attrs.iter() {
+                    if !is_tool_attr(attr) || !seen.insert(attr.span()) {
+                        continue;
+                    }
+                    if is_tool_attr_named(attr, "ref") {
+                        let name = attr.meta_item_list().and_then(|list| {
+                            list.first()
+                                .and_then(|item| item.ident())
+                                .map(|ident| ident.to_string())
+                        });
+                        match name {
+                            Some(name) => _ = refs.insert(hir_id, name),
+                            None => unnamed.push(hir_id),
+                        }
+                        continue;
+                    }
+                    let Some(attr) = tool_attr_to_string(tcx, attr) else { continue };
+                    let Some(name) = anchor_name(&attr) else { continue };
+                    if anchors.contains_key(&name) {
+                        duplicated.push((hir_id, name));
+                    } else {
+                        anchors.insert(name, Anchor { hir_id, attr });
+                    }
+                }
+            }
+        }
+
+        EntityRefs { anchors, refs, unnamed, duplicated }
+    }
+
+    fn anchor_of(&self, hir_id: HirId) -> Option<&Anchor> {
+        self.anchors.get(self.refs.get(&hir_id)?)
+    }
+
+    /// Tool attrs on the node, plus the anchor attr if the node refers to one.
+    pub fn tool_attrs(&self, hir_id: HirId, tcx: TyCtxt) -> Vec<String> {
+        let mut attrs: Vec<_> = tool_attr_on_hir(hir_id, tcx).collect();
+        if let Some(anchor) = self.anchor_of(hir_id) {
+            attrs.push(anchor.attr.clone());
+        }
+        attrs
+    }
+
+    /// Discharged properties on the node, including these shared from the anchor.
+    pub fn properties(&self, hir_id: HirId, tcx: TyCtxt) -> Vec<Property> {
+        let mut v = Property::new_with_hir_id(hir_id, tcx);
+        if let Some(anchor) = self.anchor_of(hir_id) {
+            v.extend(Property::new_with_attr(&anchor.attr));
+        }
+        v
+    }
+
+    /// References to a non-existent anchor or without an anchor name, and anchors named the
+    /// same as another one are errors.
+    pub fn check(&self, diagnostics: &mut EmitDiagnostics) {
+        let tcx = diagnostics.tcx();
+        let mut dangling: Vec<_> =
+            self.refs.iter().filter(|(_, name)| !self.anchors.contains_key(*name)).collect();
+        dangling.sort_unstable_by_key(|(hir_id, _)| hir_span(**hir_id, tcx));
+        for (&hir_id, name) in dangling {
+            diagnostics.push_dangling_ref(hir_id, name);
+        }
+        for &hir_id in &self.unnamed {
+            diagnostics.push_unnamed_ref(hir_id);
+        }
+        for (hir_id, name) in &self.duplicated {
+            let first = location(hir_span(self.anchors[name].hir_id, tcx), tcx);
+            diagnostics.push_duplicated_anchor(*hir_id, name, &first);
+        }
+    }
+
+    /// Write linked sites to `SP_OUT_DIR/<crate>.refs.json`, and sites impacted since the
+    /// previous run to `SP_OUT_DIR/<crate>.impacted.json`.
+    pub fn write_to_file(&self, tcx: TyCtxt, stat: &Stat) {
+        if self.refs.is_empty() && self.anchors.is_empty() {
+            return;
+        }
+        let Some(refs_path) = stat.krate.output_file_path("refs.json") else { return };
+        let Some(impacted_path) = stat.krate.output_file_path("impacted.json") else { return };
+
+        let site = |hir_id: HirId| {
+            let span = hir_span(hir_id, tcx);
+            let source = tcx.sess.source_map().span_to_snippet(span).unwrap_or_default();
+            Site { location: location(span, tcx), source }
+        };
+
+        let mut current = entity_ref::EntityRefs::default();
+        for (name, anchor) in &self.anchors {
+            current.map.entry(name.clone()).or_default().anchor = Some(site(anchor.hir_id));
+        }
+        let mut refs: Vec<_> = self.refs.iter().collect();
+        refs.sort_unstable_by_key(|(hir_id, _)| hir_span(**hir_id, tcx));
+        for (&hir_id, name) in refs {
+            current.map.entry(name.clone()).or_insert_with(Linked::default).refs.push(site(hir_id));
+        }
+
+        let prev: Option<entity_ref::EntityRefs> = std::fs::read_to_string(&refs_path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok());
+        if let Some(prev) = prev {
+            let impacted = current.impacted(&prev);
+            if impacted.is_empty() {
+                _ = std::fs::remove_file(&impacted_path);
+            } else if let Ok(file) = std::fs::File::create(&impacted_path) {
+                _ = serde_json::to_writer_pretty(file, &impacted);
+            }
+        }
+
+        if let Ok(file) = std::fs::File::create(&refs_path) {
+            _ = serde_json::to_writer_pretty(file, &current);
+        }
+    }
+}
+
+/// `file:line:col` of the span.
+fn location(span: Span, tcx: TyCtxt) -> String {
+    let loc = tcx.sess.source_map().lookup_char_pos(span.lo());
+    format!("{}:{}:{}", loc.file.name.prefer_local(), loc.line, loc.col.0 + 1)
+}
+
+/// Get the anchor name from `#[rapx::checked { name = anchor; ... }]`.
+fn anchor_name(attr: &str) -> Option<String> {
+    let attr = syn::parse_str::<SafetyAttr>(attr).ok()?;
+    let seg = &attr.attr.path().segments;
+    (seg.last()?.ident == "checked").then(|| attr.args.name.map(|name| name.to_string()))?
+}
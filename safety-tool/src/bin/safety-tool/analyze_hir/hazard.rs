@@ -0,0 +1,279 @@
+//! Hazard lifecycle: a hazard tag with `resolved_by` in spec is opened by the unsafe call, and
+//! must be resolved on every path before the caller returns, through
+//! * `#[resolves { Tag }]` on a statement or expression, where `Tag` is the hazard or a tag
+//!   in `resolved_by`, or
+//! * calling a function in `resolved_by`, or a function tagged with such `#[resolves]`.
+//!
+//! Callers tagged with the same hazard pass it on to their callers.
+//!
+//! A call that may unwind while the hazard is open must resolve it on the unwinding path too,
+//! which is done by a drop guard whose `Drop::drop` is tagged with `#[resolves]`. Dropping such
+//! a guard also resolves the hazard on normal paths.
+use super::{
+    def_path_matches,
+    diagnostics::{EmitDiagnostics, hir_span},
+    properties_in_attrs, required_properties,
+};
+use crate::is_tool_attr_named;
+use rustc_hir::{
+    HirId,
+    def::DefKind,
+    def_id::{DefId, LocalDefId},
+};
+use rustc_middle::{
+    mir::{BasicBlock, Body, TerminatorKind, UnwindAction},
+    ty::TyCtxt,
+};
+use rustc_span::Span;
+use safety_parser::safety::parse_attr_and_get_properties;
+
+pub fn check_hazards(tcx: TyCtxt, diagnostics: &mut EmitDiagnostics) {
+    for local in tcx.hir_body_owners() {
+        if !matches!(tcx.def_kind(local), DefKind::Fn | DefKind::AssocFn)
+            || !tcx.is_mir_available(local)
+        {
+            continue;
+        }
+        let body = tcx.optimized_mir(local);
+        let hazards = hazards_in_body(tcx, local, body);
+        if hazards.is_empty() {
+            continue;
+        }
+        let resolutions = Resolutions::new(tcx, local, body);
+        for hazard in &hazards {
+            if let Some(exit) = unresolved_exit(body, hazard, &resolutions) {
+                let title = format!("Hazard `{}` is not resolved before return", hazard.tag);
+                let exit = tcx.sess.source_map().span_to_diagnostic_string(exit);
+                let info = [
+                    format!("The function returns at {exit} with the hazard open."),
+                    format!(
+                        "Resolve it through `#[resolves {{ {} }}]`, or one of `{}`.",
+                        hazard.tag,
+                        hazard.resolved_by.join("`, `")
+                    ),
+                ];
+                diagnostics.push_unresolved_hazard(local, hazard.span, &title, &info);
+            }
+            check_unwinding(tcx, local, body, hazard, &resolutions, diagnostics);
+        }
+    }
+}
+
+/// Report calls and assertions that may unwind with the hazard open, unless the cleanup path
+/// resolves it.
+fn check_unwinding(
+    tcx: TyCtxt,
+    local: LocalDefId,
+    body: &Body,
+    hazard: &Hazard,
+    resolutions: &Resolutions,
+    diagnostics: &mut EmitDiagnostics,
+) {
+    let open = open_blocks(body, hazard, resolutions);
+    for (bb, data) in body.basic_blocks.iter_enumerated() {
+        if !open[bb.as_usize()] || data.is_cleanup || resolutions.resolves(bb, hazard) {
+            continue;
+        }
+        let terminator = data.terminator();
+        let (what, span) = match &terminator.kind {
+            TerminatorKind::Call { func, fn_span, .. } => {
+                let callee = func.const_fn_def().map(|(callee, _)| tcx.def_path_str(callee));
+                (format!("`{}` may panic", callee.as_deref().unwrap_or("the callee")), *fn_span)
+            }
+            TerminatorKind::Assert { .. } => {
+                ("the assertion may panic".to_owned(), terminator.source_info.span)
+            }
+            _ => continue,
+        };
+        let unresolved = match terminator.unwind() {
+            Some(UnwindAction::Continue) => true,
+            Some(UnwindAction::Cleanup(cleanup)) => {
+                unwinds_unresolved(body, *cleanup, hazard, resolutions)
+            }
+            _ => false,
+        };
+        if unresolved {
+            let title = format!("Unwinding here leaves hazard `{}` unresolved", hazard.tag);
+            let opened = tcx.sess.source_map().span_to_diagnostic_string(hazard.span);
+            let info = [
+                format!("The hazard is opened at {opened}, and {what}."),
+                format!(
+                    "Resolve it on unwinding through a drop guard whose `Drop::drop` is tagged \
+                     `#[resolves {{ {} }}]`.",
+                    hazard.tag
+                ),
+            ];
+            diagnostics.push_unwinding_hazard(local, span, &title, &info);
+        }
+    }
+}
+
+/// Whether unwinding from the cleanup block may resume without resolving the hazard.
+fn unwinds_unresolved(
+    body: &Body,
+    cleanup: BasicBlock,
+    hazard: &Hazard,
+    resolutions: &Resolutions,
+) -> bool {
+    let blocks = &body.basic_blocks;
+    let mut visited = vec![false; blocks.len()];
+    let mut stack = vec![cleanup];
+    while let Some(bb) = stack.pop() {
+        if std::mem::replace(&mut visited[bb.as_usize()], true) || resolutions.resolves(bb, hazard)
+        {
+            continue;
+        }
+        if matches!(blocks[bb].terminator().kind, TerminatorKind::UnwindResume) {
+            return true;
+        }
+        stack.extend(blocks[bb].terminator().successors());
+    }
+    false
+}
+
+/// A hazard opened by an unsafe call.
+pub struct Hazard {
+    /// The block the call returns to, where the hazard is open.
+    pub target: Option<BasicBlock>,
+    pub span: Span,
+    pub tag: String,
+    pub resolved_by: Box<[Box<str>]>,
+}
+
+/// Hazards with `resolved_by` opened by calls in the body. Hazards required by the function
+/// itself are not included, because they're propagated to callers.
+pub fn hazards_in_body(tcx: TyCtxt, local: LocalDefId, body: &Body) -> Vec<Hazard> {
+    let own: Vec<_> = required_properties(tcx, local.to_def_id())
+        .into_iter()
+        .filter(|sp| sp.tag.is_hazard())
+        .map(|sp| sp.tag.name().to_owned())
+        .collect();
+
+    let mut hazards = Vec::new();
+    for data in body.basic_blocks.iter() {
+        if let TerminatorKind::Call { func, target, fn_span, .. } = &data.terminator().kind
+            && let Some((callee, _)) = func.const_fn_def()
+        {
+            for sp in required_properties(tcx, callee) {
+                let name = sp.tag.name();
+                if !sp.tag.is_hazard() || own.iter().any(|tag| tag == name) {
+                    continue;
+                }
+                if let Some(spec) = sp.tag.get_spec()
+                    && !spec.resolved_by.is_empty()
+                {
+                    hazards.push(Hazard {
+                        target: *target,
+                        span: *fn_span,
+                        tag: name.to_owned(),
+                        resolved_by: spec.resolved_by.clone(),
+                    });
+                }
+            }
+        }
+    }
+    hazards
+}
+
+/// Resolution sites in the body.
+pub struct Resolutions<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    /// Span of the node with `#[resolves]` and the tag names.
+    annotated: Vec<(Span, Vec<String>)>,
+    /// Calls and guard drops in blocks with tag names in `#[resolves]` on callees.
+    calls: Vec<(BasicBlock, DefId, Vec<String>)>,
+    /// Spans of statements and terminators in each block.
+    spans: Vec<Vec<Span>>,
+}
+
+impl<'tcx> Resolutions<'tcx> {
+    pub fn new(tcx: TyCtxt<'tcx>, local: LocalDefId, body: &Body<'tcx>) -> Self {
+        let owner = tcx.local_def_id_to_hir_id(local).owner;
+        crossfig::switch! {
+            crate::asterinas => { let map = &tcx.hir_attrs(owner).map; }
+            _ => { let map = &tcx.hir_attr_map(owner).map; }
+        }
+        let mut annotated = Vec::new();
+        for &(local_id, attrs) in map.iter() {
+            for attr in attrs.iter().filter(|attr| is_tool_attr_named(attr, "resolves")) {
+                let attr = rustc_hir_pretty::attribute_to_string(&tcx, attr);
+                let tags = parse_attr_and_get_properties(attr.trim())
+                    .iter()
+                    .flat_map(|prop| prop.tags.iter().map(|sp| sp.tag.name().to_owned()))
+                    .collect();
+                annotated.push((hir_span(HirId { owner, local_id }, tcx), tags));
+            }
+        }
+
+        let mut calls = Vec::new();
+        let mut spans = Vec::with_capacity(body.basic_blocks.len());
+        for (bb, data) in body.basic_blocks.iter_enumerated() {
+            let mut v: Vec<_> = data.statements.iter().map(|stmt| stmt.source_info.span).collect();
+            v.push(data.terminator().source_info.span);
+            spans.push(v);
+
+            let callee = match &data.terminator().kind {
+                TerminatorKind::Call { func, .. } => func.const_fn_def().map(|(callee, _)| callee),
+                // Dropping a guard calls its `Drop::drop`.
+                TerminatorKind::Drop { place, .. } => place
+                    .ty(&body.local_decls, tcx)
+                    .ty
+                    .ty_adt_def()
+                    .and_then(|adt| adt.destructor(tcx))
+                    .map(|dtor| dtor.did),
+                _ => None,
+            };
+            if let Some(callee) = callee {
+                let tags =
+                    properties_in_attrs(tcx, callee, |attr| is_tool_attr_named(attr, "resolves"));
+                let tags = tags.iter().map(|sp| sp.tag.name().to_owned()).collect();
+                calls.push((bb, callee, tags));
+            }
+        }
+
+        Resolutions { tcx, annotated, calls, spans }
+    }
+
+    /// Whether the block resolves the hazard.
+    pub fn resolves(&self, bb: BasicBlock, hazard: &Hazard) -> bool {
+        let by_tag = |tag: &String| {
+            *tag == hazard.tag || hazard.resolved_by.iter().any(|name| **name == **tag)
+        };
+        let annotated = self.annotated.iter().filter(|(_, tags)| tags.iter().any(by_tag));
+        for (span, _) in annotated {
+            if self.spans[bb.as_usize()].iter().any(|s| !s.is_dummy() && span.contains(*s)) {
+                return true;
+            }
+        }
+        self.calls.iter().filter(|(b, ..)| *b == bb).any(|(_, callee, tags)| {
+            tags.iter().any(by_tag)
+                || hazard.resolved_by.iter().any(|path| def_path_matches(self.tcx, *callee, path))
+        })
+    }
+}
+
+/// Blocks where the hazard may be open at the entry, i.e. reachable from the normal return
+/// of the hazardous call without passing through a resolution.
+pub fn open_blocks(body: &Body, hazard: &Hazard, resolutions: &Resolutions) -> Vec<bool> {
+    let blocks = &body.basic_blocks;
+    let mut open = vec![false; blocks.len()];
+    let mut stack: Vec<_> = hazard.target.into_iter().collect();
+    while let Some(bb) = stack.pop() {
+        if std::mem::replace(&mut open[bb.as_usize()], true) || resolutions.resolves(bb, hazard) {
+            continue;
+        }
+        stack.extend(blocks[bb].terminator().successors());
+    }
+    open
+}
+
+/// The span of a return reached with the hazard open.
+fn unresolved_exit(body: &Body, hazard: &Hazard, resolutions: &Resolutions) -> Option<Span> {
+    let open = open_blocks(body, hazard, resolutions);
+    body.basic_blocks.iter_enumerated().find_map(|(bb, data)| {
+        (open[bb.as_usize()]
+            && matches!(data.terminator().kind, TerminatorKind::Return)
+            && !resolutions.resolves(bb, hazard))
+        .then_some(data.terminator().source_info.span)
+    })
+}
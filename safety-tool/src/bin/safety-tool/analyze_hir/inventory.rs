@@ -0,0 +1,88 @@
+//! Collect unsafe functions and methods of the local crate into [`Inventory`].
+use super::{diagnostics::hir_span, overlay_path, required_properties, stat::is_unsafe};
+use rustc_hir::{HirId, def::DefKind};
+use rustc_middle::ty::TyCtxt;
+use safety_tool::{
+    inventory::{Inventory, UnsafeApi, has_safety_section},
+    stat::Krate,
+};
+
+fn new(tcx: TyCtxt) -> Inventory {
+    let mut inventory = Inventory::default();
+    let effective_visibilities = tcx.effective_visibilities(());
+    for local in tcx.hir_crate_items(()).definitions() {
+        if !matches!(tcx.def_kind(local), DefKind::Fn | DefKind::AssocFn) {
+            continue;
+        }
+        let def_id = local.to_def_id();
+        if !is_unsafe(def_id, tcx) {
+            continue;
+        }
+
+        let hir_id = tcx.local_def_id_to_hir_id(local);
+        let mut tags = Vec::new();
+        for prop in required_properties(tcx, def_id) {
+            match prop.args_in_any_tag() {
+                Some(props) => {
+                    let names: Vec<_> = props.iter().flat_map(|p| &p.tags).collect();
+                    for (idx, prop) in names.iter().enumerate() {
+                        let sep = if idx + 1 == names.len() { "" } else { " |" };
+                        tags.push(format!("{}{sep}", prop.tag.name()));
+                    }
+                }
+                None => {
+                    let name = prop.tag.name().to_owned();
+                    if !tags.contains(&name) {
+                        tags.push(name);
+                    }
+                }
+            }
+        }
+
+        let api = UnsafeApi {
+            api: tcx.def_path_str(def_id),
+            visibility: tcx.visibility(def_id).expect_local().to_string(local, tcx),
+            exported: effective_visibilities.is_exported(local),
+            tags: [("0".to_owned(), tags)].into_iter().collect(),
+            safety_doc: has_safety_section(&doc(tcx, hir_id)),
+            location: location(tcx, hir_id),
+        };
+        inventory.map.entry(overlay_path(tcx, def_id)).or_default().push(api);
+    }
+    inventory
+}
+
+/// `file:line:col` of the item.
+pub(super) fn location(tcx: TyCtxt, hir_id: HirId) -> String {
+    let span = hir_span(hir_id, tcx);
+    let loc = tcx.sess.source_map().lookup_char_pos(span.lo());
+    format!("{}:{}:{}", loc.file.name.prefer_local(), loc.line, loc.col.0 + 1)
+}
+
+/// Doc comments and `#[doc]` on the item, one per line.
+pub(super) fn doc(tcx: TyCtxt, hir_id: HirId) -> String {
+    crossfig::switch! {
+        crate::asterinas => { let attrs = tcx.hir_attrs(hir_id.owner).get(hir_id.local_id); }
+        _ => { let attrs = tcx.hir_attrs(hir_id); }
+    }
+    let mut doc = String::new();
+    for sym in attrs.iter().filter_map(|attr| attr.doc_str()) {
+        doc.push_str(sym.as_str());
+        doc.push('\n');
+    }
+    doc
+}
+
+/// Write the inventory when `SP_OUT_DIR` is set and there is any unsafe function.
+pub fn write_to_file(tcx: TyCtxt, krate: &Krate) {
+    let Some(json) = krate.output_file_path("inventory.json") else { return };
+    let Some(md) = krate.output_file_path("inventory.md") else { return };
+    let inventory = new(tcx);
+    if inventory.map.is_empty() {
+        return;
+    }
+    if let Ok(file) = std::fs::File::create(json) {
+        _ = serde_json::to_writer_pretty(file, &inventory);
+    }
+    _ = std::fs::write(md, inventory.to_markdown());
+}
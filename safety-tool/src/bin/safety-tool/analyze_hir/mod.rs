@@ -1,21 +1,56 @@
-use crate::is_tool_attr;
-use rustc_hir::{BodyId, FnSig, HirId, ImplItemKind, ItemKind, Node, def_id::LocalDefId};
-use rustc_middle::ty::TyCtxt;
-use rustc_span::Ident;
+use crate::{is_tool_attr, is_tool_attr_named};
+use rustc_data_structures::fx::{FxIndexMap, FxIndexSet};
+use rustc_hir::{
+    Attribute, BodyId, FnSig, HirId, ImplItemKind, ItemKind, Node, TraitItemKind,
+    def_id::{DefId, LocalDefId},
+};
+use rustc_middle::{
+    mir::{Body, BorrowKind, Local, Operand, RawPtrKind, Rvalue, TerminatorKind},
+    ty::{GenericArgsRef, TyCtxt},
+};
+use rustc_span::{Ident, Span};
+use safety_parser::safety::{Property as SP, parse_attr_and_get_properties};
 
+pub mod apply_tags;
+mod audit;
+pub mod boundary;
+mod call_order;
+mod const_eval;
 mod db;
 mod diagnostics;
+mod entity_ref;
+mod hazard;
+mod inventory;
+mod option;
+mod provenance;
 mod stat;
+mod tag_args;
+mod unsafe_impl;
 mod visit;
 
-pub fn analyze_hir(tcx: TyCtxt) {
+pub fn analyze_hir(tcx: TyCtxt) -> Analysis {
     let mut stat = stat::new(tcx);
     let mut v_hir_fn = Vec::with_capacity(64);
+    // Tagged items other than functions with bodies, e.g. unsafe traits and their methods,
+    // and types with invariants.
+    let mut v_data = Vec::new();
 
     let def_items = tcx.hir_crate_items(()).definitions();
     for local_def_id in def_items {
         let node = tcx.hir_node_by_def_id(local_def_id);
 
+        if matches!(node, Node::Item(item) if matches!(
+            item.kind,
+            ItemKind::Trait(..) | ItemKind::Struct(..) | ItemKind::Enum(..) | ItemKind::Union(..)
+        )) || matches!(node, Node::TraitItem(item) if matches!(item.kind, TraitItemKind::Fn(..)))
+        {
+            let hir_id = tcx.local_def_id_to_hir_id(local_def_id);
+            if has_tool_attrs(hir_id, tcx) {
+                v_data.push(db::Data::new(local_def_id, hir_id, tcx));
+            }
+            continue;
+        }
+
         // fn item or associated fn item
         let hir_fn = match node {
             Node::Item(item) if matches!(item.kind, ItemKind::Fn { .. }) => {
@@ -41,14 +76,21 @@ pub fn analyze_hir(tcx: TyCtxt) {
         v_hir_fn.push(hir_fn);
     }
 
-    let mut tool_attrs =
-        db::get_all_tool_attrs(v_hir_fn.iter().filter_map(|f| f.to_data(tcx))).unwrap();
+    let data = v_hir_fn.iter().filter_map(|f| f.to_data(tcx)).chain(v_data).collect();
+    let mut tool_attrs = db::get_all_tool_attrs(tcx, data).unwrap();
     let mut diagnostics = diagnostics::EmitDiagnostics::new(tcx);
+    let refs = entity_ref::EntityRefs::new(tcx);
+    let mut audit = safety_tool::audit::Audit::default();
+    refs.check(&mut diagnostics);
+    let crate_types = tag_args::crate_types(tcx);
 
     for hir_fn in &v_hir_fn {
         let fn_hir_id = hir_fn.hir_id;
+        tag_args::check_tag_args(tcx, hir_fn.local, &crate_types, &mut diagnostics);
         let rapx_attrs: Vec<_> = db::tool_attr_on_hir(fn_hir_id, tcx).collect();
         let mut stat_caller = stat::new_caller(fn_hir_id, tcx, &rapx_attrs);
+        let mut discharges = provenance::Discharges::default();
+        let mut forwarded = audit::Forwarded::default();
 
         let body_id = hir_fn.body;
 
@@ -67,22 +109,56 @@ pub fn analyze_hir(tcx: TyCtxt) {
         if !unsafe_calls.is_empty() {
             debug!(?unsafe_calls);
             for call in &unsafe_calls {
-                call.check_tool_attrs(hir_fn.hir_id, &mut tool_attrs, &mut diagnostics);
-                if let Some(collect_callee_tags) = call.stat(hir_fn.hir_id, tcx, &mut tool_attrs) {
-                    let callee = collect_callee_tags.into_stat_func();
+                call.check_tool_attrs(
+                    hir_fn.hir_id,
+                    &mut tool_attrs,
+                    &refs,
+                    &mut discharges,
+                    &mut forwarded,
+                    &mut diagnostics,
+                );
+                if let Some(collect_callee_tags) =
+                    call.stat(hir_fn.hir_id, tcx, &mut tool_attrs, &refs)
+                {
+                    let callee = collect_callee_tags.into_stat_func(&tool_attrs);
                     stat_caller.unsafe_calls.push(callee);
                 }
             }
         }
 
+        discharges.check_redundant(&mut diagnostics);
+        audit::check(tcx, hir_fn.local, &stat_caller, forwarded, &mut audit);
         stat.funcs.push(stat_caller);
     }
 
+    unsafe_impl::check_unsafe_impls(tcx, &mut tool_attrs, &mut diagnostics);
+    call_order::check_call_order(tcx, &mut diagnostics);
+    hazard::check_hazards(tcx, &mut diagnostics);
+
     diagnostics.emit();
+    refs.write_to_file(tcx, &stat);
     stat.update_metrics();
     stat.write_to_file();
+    inventory::write_to_file(tcx, &stat.krate);
+    audit::write_to_file(&audit, &stat.krate);
+    Analysis { stat, tool_attrs }
+}
+
+/// Results of HIR analysis, which are used by MIR analysis.
+pub struct Analysis {
+    stat: stat::Stat,
+    tool_attrs: db::ToolAttrs,
 }
 
+impl Analysis {
+    pub fn krate(&self) -> &stat::Krate {
+        &self.stat.krate
+    }
+}
+
+/// Functions reached from each function in MIR.
+pub type Callees = FxIndexMap<DefId, FxIndexSet<DefId>>;
+
 #[allow(dead_code)]
 struct HirFn<'hir> {
     local: LocalDefId,
@@ -93,15 +169,128 @@ struct HirFn<'hir> {
 }
 
 impl HirFn<'_> {
-    fn has_tool_attrs(&self, tcx: TyCtxt) -> bool {
-        let hir_id = self.hir_id;
-        crossfig::switch! {
-            crate::asterinas => { tcx.hir_attrs(hir_id.owner).get(hir_id.local_id).iter().any(is_tool_attr) }
-            _ => { tcx.hir_attrs(hir_id).iter().any(is_tool_attr) }
+    fn to_data(&self, tcx: TyCtxt) -> Option<db::Data> {
+        has_tool_attrs(self.hir_id, tcx).then(|| db::Data::new(self.local, self.hir_id, tcx))
+    }
+}
+
+fn has_tool_attrs(hir_id: HirId, tcx: TyCtxt) -> bool {
+    crossfig::switch! {
+        crate::asterinas => { tcx.hir_attrs(hir_id.owner).get(hir_id.local_id).iter().any(is_tool_attr) }
+        _ => { tcx.hir_attrs(hir_id).iter().any(is_tool_attr) }
+    }
+}
+
+/// SPs in the tool attrs on the item, which can be from another crate.
+fn properties_in_attrs(
+    tcx: TyCtxt,
+    def_id: DefId,
+    is_wanted: impl Fn(&Attribute) -> bool,
+) -> Vec<SP> {
+    crossfig::switch! {
+        crate::asterinas => { let attrs = tcx.get_attrs_unchecked(def_id); }
+        _ => { let attrs = tcx.get_all_attrs(def_id); }
+    }
+    let mut v = Vec::new();
+    for attr in attrs.iter().filter(|attr| is_tool_attr(attr) && is_wanted(attr)) {
+        let attr = rustc_hir_pretty::attribute_to_string(&tcx, attr);
+        for prop in parse_attr_and_get_properties(attr.trim()) {
+            v.extend(prop.tags);
+        }
+    }
+    v
+}
+
+/// SPs required by the item, i.e. tool attrs other than discharges, postconditions, type
+/// invariants, references, and resolutions.
+fn required_properties(tcx: TyCtxt, def_id: DefId) -> Vec<SP> {
+    properties_in_attrs(tcx, def_id, |attr| {
+        !["checked", "ensures", "invariant", "ref", "resolves"]
+            .iter()
+            .any(|name| is_tool_attr_named(attr, name))
+    })
+}
+
+/// Whether the function is named by the path in tag args, e.g. `init` or `module::init`.
+/// Generic args in the def path like `Box::<T>::into_raw` are ignored.
+fn def_path_matches(tcx: TyCtxt, def_id: DefId, path: &str) -> bool {
+    let name = without_generic_args(&tcx.def_path_str(def_id));
+    let path = path.strip_prefix("crate::").unwrap_or(path);
+    name == path || name.ends_with(&format!("::{path}"))
+}
+
+/// The path of the item in its crate without impl blocks, which is the key in overlay files,
+/// e.g. `core::ptr::const_ptr::add` for `<*const T>::add`.
+fn overlay_path(tcx: TyCtxt, def_id: DefId) -> String {
+    let mut path = tcx.crate_name(def_id.krate).to_string();
+    for name in tcx.def_path(def_id).data.iter().filter_map(|data| data.data.get_opt_name()) {
+        path.push_str("::");
+        path.push_str(name.as_str());
+    }
+    path
+}
+
+/// A call in MIR of the enclosing body of a HIR node.
+struct MirCall<'tcx> {
+    body: &'tcx Body<'tcx>,
+    args: Vec<&'tcx Operand<'tcx>>,
+    /// Generic args of the callee.
+    generic_args: GenericArgsRef<'tcx>,
+}
+
+/// The innermost call to the callee in MIR, which covers the HIR node.
+fn mir_call<'tcx>(tcx: TyCtxt<'tcx>, hir_id: HirId, callee: DefId) -> Option<MirCall<'tcx>> {
+    let body_owner = tcx.hir_enclosing_body_owner(hir_id);
+    if !tcx.def_kind(body_owner).is_fn_like() || !tcx.is_mir_available(body_owner) {
+        return None;
+    }
+    let body = tcx.optimized_mir(body_owner);
+    let span = diagnostics::hir_span(hir_id, tcx);
+    let mut found: Option<(Span, MirCall)> = None;
+    for data in body.basic_blocks.iter() {
+        if let TerminatorKind::Call { func, args, fn_span, .. } = &data.terminator().kind
+            && let Some((def_id, generic_args)) = func.const_fn_def()
+            && def_id == callee
+            && fn_span.contains(span)
+            && found.as_ref().is_none_or(|(outer, _)| outer.contains(*fn_span))
+        {
+            let args = args.iter().map(|arg| &arg.node).collect();
+            found = Some((*fn_span, MirCall { body, args, generic_args }));
         }
     }
+    found.map(|(_, call)| call)
+}
 
-    fn to_data(&self, tcx: TyCtxt) -> Option<db::Data> {
-        self.has_tool_attrs(tcx).then(|| db::Data::new(self, tcx))
+/// Whether the local is borrowed by `&mut` or `&raw mut` in the body, so it may be written
+/// through the borrow besides its own assignments.
+fn mutably_borrowed(body: &Body, local: Local) -> bool {
+    let stmts = body.basic_blocks.iter().flat_map(|data| &data.statements);
+    stmts.filter_map(|stmt| stmt.kind.as_assign()).any(|(_, rvalue)| match rvalue {
+        Rvalue::Ref(_, BorrowKind::Mut { .. }, place) | Rvalue::RawPtr(RawPtrKind::Mut, place) => {
+            place.local == local
+        }
+        _ => false,
+    })
+}
+
+/// Remove `::<..>` in the def path.
+fn without_generic_args(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    let mut depth = 0usize;
+    let mut rest = path;
+    while let Some(c) = rest.chars().next() {
+        if depth == 0 && rest.starts_with("::<") {
+            depth = 1;
+            rest = &rest[3..];
+            continue;
+        }
+        match c {
+            '<' if depth > 0 => depth += 1,
+            '>' if depth > 0 => depth -= 1,
+            _ if depth == 0 => out.push(c),
+            _ => (),
+        }
+        rest = &rest[c.len_utf8()..];
     }
+    out
 }
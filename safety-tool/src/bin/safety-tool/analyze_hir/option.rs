@@ -0,0 +1,103 @@
+//! Options are optional preconditions. When an option holds at the call site, tags in its
+//! `waives` in spec are auto-discharged. Options are evaluated against generic args of the
+//! call after type checking, which are monomorphic or bounded by the caller's where clauses:
+//! * `Trait(T, trait, ..)`: `T` implements the trait, through the trait solver.
+//! * `Size(T, c)`: the size of `T` is `c` bytes through layout, or `T` is statically sized
+//!   when `c` is `any`, or `T` is dynamically sized when `c` is `unknown`.
+//!
+//! Other options are never considered to hold.
+use super::{
+    db::{OptionTag, TagState},
+    def_path_matches,
+};
+use rustc_hir::{HirId, def_id::DefId};
+use rustc_infer::infer::TyCtxtInferExt;
+use rustc_middle::ty::{GenericArgsRef, Ty, TyCtxt, TypingEnv};
+use rustc_trait_selection::infer::InferCtxtExt;
+
+/// Waive tags on the call to the callee by options that hold.
+pub fn waive_by_options(tcx: TyCtxt, hir_id: HirId, callee: DefId, tag_state: &mut TagState) {
+    if tag_state.options().is_empty() {
+        return;
+    }
+    let body_owner = tcx.hir_enclosing_body_owner(hir_id);
+    let Some(args) = tcx.typeck(body_owner).node_args_opt(hir_id) else { return };
+    let eval =
+        Evaluator { tcx, typing_env: TypingEnv::post_analysis(tcx, body_owner), callee, args };
+
+    for option in tag_state.options().to_vec() {
+        let waives = option.waives();
+        if waives.is_empty() {
+            continue;
+        }
+        if let Some(reason) = eval.holds(&option) {
+            for waived in waives {
+                tag_state.waive(&waived, format!("because `{option}` holds: {reason}."));
+            }
+        }
+    }
+}
+
+struct Evaluator<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    typing_env: TypingEnv<'tcx>,
+    callee: DefId,
+    args: GenericArgsRef<'tcx>,
+}
+
+impl<'tcx> Evaluator<'tcx> {
+    /// The reason why the option holds, or None if it doesn't or can't be evaluated.
+    fn holds(&self, option: &OptionTag) -> Option<String> {
+        let ty = self.generic_ty(option.arg("T", 0)?)?;
+        match &*option.name {
+            "Trait" => {
+                let path = option.arg("trait", 1)?;
+                self.implements(ty, path).then(|| format!("`{ty}` implements `{path}`"))
+            }
+            "Size" => {
+                let size = option.arg("c", 1)?;
+                self.has_size(ty, size).then(|| match size {
+                    "any" => format!("`{ty}` is statically sized"),
+                    "unknown" => format!("`{ty}` is dynamically sized"),
+                    _ => format!("`{ty}` is {size} bytes"),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The type substituted for the generic param of the callee by name.
+    fn generic_ty(&self, name: &str) -> Option<Ty<'tcx>> {
+        let mut generics = self.tcx.generics_of(self.callee);
+        loop {
+            if let Some(param) = generics.own_params.iter().find(|p| p.name.as_str() == name) {
+                let ty = self.args.get(param.index as usize)?.as_type()?;
+                return self.tcx.try_normalize_erasing_regions(self.typing_env, ty).ok();
+            }
+            generics = self.tcx.generics_of(generics.parent?);
+        }
+    }
+
+    fn implements(&self, ty: Ty<'tcx>, path: &str) -> bool {
+        let name = path.rsplit("::").next().unwrap_or(path);
+        let Some(trait_def_id) = self.tcx.all_traits_including_private().find(|&did| {
+            self.tcx.item_name(did).as_str() == name && def_path_matches(self.tcx, did, path)
+        }) else {
+            return false;
+        };
+        let (infcx, param_env) = self.tcx.infer_ctxt().build_with_typing_env(self.typing_env);
+        infcx.type_implements_trait(trait_def_id, [ty], param_env).must_apply_modulo_regions()
+    }
+
+    fn has_size(&self, ty: Ty<'tcx>, size: &str) -> bool {
+        match size {
+            "any" => ty.is_sized(self.tcx, self.typing_env),
+            "unknown" => !ty.is_sized(self.tcx, self.typing_env),
+            _ => size.parse::<u64>().is_ok_and(|size| {
+                self.tcx
+                    .layout_of(self.typing_env.as_query_input(ty))
+                    .is_ok_and(|layout| layout.size.bytes() == size)
+            }),
+        }
+    }
+}
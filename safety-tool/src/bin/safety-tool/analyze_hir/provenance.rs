@@ -0,0 +1,223 @@
+//! Tags on pointer args are auto-discharged when the pointers come from sources guaranteeing
+//! them, which are traced back through MIR of the caller when `[provenance] enable = true`:
+//! * a reference or a place, e.g. `&x as *const T` or `&raw const x`, guarantees tags in
+//!   `reference`;
+//! * the return value of a function in `producers`, e.g. `Box::into_raw`, guarantees the tags
+//!   listed for the function.
+//!
+//! Pointers are traced through moves, copies, and casts that keep the pointee type, as long as
+//! each local is assigned once and never borrowed mutably. Places behind raw pointers or in packed structs are not
+//! trusted.
+//!
+//! Explicit discharges of tags that are auto-discharged for every call they cover are
+//! redundant, and reported as warnings.
+use super::{
+    MirCall,
+    db::{Property, TagState},
+    def_path_matches,
+    diagnostics::EmitDiagnostics,
+    mir_call, mutably_borrowed, required_properties,
+};
+use rustc_data_structures::fx::FxIndexMap;
+use rustc_hir::{HirId, def_id::DefId};
+use rustc_middle::{
+    mir::{Body, Local, Operand, Place, ProjectionElem, Rvalue, StatementKind, TerminatorKind},
+    ty::TyCtxt,
+};
+use safety_parser::{
+    configuration::{ArgKind, provenance_option},
+    safety::Property as SP,
+};
+
+/// Traced locals are at most this deep.
+const MAX_DEPTH: usize = 16;
+
+/// Auto-discharge tags on the call to the callee.
+pub fn discharge_by_provenance(tcx: TyCtxt, hir_id: HirId, callee: DefId, state: &mut TagState) {
+    for (sp, reason) in auto_discharged(tcx, hir_id, callee) {
+        state.auto_discharge(sp.tag.name(), reason);
+    }
+}
+
+/// Tags on the call to the callee guaranteed by provenance of pointer args, with reasons.
+pub fn auto_discharged(tcx: TyCtxt, hir_id: HirId, callee: DefId) -> Vec<(SP, String)> {
+    let mut v = Vec::new();
+    if !provenance_option().enable {
+        return v;
+    }
+    let Some(MirCall { body, args, .. }) = mir_call(tcx, hir_id, callee) else { return v };
+    let tracer = Tracer { tcx, body };
+
+    let params = tcx.fn_arg_idents(callee);
+    let inputs = tcx.fn_sig(callee).skip_binder().inputs().skip_binder();
+    for sp in required_properties(tcx, callee) {
+        if sp.args_in_any_tag().is_some() || sp.tag.is_option() {
+            continue;
+        }
+        let Some(spec) = sp.tag.get_spec() else { continue };
+        let args_str = sp.args_as_str();
+        // Indices of pointer params named in tag args, or all raw pointer params if args are
+        // omitted.
+        let pointers: Option<Vec<usize>> = if args_str.is_empty() {
+            Some((0..inputs.len()).filter(|&idx| inputs[idx].is_raw_ptr()).collect())
+        } else {
+            args_str
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| spec.arg_kind(*idx) == ArgKind::Pointer)
+                .map(|(_, arg)| {
+                    params.iter().position(|p| p.is_some_and(|p| p.name.as_str() == arg))
+                })
+                .collect()
+        };
+        let Some(pointers) = pointers.filter(|v| !v.is_empty()) else { continue };
+
+        let name = sp.tag.name();
+        let reasons: Option<Vec<String>> = pointers
+            .iter()
+            .map(|&idx| {
+                let source = tracer.trace(args.get(idx)?, 0)?;
+                let guaranteed = source.tags.iter().any(|tag| **tag == *name);
+                let param = params[idx].map_or_else(|| format!("arg {idx}"), |p| p.to_string());
+                guaranteed.then(|| format!("`{param}` {}", source.what))
+            })
+            .collect();
+        if let Some(reasons) = reasons {
+            v.push((sp, format!("because {}.", reasons.join(", and "))));
+        }
+    }
+    v
+}
+
+/// Where a pointer comes from, and the tags it guarantees.
+struct Source {
+    what: String,
+    tags: &'static [Box<str>],
+}
+
+struct Tracer<'a, 'tcx> {
+    tcx: TyCtxt<'tcx>,
+    body: &'a Body<'tcx>,
+}
+
+impl<'tcx> Tracer<'_, 'tcx> {
+    fn trace(&self, operand: &Operand<'tcx>, depth: usize) -> Option<Source> {
+        match operand {
+            Operand::Copy(place) | Operand::Move(place) if place.projection.is_empty() => {
+                self.trace_local(place.local, depth + 1)
+            }
+            _ => None,
+        }
+    }
+
+    /// Trace the unique definition of the local.
+    fn trace_local(&self, local: Local, depth: usize) -> Option<Source> {
+        let body = self.body;
+        // A pointer written through `&mut p` is re-pointed without an assignment to `p`.
+        if depth > MAX_DEPTH || local.as_usize() <= body.arg_count || mutably_borrowed(body, local)
+        {
+            return None;
+        }
+        let option = provenance_option();
+        let mut source = None;
+        let mut defs = 0;
+        for data in body.basic_blocks.iter() {
+            for stmt in &data.statements {
+                if let StatementKind::Assign(assign) = &stmt.kind
+                    && assign.0.local == local
+                {
+                    defs += 1;
+                    source = self.trace_rvalue(&assign.0, &assign.1, depth);
+                }
+            }
+            if let TerminatorKind::Call { func, destination, .. } = &data.terminator().kind
+                && destination.local == local
+            {
+                defs += 1;
+                source = func.const_fn_def().and_then(|(producer, _)| {
+                    let (path, tags) = option
+                        .producers
+                        .iter()
+                        .find(|(path, _)| def_path_matches(self.tcx, producer, path))?;
+                    Some(Source { what: format!("is returned by `{path}`"), tags })
+                });
+            }
+        }
+        if defs == 1 { source } else { None }
+    }
+
+    fn trace_rvalue(
+        &self,
+        dest: &Place<'tcx>,
+        rvalue: &Rvalue<'tcx>,
+        depth: usize,
+    ) -> Option<Source> {
+        if !dest.projection.is_empty() {
+            return None;
+        }
+        match rvalue {
+            Rvalue::Use(operand) => self.trace(operand, depth),
+            Rvalue::Cast(_, operand, ty) => {
+                let from = operand.ty(&self.body.local_decls, self.tcx).builtin_deref(true);
+                // Alignment doesn't hold for another pointee type.
+                (from.is_some() && from == ty.builtin_deref(true))
+                    .then(|| self.trace(operand, depth))?
+            }
+            Rvalue::Ref(_, _, place) | Rvalue::RawPtr(_, place) if self.trusted_place(place) => {
+                let what = "comes from a reference".to_owned();
+                Some(Source { what, tags: &provenance_option().reference })
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the place is not behind a raw pointer, and not a field of a packed struct.
+    fn trusted_place(&self, place: &Place<'tcx>) -> bool {
+        place.iter_projections().all(|(base, elem)| {
+            let ty = base.ty(&self.body.local_decls, self.tcx).ty;
+            match elem {
+                ProjectionElem::Deref => !ty.is_raw_ptr(),
+                ProjectionElem::Field(..) => {
+                    !ty.ty_adt_def().is_some_and(|adt| adt.repr().packed())
+                }
+                _ => true,
+            }
+        })
+    }
+}
+
+/// Explicit discharges by tool attrs on HIR nodes, and whether each is needed by some call.
+#[derive(Default)]
+pub struct Discharges {
+    /// A discharge is needed if the value is None, or auto-discharged with the reason.
+    map: FxIndexMap<(HirId, Property), Option<String>>,
+}
+
+impl Discharges {
+    /// Record the discharge of the tag by the node for a call.
+    pub fn record(&mut self, hir_id: HirId, prop: &Property, state: &TagState) {
+        if !state.requires(prop) {
+            return;
+        }
+        let auto = state.auto_reason(prop).map(str::to_owned);
+        self.map
+            .entry((hir_id, prop.clone()))
+            .and_modify(|reason| {
+                if auto.is_none() {
+                    *reason = None;
+                }
+            })
+            .or_insert(auto);
+    }
+
+    /// Report discharges that are auto-discharged for every call they cover.
+    pub fn check_redundant(self, diagnostics: &mut EmitDiagnostics) {
+        for ((hir_id, prop), reason) in self.map {
+            if let Some(reason) = reason {
+                let title = format!("Discharge of `{prop}` is redundant");
+                let info = [format!("`{prop}` is auto-discharged, {reason}")];
+                diagnostics.push_redundant_discharge(hir_id, &title, &info);
+            }
+        }
+    }
+}
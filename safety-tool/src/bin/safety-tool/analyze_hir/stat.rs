@@ -53,7 +53,11 @@ fn hir_span(fn_hir_id: HirId, tcx: TyCtxt) -> Span {
 }
 
 pub fn new_func(fn_hir_id: HirId, fn_def_id: DefId, tcx: TyCtxt) -> Func {
-    let span = hir_span(fn_hir_id, tcx);
+    new_func_with(fn_hir_id, tcx.def_path_str(fn_def_id), !is_unsafe(fn_def_id, tcx), tcx)
+}
+
+fn new_func_with(hir_id: HirId, name: String, safe: bool, tcx: TyCtxt) -> Func {
+    let span = hir_span(hir_id, tcx);
 
     let src_map = tcx.sess.source_map();
     let file_lines = src_map
@@ -61,8 +65,8 @@ pub fn new_func(fn_hir_id: HirId, fn_def_id: DefId, tcx: TyCtxt) -> Func {
         .unwrap_or_else(|err| panic!("Failed to know {span:?}:\n{err:?}"));
 
     Func {
-        name: tcx.def_path_str(fn_def_id),
-        safe: !is_unsafe(fn_def_id, tcx),
+        name,
+        safe,
         tags: Vec::new(),
         path: file_lines.file.name.prefer_local().to_string().into(),
         span: {
@@ -79,6 +83,7 @@ pub fn new_func(fn_hir_id: HirId, fn_def_id: DefId, tcx: TyCtxt) -> Func {
             buf
         },
         unsafe_calls: Vec::new(),
+        version: None,
     }
 }
 
@@ -88,7 +93,8 @@ pub fn new_caller(fn_hir_id: HirId, tcx: TyCtxt, attrs: &[String]) -> Func {
     let mut func = new_func(fn_hir_id, fn_def_id, tcx);
 
     for attr in attrs {
-        let props = parse_attr_and_get_properties(attr);
+        let mut props = parse_attr_and_get_properties(attr);
+        super::tag_args::fill_type_args(tcx, fn_hir_id.owner.def_id, &mut props);
         push_tag(props, &mut func.tags);
     }
 
@@ -116,6 +122,13 @@ pub fn new_callee(fn_hir_id: HirId, fn_def_id: DefId, tcx: TyCtxt, tags: Vec<Tag
     func
 }
 
+/// An operation on a type with invariants, which is treated as an unsafe call.
+pub fn new_operation(hir_id: HirId, name: String, tcx: TyCtxt, tags: Vec<Tag>) -> Func {
+    let mut func = new_func_with(hir_id, name, false, tcx);
+    func.tags = tags;
+    func
+}
+
 pub fn is_unsafe(fn_def_id: DefId, tcx: TyCtxt) -> bool {
     tcx.fn_sig(fn_def_id).skip_binder().safety().is_unsafe()
 }
@@ -0,0 +1,310 @@
+//! Tag args in `#[requires]` are resolved against the signature of the tagged function.
+//! Kinds of args are declared through `arg_kinds` in spec, i.e. [`ArgKind`]:
+//! * a pointer arg must name a param or `self.field` of a raw pointer or `NonNull`;
+//! * a type arg must be a generic param, `Self`, a primitive, a type in the signature, a
+//!   type defined in the crate, or a type imported by `use` in the module of the function;
+//! * `self.field` in any arg must name a field of `Self`.
+//!
+//! Type names not found are only warned about, since they may be visible through glob imports
+//! or the prelude, which aren't resolved here.
+//!
+//! Omitted type args are filled with the pointee type of the pointer arg for stats.
+use super::diagnostics::EmitDiagnostics;
+use crate::is_tool_attr_named;
+use rustc_data_structures::fx::FxHashSet;
+use rustc_hir::{
+    ItemKind, PrimTy, UseKind,
+    def::{DefKind, Res},
+    def_id::LocalDefId,
+};
+use rustc_middle::ty::{self, GenericArgKind, Ty, TyCtxt};
+use rustc_span::{Symbol, kw, sym};
+use safety_parser::{
+    configuration::ArgKind,
+    safety::{PropertiesAndReason, Property, parse_attr_and_get_properties},
+    syn::{Expr, ExprField, Member},
+};
+
+pub fn check_tag_args(
+    tcx: TyCtxt,
+    local: LocalDefId,
+    crate_types: &FxHashSet<Symbol>,
+    diagnostics: &mut EmitDiagnostics,
+) {
+    crossfig::switch! {
+        crate::asterinas => { let attrs = tcx.get_attrs_unchecked(local.to_def_id()); }
+        _ => { let attrs = tcx.get_all_attrs(local.to_def_id()); }
+    }
+    let mut tagged = attrs.iter().filter(|attr| is_tool_attr_named(attr, "requires")).peekable();
+    if tagged.peek().is_none() {
+        return;
+    }
+    let scope = Scope::new(tcx, local).with_types(crate_types);
+    for attr in tagged {
+        let span = attr.span();
+        let attr = rustc_hir_pretty::attribute_to_string(&tcx, attr);
+        for prop in parse_attr_and_get_properties(attr.trim()) {
+            for tag in &prop.tags {
+                for Report { title, info, warning } in scope.check(tag) {
+                    if warning {
+                        diagnostics.push_unresolved_tag_arg(local, span, &title, &info);
+                    } else {
+                        diagnostics.push_invalid_tag_arg(local, span, &title, &info);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Names of types defined in the crate, which are visible to type args through paths.
+pub fn crate_types(tcx: TyCtxt) -> FxHashSet<Symbol> {
+    tcx.hir_free_items()
+        .map(|item| item.owner_id.to_def_id())
+        .filter(|&def_id| {
+            matches!(
+                tcx.def_kind(def_id),
+                DefKind::Struct | DefKind::Enum | DefKind::Union | DefKind::TyAlias
+            )
+        })
+        .map(|def_id| tcx.item_name(def_id))
+        .collect()
+}
+
+/// Fill omitted type args of tags on the function with pointee types of pointer args.
+pub fn fill_type_args(tcx: TyCtxt, local: LocalDefId, props: &mut [PropertiesAndReason]) {
+    let scope = Scope::new(tcx, local);
+    for tag in props.iter_mut().flat_map(|prop| prop.tags.iter_mut()) {
+        tag.fill_type_args(|arg| {
+            let ty = scope.arg_ty(arg)?;
+            pointee(tcx, ty).map(|ty| ty.to_string())
+        });
+    }
+}
+
+/// A diagnostic on a tag arg. Warnings are for type names not found.
+struct Report {
+    title: String,
+    info: Vec<String>,
+    warning: bool,
+}
+
+/// Names visible to tag args on the function.
+struct Scope<'tcx> {
+    tcx: TyCtxt<'tcx>,
+    local: LocalDefId,
+    fn_name: String,
+    params: Vec<(Symbol, Ty<'tcx>)>,
+    types: FxHashSet<Symbol>,
+}
+
+impl<'tcx> Scope<'tcx> {
+    fn new(tcx: TyCtxt<'tcx>, local: LocalDefId) -> Self {
+        let def_id = local.to_def_id();
+        let sig = tcx.fn_sig(def_id).instantiate_identity().skip_binder();
+        let idents = tcx.fn_arg_idents(def_id);
+        let params = idents
+            .iter()
+            .zip(sig.inputs())
+            .filter_map(|(ident, ty)| Some((ident.as_ref()?.name, *ty)))
+            .collect();
+        let fn_name = tcx.def_path_str(def_id);
+        Scope { tcx, local, fn_name, params, types: FxHashSet::default() }
+    }
+
+    /// Collect type names visible to type args, which are only needed to check tags.
+    fn with_types(mut self, crate_types: &FxHashSet<Symbol>) -> Self {
+        let tcx = self.tcx;
+        let def_id = self.local.to_def_id();
+        let sig = tcx.fn_sig(def_id).instantiate_identity().skip_binder();
+        let types = &mut self.types;
+        let mut generics = Some(tcx.generics_of(def_id));
+        while let Some(g) = generics {
+            types.extend(g.own_params.iter().map(|param| param.name));
+            generics = g.parent.map(|parent| tcx.generics_of(parent));
+        }
+        if tcx.opt_parent(def_id).is_some_and(|parent| {
+            matches!(tcx.def_kind(parent), DefKind::Impl { .. } | DefKind::Trait)
+        }) {
+            types.insert(kw::SelfUpper);
+        }
+        for ty in sig.inputs_and_output {
+            for arg in ty.walk() {
+                if let GenericArgKind::Type(ty) = arg.kind()
+                    && let Some(adt) = ty.ty_adt_def()
+                {
+                    types.insert(tcx.item_name(adt.did()));
+                }
+            }
+        }
+        types.extend(crate_types);
+        // Types imported by `use` in the module, including renamed ones like `use a::B as C`.
+        let module = tcx.parent_module_from_def_id(self.local);
+        for id in tcx.hir_module_free_items(module) {
+            if let ItemKind::Use(path, UseKind::Single(ident)) = tcx.hir_item(id).kind
+                && path.res.present_items().any(|res| {
+                    matches!(
+                        res,
+                        Res::Def(
+                            DefKind::Struct
+                                | DefKind::Enum
+                                | DefKind::Union
+                                | DefKind::TyAlias
+                                | DefKind::ForeignTy,
+                            _
+                        )
+                    )
+                })
+            {
+                types.insert(ident.name);
+            }
+        }
+        self
+    }
+
+    fn param(&self, name: &str) -> Option<Ty<'tcx>> {
+        self.params.iter().find(|(param, _)| param.as_str() == name).map(|(_, ty)| *ty)
+    }
+
+    /// The type of a param or `self.field` named by the arg.
+    fn arg_ty(&self, arg: &Expr) -> Option<Ty<'tcx>> {
+        match arg {
+            Expr::Path(path) => self.param(&path.path.get_ident()?.to_string()),
+            Expr::Field(field) => self.self_field(field).ok()?,
+            _ => None,
+        }
+    }
+
+    /// The type of `self.field`. Err means the field is not found, and Ok(None) means the
+    /// expression is not a field of `self`.
+    fn self_field(&self, expr: &ExprField) -> Result<Option<Ty<'tcx>>, String> {
+        let Expr::Path(base) = &*expr.base else { return Ok(None) };
+        let Member::Named(field) = &expr.member else { return Ok(None) };
+        if !base.path.is_ident("self") {
+            return Ok(None);
+        }
+        let Some(self_ty) = self.param("self") else {
+            return Err(format!("`{}` has no `self` param.", self.fn_name));
+        };
+        let self_ty = self_ty.peel_refs();
+        let found = match self_ty.kind() {
+            ty::Adt(adt, args) if adt.is_struct() || adt.is_union() => {
+                let name = field.to_string();
+                let fields = &adt.non_enum_variant().fields;
+                fields.iter().find(|f| f.name.as_str() == name).map(|f| f.ty(self.tcx, args))
+            }
+            _ => None,
+        };
+        match found {
+            Some(ty) => Ok(Some(ty)),
+            None => Err(format!("`{self_ty}` has no field `{field}`.")),
+        }
+    }
+
+    /// Check args of the tag, and return diagnostics as (title, info).
+    fn check(&self, tag: &Property) -> Vec<Report> {
+        let mut reports = Vec::new();
+        if let Some(props) = tag.args_in_any_tag() {
+            for prop in &props {
+                for tag in &prop.tags {
+                    reports.extend(self.check(tag));
+                }
+            }
+            return reports;
+        }
+        let name = tag.tag.name();
+        let Some(spec) = tag.tag.get_spec() else { return reports };
+        if spec.args.is_empty() {
+            return reports;
+        }
+        if tag.args.len() > spec.args.len() {
+            let title = format!(
+                "Tag `{name}` takes {} args, but {} are given",
+                spec.args.len(),
+                tag.args.len()
+            );
+            let info = vec![format!("Args are defined as `{name}({})`.", spec.args.join(", "))];
+            reports.push(Report { title, info, warning: false });
+        }
+
+        let args_str = tag.args_as_str();
+        for (idx, ((defined, arg), arg_str)) in
+            spec.args.iter().zip(&tag.args).zip(&args_str).enumerate()
+        {
+            let report = |reason: String| {
+                let title = format!("Invalid arg `{arg_str}` for `{defined}` in tag `{name}`");
+                Report { title, info: vec![reason], warning: false }
+            };
+            // `self.field` in any kind
+            let field_ty = match arg {
+                Expr::Field(field) => match self.self_field(field) {
+                    Ok(ty) => ty,
+                    Err(reason) => {
+                        reports.push(report(reason));
+                        continue;
+                    }
+                },
+                _ => None,
+            };
+            let ident = match arg {
+                Expr::Path(path) if path.qself.is_none() => path.path.get_ident(),
+                _ => None,
+            };
+
+            match spec.arg_kind(idx) {
+                ArgKind::Pointer => {
+                    let ty = match (ident, field_ty) {
+                        (_, Some(ty)) => ty,
+                        (Some(ident), None) => match self.param(&ident.to_string()) {
+                            Some(ty) => ty,
+                            None => {
+                                let reason =
+                                    format!("`{ident}` is not a param of `{}`.", self.fn_name);
+                                reports.push(report(reason));
+                                continue;
+                            }
+                        },
+                        // Other expressions are not typed.
+                        (None, None) => continue,
+                    };
+                    if pointee(self.tcx, ty).is_none() {
+                        let reason =
+                            format!("`{arg_str}` is `{ty}`, not a raw pointer or `NonNull`.");
+                        reports.push(report(reason));
+                    }
+                }
+                ArgKind::Type => {
+                    let Some(ident) = ident else { continue };
+                    let sym = Symbol::intern(&ident.to_string());
+                    if !self.types.contains(&sym) && PrimTy::from_name(sym).is_none() {
+                        let title =
+                            format!("Unresolved arg `{arg_str}` for `{defined}` in tag `{name}`");
+                        let reason = format!(
+                            "`{ident}` is not a generic param, `Self`, a primitive, or a type \
+                             in the signature, the crate, or `use` of the module."
+                        );
+                        reports.push(Report { title, info: vec![reason], warning: true });
+                    }
+                }
+                ArgKind::Value => (),
+            }
+        }
+        reports
+    }
+}
+
+/// The pointee type of a raw pointer or `NonNull`, through references.
+fn pointee<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Option<Ty<'tcx>> {
+    let ty = ty.peel_refs();
+    if let Some(pointee) = ty.builtin_deref(true)
+        && ty.is_raw_ptr()
+    {
+        return Some(pointee);
+    }
+    match ty.kind() {
+        ty::Adt(adt, args) if tcx.is_diagnostic_item(sym::NonNull, adt.did()) => {
+            Some(args.type_at(0))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,61 @@
+//! Unsafe traits and their methods declare postconditions through `#[ensures]`,
+//! which each `unsafe impl` must discharge through `#[checked]`.
+//!
+//! Only methods written in the impl block are checked. Default methods of the trait that
+//! aren't overridden, and `unsafe fn` with `#[ensures]` in safe traits, are not checked yet.
+use super::{
+    db::{Property, TagState, ToolAttrs},
+    diagnostics::EmitDiagnostics,
+};
+use rustc_hir::{HirId, def::DefKind};
+use rustc_middle::ty::TyCtxt;
+
+pub fn check_unsafe_impls(
+    tcx: TyCtxt,
+    tool_attrs: &mut ToolAttrs,
+    diagnostics: &mut EmitDiagnostics,
+) {
+    for local in tcx.hir_crate_items(()).definitions() {
+        if !matches!(tcx.def_kind(local), DefKind::Impl { of_trait: true }) {
+            continue;
+        }
+        let Some(header) = tcx.impl_trait_header(local) else { continue };
+        if !header.safety.is_unsafe() {
+            continue;
+        }
+
+        // Obligations on the trait are discharged on the impl block.
+        let trait_def_id = header.trait_ref.skip_binder().def_id;
+        let impl_hir_id = tcx.local_def_id_to_hir_id(local);
+        if let Some(tag_state) = tool_attrs.get_ensures(trait_def_id, tcx) {
+            discharge(tag_state, impl_hir_id, diagnostics);
+        }
+
+        // Obligations on trait methods are discharged on the methods in impl.
+        for &item in tcx.associated_item_def_ids(local) {
+            let assoc = tcx.associated_item(item);
+            if let Some(trait_item) = assoc.trait_item_def_id
+                && assoc.is_fn()
+                && let Some(tag_state) = tool_attrs.get_ensures(trait_item, tcx)
+            {
+                let item_hir_id = tcx.local_def_id_to_hir_id(item.expect_local());
+                discharge(tag_state, item_hir_id, diagnostics);
+            }
+        }
+    }
+}
+
+fn discharge(tag_state: &mut TagState, hir_id: HirId, diagnostics: &mut EmitDiagnostics) {
+    let tcx = diagnostics.tcx();
+    for tag in &Property::new_with_hir_id(hir_id, tcx) {
+        if let Err(err) = tag_state.discharge(tag) {
+            diagnostics.push_duplicate_discharge(hir_id, &err);
+        }
+    }
+
+    let undischarged = tag_state.undischarged();
+    let title = undischarged.title();
+    if !title.is_empty() {
+        diagnostics.push_missing_discharge_on_impl(hir_id, &title, &undischarged.info());
+    }
+}
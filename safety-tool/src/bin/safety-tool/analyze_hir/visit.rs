@@ -1,23 +1,55 @@
 use crate::analyze_hir::{
-    db::{Property, TagState, ToolAttrs, tool_attr_on_hir},
+    audit::Forwarded,
+    const_eval,
+    db::{TagState, ToolAttrs},
     diagnostics::EmitDiagnostics,
+    entity_ref::EntityRefs,
+    option,
+    provenance::{self, Discharges},
     stat,
 };
+use rustc_data_structures::fx::FxIndexSet;
 use rustc_hir::{
     def::{DefKind, Res},
     def_id::DefId,
     intravisit::*,
     *,
 };
-use rustc_middle::ty::{TyCtxt, TypeckResults};
+use rustc_middle::ty::{
+    TyCtxt, TypeckResults,
+    adjustment::{Adjust, Adjustment, AutoBorrow, AutoBorrowMutability},
+};
+use rustc_span::Ident;
 use safety_parser::{safety::SafetyAttr, syn};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Call {
     /// function use id
     pub hir_id: HirId,
-    /// function def id
+    /// function def id, or ADT def id for operations on types with invariants
     pub def_id: DefId,
+    pub kind: CallKind,
+}
+
+/// Operations that require discharging tags.
+#[derive(Debug, Clone, Copy)]
+pub enum CallKind {
+    /// Call an unsafe function.
+    Fn,
+    /// Construct a type with invariants through a struct literal.
+    Construct,
+    /// Write the field of a type with invariants outside its own inherent impl.
+    FieldWrite(Ident),
+}
+
+impl CallKind {
+    fn label(self) -> &'static str {
+        match self {
+            CallKind::Fn => "For this unsafe call.",
+            CallKind::Construct => "For this construction.",
+            CallKind::FieldWrite(_) => "For this field write.",
+        }
+    }
 }
 
 impl Call {
@@ -25,6 +57,9 @@ impl Call {
         &self,
         caller: HirId,
         tool_attrs: &mut ToolAttrs,
+        refs: &EntityRefs,
+        discharges: &mut Discharges,
+        forwarded: &mut Forwarded,
         diagnostics: &mut EmitDiagnostics,
     ) {
         let tcx = diagnostics.tcx();
@@ -32,28 +67,50 @@ impl Call {
             // No tool attrs to be checked.
             return;
         };
+        if let CallKind::Fn = self.kind {
+            option::waive_by_options(tcx, self.hir_id, self.def_id, tag_state);
+            provenance::discharge_by_provenance(tcx, self.hir_id, self.def_id, tag_state);
+            for evaluated in const_eval::evaluate_tags(tcx, self.hir_id, self.def_id) {
+                let name = evaluated.sp.tag.name();
+                if evaluated.proven {
+                    tag_state.auto_discharge(name, evaluated.reason);
+                } else {
+                    let title = format!("Tag `{name}` is violated: {}", evaluated.reason);
+                    diagnostics.push_violated_tag(self.hir_id, &title, &evaluated.info());
+                    // The violation is reported, and isn't reported as missing again.
+                    tag_state.waive(name, "because it's violated.".to_owned());
+                }
+            }
+        }
 
-        let mut check = |hir_id: HirId| {
+        let mut check = |hir_id: HirId, tag_state: &mut TagState| {
             debug!(?hir_id, ?caller);
 
-            let properties = Property::new_with_hir_id(hir_id, tcx);
+            let properties = refs.properties(hir_id, tcx);
 
             let is_empty = properties.is_empty();
             if !is_empty {
                 for tag in &properties {
+                    discharges.record(hir_id, tag, tag_state);
                     if let Err(err) = tag_state.discharge(tag) {
                         diagnostics.push_duplicate_discharge(hir_id, &err);
                     }
                 }
                 // only checks if Safety tags exist
-                check_tag_state(tag_state, hir_id, diagnostics);
+                check_tag_state(tag_state, hir_id, self.kind, diagnostics);
             }
             is_empty
         };
-        check(self.hir_id);
+        check(self.hir_id, tag_state);
 
+        let mut is_forwarded = false;
         for parent in parent_hirs(tcx, self.hir_id) {
-            let empty = check(parent);
+            if parent == caller {
+                // Tags left are forwarded to tags of the caller.
+                forwarded.record(self.def_id, tag_state);
+                is_forwarded = true;
+            }
+            let empty = check(parent, tag_state);
             // Stop at first tool attrs or the function item.
             // For a function inside a nested module, hir_parent_id_iter
             // will pop up to the crate root, thus it's necessary to
@@ -62,9 +119,12 @@ impl Call {
                 break;
             }
         }
+        if !is_forwarded {
+            forwarded.record(self.def_id, tag_state);
+        }
 
         // make sure Safety tags are all discharged
-        check_tag_state(tag_state, self.hir_id, diagnostics);
+        check_tag_state(tag_state, self.hir_id, self.kind, diagnostics);
     }
 
     pub fn stat<'tcx>(
@@ -72,8 +132,9 @@ impl Call {
         caller: HirId,
         tcx: TyCtxt<'tcx>,
         tool_attrs: &mut ToolAttrs,
+        refs: &EntityRefs,
     ) -> Option<CollectCalleeTags<'tcx>> {
-        CollectCalleeTags::new(self, caller, tcx, tool_attrs)
+        CollectCalleeTags::new(self, caller, tcx, tool_attrs, refs)
     }
 }
 
@@ -84,11 +145,16 @@ fn parent_hirs(tcx: TyCtxt, hir_id: HirId) -> impl Iterator<Item = HirId> {
     }
 }
 
-fn check_tag_state(tag_state: &mut TagState, hir_id: HirId, diagnostics: &mut EmitDiagnostics) {
+fn check_tag_state(
+    tag_state: &mut TagState,
+    hir_id: HirId,
+    kind: CallKind,
+    diagnostics: &mut EmitDiagnostics,
+) {
     let undischarged = tag_state.undischarged();
     let title = undischarged.title();
     if !title.is_empty() {
-        diagnostics.push_missing_discharge(hir_id, &title, &undischarged.info());
+        diagnostics.push_missing_discharge(hir_id, &title, kind.label(), &undischarged.info());
     }
 }
 
@@ -96,6 +162,8 @@ pub struct Calls<'tcx> {
     tcx: TyCtxt<'tcx>,
     tyck: &'tcx TypeckResults<'tcx>,
     calls: Vec<Call>,
+    /// Field places already recorded, since a place can be both assigned and autoref'd.
+    field_writes: FxIndexSet<HirId>,
 }
 
 crossfig::switch! {
@@ -138,7 +206,7 @@ impl<'tcx> Calls<'tcx> {
                 let qpath_res = self.tyck.qpath_res(qpath, hir_id);
                 // maybe use [DefKind::is_fn_like](https://doc.rust-lang.org/nightly/nightly-rustc/rustc_hir/def/enum.DefKind.html#method.is_fn_like)
                 if let Res::Def(DefKind::Fn | DefKind::AssocFn, def_id) = qpath_res {
-                    self.calls.push(Call { hir_id, def_id });
+                    self.calls.push(Call { hir_id, def_id, kind: CallKind::Fn });
                 }
             }
             // https://doc.rust-lang.org/nightly/nightly-rustc/rustc_hir/hir/enum.ExprKind.html#variant.MethodCall
@@ -147,27 +215,105 @@ impl<'tcx> Calls<'tcx> {
             // https://doc.rust-lang.org/nightly/nightly-rustc/rustc_middle/ty/struct.TypeckResults.html#method.type_dependent_def_id
             ExprKind::MethodCall(..) => {
                 if let Some(def_id) = self.tyck.type_dependent_def_id(hir_id) {
-                    self.calls.push(Call { hir_id, def_id });
+                    self.calls.push(Call { hir_id, def_id, kind: CallKind::Fn });
                 } else {
                     eprintln!("Unable to resolve DefId from {:?}", ex.kind);
                 }
             }
+            ExprKind::Struct(..) => {
+                if let Some(adt) = self.tyck.expr_ty(ex).ty_adt_def()
+                    && has_invariant(adt.did(), self.tcx)
+                {
+                    self.calls.push(Call { hir_id, def_id: adt.did(), kind: CallKind::Construct });
+                }
+            }
+            ExprKind::Assign(lhs, ..) | ExprKind::AssignOp(_, lhs, _) => {
+                self.push_field_writes(lhs)
+            }
+            ExprKind::AddrOf(_, Mutability::Mut, place) => self.push_field_writes(place),
             _ => (),
         }
+        // An owned place borrowed mutably by autoref, e.g. `buf.vec.push(1)` or `buf.vec[0] = 1`.
+        // Exprs in anon consts like `8` in `f::<8>()` have no type in results of the body.
+        if let Some(ty) = self.tyck.expr_ty_opt(ex)
+            && !ty.is_ref()
+            && !ty.is_raw_ptr()
+            && self.tyck.expr_adjustments(ex).iter().any(is_mut_borrow)
+        {
+            self.push_field_writes(ex);
+        }
         walk_expr(self, ex)
     }
 
+    /// Record writes to fields of types with invariants along projections of the place,
+    /// e.g. both `a.b` and `a.b.c` in `a.b.c[i]`. Writes through derefs like `*a.p` are
+    /// writes to the pointee rather than the field, thus not recorded.
+    fn push_field_writes(&mut self, mut place: &'tcx Expr<'tcx>) {
+        loop {
+            match place.kind {
+                ExprKind::Field(base, field) => {
+                    let hir_id = place.hir_id;
+                    if let Some(adt) = self.tyck.expr_ty_adjusted(base).peel_refs().ty_adt_def()
+                        && has_invariant(adt.did(), self.tcx)
+                        && !self.in_inherent_impl_of(adt.did())
+                        && self.field_writes.insert(hir_id)
+                    {
+                        let kind = CallKind::FieldWrite(field);
+                        self.calls.push(Call { hir_id, def_id: adt.did(), kind });
+                    }
+                    place = base;
+                }
+                ExprKind::Index(base, ..) => place = base,
+                _ => return,
+            }
+        }
+    }
+
+    /// Whether the body being visited is an associated fn of the inherent impl of the ADT.
+    fn in_inherent_impl_of(&self, adt: DefId) -> bool {
+        let owner = self.tyck.hir_owner.to_def_id();
+        let Some(parent) = self.tcx.opt_parent(owner) else { return false };
+        matches!(self.tcx.def_kind(parent), DefKind::Impl { of_trait: false })
+            && self.tcx.type_of(parent).skip_binder().ty_adt_def().map(|adt| adt.did()) == Some(adt)
+    }
+
     pub fn get_unsafe_calls(&self) -> Vec<&Call> {
-        self.calls.iter().filter(|call| stat::is_unsafe(call.def_id, self.tcx)).collect()
+        self.calls
+            .iter()
+            .filter(|call| match call.kind {
+                CallKind::Fn => stat::is_unsafe(call.def_id, self.tcx),
+                // Operations on types with invariants are treated as unsafe.
+                CallKind::Construct | CallKind::FieldWrite(_) => true,
+            })
+            .collect()
     }
 }
 
+fn is_mut_borrow(adjustment: &Adjustment) -> bool {
+    matches!(
+        adjustment.kind,
+        Adjust::Borrow(
+            AutoBorrow::Ref(.., AutoBorrowMutability::Mut { .. })
+                | AutoBorrow::RawPtr(Mutability::Mut)
+        )
+    )
+}
+
+/// Whether the ADT declares type invariants through `#[rapx::invariant]`.
+fn has_invariant(def_id: DefId, tcx: TyCtxt) -> bool {
+    crossfig::switch! {
+        crate::asterinas => { let attrs = tcx.get_attrs_unchecked(def_id); }
+        _ => { let attrs = tcx.get_all_attrs(def_id); }
+    }
+    attrs.iter().any(|attr| crate::is_tool_attr_named(attr, "invariant"))
+}
+
 pub fn get_calls<'tcx>(
     tcx: TyCtxt<'tcx>,
     expr: &'tcx Expr<'tcx>,
     tyck: &'tcx TypeckResults<'tcx>,
 ) -> Calls<'tcx> {
-    let mut calls = Calls { tcx, tyck, calls: Vec::new() };
+    let mut calls = Calls { tcx, tyck, calls: Vec::new(), field_writes: FxIndexSet::default() };
     walk_expr(&mut calls, expr);
     calls
 }
@@ -189,6 +335,7 @@ impl<'tcx> CollectCalleeTags<'tcx> {
         caller: HirId,
         tcx: TyCtxt<'tcx>,
         _tool_attrs: &mut ToolAttrs,
+        refs: &EntityRefs,
     ) -> Option<Self> {
         // let Some(_tag_state) = tool_attrs.get_tags(callee.def_id, tcx) else {
         //     // No tool attrs to be checked on the callee.
@@ -200,7 +347,7 @@ impl<'tcx> CollectCalleeTags<'tcx> {
         // FIXME: the validity of attributes are not checked. Tags that do not target
         // any calls should be warned.
         for parent in parent_hirs(tcx, callee.hir_id) {
-            for attr_str in tool_attr_on_hir(parent, tcx) {
+            for attr_str in refs.tool_attrs(parent, tcx) {
                 match syn::parse_str::<SafetyAttr>(&attr_str) {
                     Ok(attr) => {
                         let seg = &attr.attr.path().segments;
@@ -229,10 +376,49 @@ impl<'tcx> CollectCalleeTags<'tcx> {
                 break;
             }
         }
+        if let CallKind::Fn = callee.kind {
+            let (hir_id, def_id) = (callee.hir_id, callee.def_id);
+            for (sp, _) in provenance::auto_discharged(tcx, hir_id, def_id) {
+                if explicit_tag(&mut tags, sp.tag.name()).is_none() {
+                    tags.push(stat::Tag::auto_discharged(sp));
+                }
+            }
+            for evaluated in const_eval::evaluate_tags(tcx, hir_id, def_id) {
+                let (sp, proven) = (evaluated.sp, evaluated.proven);
+                let result =
+                    if proven { stat::EvalResult::Proven } else { stat::EvalResult::Violated };
+                if let Some(tag) = explicit_tag(&mut tags, sp.tag.name()) {
+                    tag.eval = Some(result);
+                } else if proven {
+                    tags.push(stat::Tag { eval: Some(result), ..stat::Tag::auto_discharged(sp) });
+                }
+            }
+        }
         Some(CollectCalleeTags { tcx, tags, callee, caller })
     }
 
-    pub fn into_stat_func(self) -> stat::Func {
-        stat::new_callee(self.callee.hir_id, self.callee.def_id, self.tcx, self.tags)
+    pub fn into_stat_func(self, tool_attrs: &ToolAttrs) -> stat::Func {
+        let Call { hir_id, def_id, kind } = self.callee;
+        match kind {
+            CallKind::Fn => {
+                let mut func = stat::new_callee(hir_id, def_id, self.tcx, self.tags);
+                func.version = tool_attrs.crate_version(def_id.krate).map(Into::into);
+                func
+            }
+            CallKind::Construct => {
+                let name = self.tcx.def_path_str(def_id);
+                stat::new_operation(hir_id, name, self.tcx, self.tags)
+            }
+            CallKind::FieldWrite(field) => {
+                let name = format!("{}.{field}", self.tcx.def_path_str(def_id));
+                stat::new_operation(hir_id, name, self.tcx, self.tags)
+            }
+        }
     }
 }
+
+/// The tag discharged individually by tool attrs.
+fn explicit_tag<'a>(tags: &'a mut [stat::Tag], name: &str) -> Option<&'a mut stat::Tag> {
+    tags.iter_mut()
+        .find(|tag| matches!(&tag.tag, stat::TagType::Vanilla(prop) if prop.tag.name() == name))
+}
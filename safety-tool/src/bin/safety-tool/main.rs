@@ -7,10 +7,12 @@ extern crate rustc_data_structures;
 extern crate rustc_driver;
 extern crate rustc_hir;
 extern crate rustc_hir_pretty;
+extern crate rustc_infer;
 extern crate rustc_interface;
 extern crate rustc_middle;
 extern crate rustc_session;
 extern crate rustc_span;
+extern crate rustc_trait_selection;
 
 // Conditional compilation of feature names. Used in [`crossfig::switch`].
 // NOTE: before compilation (i.e. calling `cargo build` or something)
@@ -41,8 +43,12 @@ crossfig::switch! {
 }
 
 use eyre::Result;
-use rustc_data_structures::fx::FxHashSet;
-use rustc_middle::ty::TyCtxt;
+use rustc_data_structures::fx::{FxHashMap, FxHashSet, FxIndexMap, FxIndexSet};
+use rustc_hir::{def::DefKind, def_id::DefId};
+use rustc_middle::{
+    mir::TerminatorKind,
+    ty::{self, TyCtxt},
+};
 use rustc_public::{
     CompilerError, CrateDef, ItemKind,
     mir::{
@@ -52,7 +58,8 @@ use rustc_public::{
     },
     ty::Ty,
 };
-use std::ops::ControlFlow;
+use safety_tool::reach::{DeadFn, Reach, TaggedInstance};
+use std::{hash::Hash, ops::ControlFlow};
 
 #[macro_use]
 extern crate tracing;
@@ -62,7 +69,13 @@ mod analyze_hir;
 fn main() {
     safety_tool::logger::init();
 
-    let rustc_args: Vec<_> = std::env::args().collect();
+    let mut rustc_args: Vec<_> = std::env::args().collect();
+    // Analyses on MIR of local bodies need calls as written, which MIR inlining at higher
+    // opt-levels would remove from `optimized_mir`. The flag is only added when no artifact
+    // is emitted, so that codegen of a real build isn't changed by the tool.
+    if stops_compilation() {
+        rustc_args.push("-Zinline-mir=no".to_owned());
+    }
 
     crossfig::switch! {
         asterinas => { }
@@ -70,8 +83,15 @@ fn main() {
     };
 
     let res = run_with_tcx!(rustc_args, |tcx| {
-        analyze_hir::analyze_hir(tcx);
-        analyze(tcx);
+        // Rewrite the source instead of analyzing it.
+        if let Some(mapping) = analyze_hir::apply_tags::mapping() {
+            if analyze_hir::apply_tags::is_target_crate() {
+                analyze_hir::apply_tags::apply(tcx, &mapping).unwrap();
+            }
+            return compilation_status();
+        }
+        let mut analysis = analyze_hir::analyze_hir(tcx);
+        analyze(tcx, &mut analysis);
         compilation_status()
     });
 
@@ -81,15 +101,20 @@ fn main() {
 }
 
 fn compilation_status() -> ControlFlow<()> {
-    // When STOP_COMPILATION is set to non-0, stop compiling.
-    if std::env::var("STOP_COMPILATION").map(|s| s != "0").unwrap_or(false) {
+    if stops_compilation() {
         ControlFlow::<(), ()>::Break(())
     } else {
         ControlFlow::<(), ()>::Continue(())
     }
 }
 
-fn analyze(tcx: TyCtxt) {
+/// When STOP_COMPILATION is set to non-0, stop compiling after analysis, thus no artifact
+/// is emitted.
+fn stops_compilation() -> bool {
+    std::env::var("STOP_COMPILATION").map(|s| s != "0").unwrap_or(false)
+}
+
+fn analyze(tcx: TyCtxt, analysis: &mut analyze_hir::Analysis) {
     let mut reachability = Reachability::default();
     let local_items = rustc_public::all_local_items();
     let functions = local_items.iter().filter(|item| matches!(item.kind(), ItemKind::Fn));
@@ -102,46 +127,260 @@ fn analyze(tcx: TyCtxt) {
                 continue;
             }
         };
-        reachability.add_instance(instance);
+        reachability.add_root(instance);
     }
 
-    let local_crate = rustc_public::local_crate();
-    println!(
-        "********* {name:?} {typ:?} has reached {len} instances *********",
-        name = local_crate.name,
-        typ = tcx.crate_types(),
-        len = reachability.instances.len()
+    debug!(
+        krate = rustc_public::local_crate().name,
+        typ = ?tcx.crate_types(),
+        instances = reachability.instances.len()
     );
-    reachability.print_tag_std_attrs(tcx);
+    reachability.write_to_file(tcx, analysis.krate());
+    analyze_hir::boundary::write_to_file(tcx, analysis, &reachability.callees(tcx));
 }
 
 #[derive(Debug, Default)]
 struct Reachability {
     /// Collect monomorphized instances.
     instances: FxHashSet<Instance>,
+    /// Instances reached from each instance with a body.
+    edges: FxIndexMap<Instance, FxIndexSet<Instance>>,
+    /// The instance whose body is being visited.
+    caller: Option<Instance>,
+    /// Local functions which the collection starts from.
+    roots: Vec<Instance>,
 }
 
 impl Reachability {
+    fn add_root(&mut self, instance: Instance) {
+        self.roots.push(instance);
+        self.add_instance(instance);
+    }
+
     fn add_instance(&mut self, instance: Instance) {
+        if let Some(caller) = self.caller {
+            self.edges.entry(caller).or_default().insert(instance);
+        }
         if self.instances.insert(instance)
             && instance.has_body()
             && matches!(instance.kind, InstanceKind::Item)
         {
             // recurse if this is the first time of insertion
             if let Some(body) = instance.body() {
+                let caller = self.caller.replace(instance);
                 self.visit_body(&body);
+                self.caller = caller;
             }
         }
     }
 
-    fn print_tag_std_attrs(&self, tcx: TyCtxt) {
-        for instance in &self.instances {
-            // Resort to internal API for all attrs, rather than tool attrs.
-            print_tag_std_attrs_through_internal_apis(tcx, instance);
+    /// Functions reached from each function, regardless of generic args.
+    fn callees(&self, tcx: TyCtxt) -> analyze_hir::Callees {
+        let mut callees = analyze_hir::Callees::default();
+        for (caller, reached) in &self.edges {
+            let caller = internal(tcx, caller.def.def_id());
+            let reached = reached.iter().map(|callee| internal(tcx, callee.def.def_id()));
+            callees.entry(caller).or_default().extend(reached);
+        }
+        callees
+    }
+
+    /// Write tagged instances reached from each entry point, and tagged functions never
+    /// reached, when `SP_OUT_DIR` is set.
+    fn write_to_file(&self, tcx: TyCtxt, krate: &safety_tool::stat::Krate) {
+        let Some(path) = krate.output_file_path("reach.json") else { return };
+        let reach = self.reach(tcx);
+        if !reach.is_empty()
+            && let Ok(file) = std::fs::File::create(path)
+        {
+            _ = serde_json::to_writer_pretty(file, &reach);
+        }
+    }
+
+    fn reach(&self, tcx: TyCtxt) -> Reach {
+        let mut reach = Reach::default();
+        let mut reached = FxHashSet::default();
+        for entry in self.entries(tcx) {
+            let callees = |instance| self.edges.get(&instance).into_iter().flatten().copied();
+            let internal = |instance| internal(tcx, instance);
+            let tagged = reach_from(tcx, entry, callees, internal, &mut reached);
+            reach.entries.insert(instance_name(tcx, &entry), tagged);
+        }
+        // Generic entry points have no monomorphized instance, so calls in their MIR are
+        // followed with their own generic params.
+        for entry in generic_entries(tcx) {
+            let typing_env = ty::TypingEnv::post_analysis(tcx, entry.def_id());
+            let callees = |instance| generic_callees(tcx, typing_env, instance).into_iter();
+            let tagged = reach_from(tcx, entry, callees, |instance| instance, &mut reached);
+            reach.entries.insert(entry.to_string(), tagged);
+        }
+
+        for local in tcx.hir_crate_items(()).definitions() {
+            let def_id = local.to_def_id();
+            if !matches!(tcx.def_kind(def_id), DefKind::Fn | DefKind::AssocFn)
+                || reached.contains(&def_id)
+            {
+                continue;
+            }
+            let attrs = tool_attrs_through_internal_apis(tcx, def_id);
+            if !attrs.is_empty() {
+                let loc = tcx.sess.source_map().lookup_char_pos(tcx.def_span(def_id).lo());
+                let file = loc.file.name.prefer_local();
+                let location = format!("{file}:{}:{}", loc.line, loc.col.0 + 1);
+                reach.dead.push(DeadFn { name: tcx.def_path_str(def_id), attrs, location });
+            }
         }
+        reach
+    }
+
+    /// `main` of a binary, or exported functions of a library.
+    fn entries(&self, tcx: TyCtxt) -> Vec<Instance> {
+        let main = tcx.entry_fn(()).map(|(def_id, _)| def_id);
+        let effective_visibilities = tcx.effective_visibilities(());
+        let is_entry = |instance: &Instance| {
+            let def_id = internal(tcx, instance.def.def_id());
+            match (main, def_id.as_local()) {
+                (Some(main), _) => def_id == main,
+                (None, Some(local)) => effective_visibilities.is_exported(local),
+                (None, None) => false,
+            }
+        };
+        let mut entries: Vec<_> = self.roots.iter().copied().filter(is_entry).collect();
+        entries.sort_by_cached_key(|instance| instance.def.span().diagnostic());
+        entries
     }
 }
 
+/// Tagged instances reached from the entry point, with the shortest paths from it through each
+/// caller. Functions of reached instances are recorded in `reached`.
+fn reach_from<'tcx, N, I>(
+    tcx: TyCtxt<'tcx>,
+    entry: N,
+    callees: impl Fn(N) -> I,
+    internal: impl Fn(N) -> ty::Instance<'tcx>,
+    reached: &mut FxHashSet<DefId>,
+) -> Vec<TaggedInstance>
+where
+    N: Copy + Eq + Hash,
+    I: Iterator<Item = N>,
+{
+    // Parents in shortest paths from the entry point, in the order of BFS, and callees of each.
+    let mut parents = FxIndexMap::<N, Option<N>>::default();
+    let mut edges = FxHashMap::<N, FxIndexSet<N>>::default();
+    parents.insert(entry, None);
+    let mut idx = 0;
+    while let Some((&instance, _)) = parents.get_index(idx) {
+        idx += 1;
+        let reached: FxIndexSet<_> = callees(instance).collect();
+        for &callee in &reached {
+            parents.entry(callee).or_insert(Some(instance));
+        }
+        edges.insert(instance, reached);
+    }
+    let name = |instance| internal(instance).to_string();
+    let path_to = |mut instance| {
+        let mut path = vec![name(instance)];
+        while let Some(parent) = parents[&instance] {
+            path.push(name(parent));
+            instance = parent;
+        }
+        path.reverse();
+        path
+    };
+
+    let mut tagged = Vec::new();
+    for &instance in parents.keys() {
+        let internal = internal(instance);
+        let def_id = internal.def_id();
+        reached.insert(def_id);
+        let attrs = tool_attrs_through_internal_apis(tcx, def_id);
+        if attrs.is_empty() {
+            continue;
+        }
+        let callers = parents.keys().filter(|caller| edges[*caller].contains(&instance));
+        let mut paths: Vec<_> = callers
+            .map(|&caller| {
+                let mut path = path_to(caller);
+                path.push(name(instance));
+                path
+            })
+            .collect();
+        if paths.is_empty() {
+            // The entry point itself.
+            paths.push(path_to(instance));
+        }
+        tagged.push(TaggedInstance {
+            name: tcx.def_path_str(def_id),
+            generic_args: internal.args.iter().map(|arg| arg.to_string()).collect(),
+            attrs,
+            paths,
+        });
+    }
+    tagged
+}
+
+/// Exported generic functions of a library, with their own generic params as args.
+fn generic_entries<'tcx>(tcx: TyCtxt<'tcx>) -> Vec<ty::Instance<'tcx>> {
+    if tcx.entry_fn(()).is_some() {
+        return Vec::new();
+    }
+    let effective_visibilities = tcx.effective_visibilities(());
+    let mut entries: Vec<_> = tcx
+        .hir_crate_items(())
+        .definitions()
+        .filter(|&local| {
+            let def_id = local.to_def_id();
+            matches!(tcx.def_kind(def_id), DefKind::Fn | DefKind::AssocFn)
+                && effective_visibilities.is_exported(local)
+                && tcx.generics_of(def_id).requires_monomorphization(tcx)
+                && tcx.is_mir_available(def_id)
+        })
+        .map(|local| {
+            let def_id = local.to_def_id();
+            ty::Instance::new_raw(def_id, ty::GenericArgs::identity_for_item(tcx, def_id))
+        })
+        .collect();
+    entries.sort_by_key(|instance| tcx.def_span(instance.def_id()));
+    entries
+}
+
+/// Functions called in MIR of a local function with generic args in terms of the entry point.
+/// A call is resolved to the implementation when generic args are known enough.
+fn generic_callees<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    typing_env: ty::TypingEnv<'tcx>,
+    instance: ty::Instance<'tcx>,
+) -> Vec<ty::Instance<'tcx>> {
+    let def_id = instance.def_id();
+    if !def_id.is_local() || !tcx.def_kind(def_id).is_fn_like() || !tcx.is_mir_available(def_id) {
+        return Vec::new();
+    }
+    let mut callees = Vec::new();
+    for data in tcx.optimized_mir(def_id).basic_blocks.iter() {
+        let TerminatorKind::Call { func, .. } = &data.terminator().kind else { continue };
+        let Some((callee, args)) = func.const_fn_def() else { continue };
+        let binder = ty::EarlyBinder::bind(args);
+        let Ok(args) =
+            instance.try_instantiate_mir_and_normalize_erasing_regions(tcx, typing_env, binder)
+        else {
+            continue;
+        };
+        let callee = match ty::Instance::try_resolve(tcx, typing_env, callee, args) {
+            Ok(Some(resolved)) if matches!(resolved.def, ty::InstanceKind::Item(_)) => resolved,
+            _ => ty::Instance::new_raw(callee, args),
+        };
+        if !callees.contains(&callee) {
+            callees.push(callee);
+        }
+    }
+    callees
+}
+
+/// The instance with generic args, e.g. `std::ptr::read::<u8>`.
+fn instance_name(tcx: TyCtxt, instance: &Instance) -> String {
+    internal(tcx, instance).to_string()
+}
+
 const REGISTER_TOOL: &str = "rapx";
 
 fn is_tool_attr(attr: &rustc_hir::Attribute) -> bool {
@@ -165,23 +404,40 @@ fn is_tool_attr(attr: &rustc_hir::Attribute) -> bool {
     }
 }
 
-fn print_tag_std_attrs_through_internal_apis(tcx: TyCtxt<'_>, instance: &Instance) {
-    let def_id = internal(tcx, instance.def.def_id());
+/// Check the tool attr like `#[rapx::name(...)]`.
+fn is_tool_attr_named(attr: &rustc_hir::Attribute, name: &str) -> bool {
+    crossfig::switch! {
+        asterinas => {
+            if let rustc_hir::AttrKind::Normal(tool_attr) = &attr.kind
+                && tool_attr.path.segments[0].as_str() == REGISTER_TOOL
+            {
+                return tool_attr.path.segments.get(1).is_some_and(|seg| seg.as_str() == name);
+            }
+            false
+        }
+        _  => {
+            if let rustc_hir::Attribute::Unparsed(tool_attr) = attr
+                && tool_attr.path.segments[0].as_str() == REGISTER_TOOL
+            {
+                return tool_attr.path.segments.get(1).is_some_and(|seg| seg.as_str() == name);
+            }
+            false
+        }
+    }
+}
 
+/// Tool attrs of the function, which can be from another crate.
+fn tool_attrs_through_internal_apis(tcx: TyCtxt<'_>, def_id: DefId) -> Vec<String> {
+    // Resort to internal API for all attrs, rather than tool attrs.
     crossfig::switch! {
         asterinas => { let attrs = tcx.get_attrs_unchecked(def_id).iter(); }
         _ => { let attrs = tcx.get_all_attrs(def_id).iter(); }
     }
 
     let tool_attrs = attrs.filter(|&attr| is_tool_attr(attr));
-    for attr in tool_attrs {
-        println!(
-            "{fn_name:?} ({span:?})\n => {attr:?}\n",
-            fn_name = instance.name(),
-            span = instance.def.span().diagnostic(),
-            attr = rustc_hir_pretty::attribute_to_string(&tcx, attr)
-        );
-    }
+    tool_attrs
+        .map(|attr| rustc_hir_pretty::attribute_to_string(&tcx, attr).trim().to_owned())
+        .collect()
 }
 
 impl MirVisitor for Reachability {
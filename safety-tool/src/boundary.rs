@@ -0,0 +1,95 @@
+//! Safety boundaries of safe public functions, written to `SP_OUT_DIR/<crate>.boundary.json`
+//! and as trees to `SP_OUT_DIR/<crate>.boundary.txt`.
+//!
+//! A safe function calling a tagged unsafe function is where soundness is decided: tags of the
+//! callee must be discharged by the safe caller, i.e. the boundary, either at the call, or at
+//! the call to an unsafe function forwarding them through its own tags. Tagged unsafe callees
+//! reachable from a safe public function are listed with the boundary discharging them, which
+//! can be the function itself, or a safe function reachable from it.
+use indexmap::IndexMap;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Boundaries {
+    /// Def paths of safe public functions to their boundaries.
+    pub map: IndexMap<String, Boundary>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Boundary {
+    /// `file:line:col`
+    pub location: String,
+    pub callees: Vec<TaggedCallee>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TaggedCallee {
+    pub callee: String,
+    /// Functions from the public function to the callee, both exclusive.
+    pub via: Vec<String>,
+    /// The safe function discharging tags of the callee, i.e. the nearest safe caller.
+    pub boundary: String,
+    pub obligations: Vec<Obligation>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Obligation {
+    /// A tag name, or alternatives in `any`.
+    pub tags: Vec<String>,
+    /// Whether the tag is discharged at the boundary.
+    pub discharged: bool,
+}
+
+impl TaggedCallee {
+    pub fn is_discharged(&self) -> bool {
+        self.obligations.iter().all(|ob| ob.discharged)
+    }
+
+    fn node(&self) -> String {
+        let obligations = self.obligations.iter().format_with(", ", |ob, f| {
+            let mark = if ob.discharged { "✓" } else { "✗" };
+            f(&format_args!("{} {mark}", ob.tags.join(" | ")))
+        });
+        format!("{} {{ {obligations} }}", self.callee)
+    }
+}
+
+type Tree = termtree::Tree<String>;
+
+impl Boundaries {
+    /// Render a tree for each public function, with paths to tagged callees merged.
+    pub fn to_trees(&self) -> String {
+        let mut out = String::new();
+        for (name, boundary) in &self.map {
+            let mut tree = Tree::new(name.clone());
+            for callee in &boundary.callees {
+                push_path(&mut tree, &callee.via, callee.node());
+            }
+            out.push_str(&tree.to_string());
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn push_path(tree: &mut Tree, via: &[String], leaf: String) {
+    let Some((first, rest)) = via.split_first() else {
+        tree.push(leaf);
+        return;
+    };
+    // The node can be a tagged callee with tags after its name.
+    let is_node = |t: &Tree| {
+        t.root
+            .strip_prefix(first.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(" {"))
+    };
+    let idx = match tree.leaves.iter().position(is_node) {
+        Some(idx) => idx,
+        None => {
+            tree.push(Tree::new(first.clone()));
+            tree.leaves.len() - 1
+        }
+    };
+    push_path(&mut tree.leaves[idx], rest, leaf);
+}
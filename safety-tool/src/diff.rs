@@ -0,0 +1,282 @@
+//! Compare stat JSON of crates in two `SP_OUT_DIR`s, e.g. before and after a change, to track
+//! progress of annotations.
+//!
+//! Crates are matched by names and types, and functions and calls by def paths. Calls to the
+//! same callee in a caller are matched in order. Discharge coverage regresses when tags
+//! discharged at a call in both runs disappear, unless a local callee no longer declares them.
+use crate::{
+    Result,
+    stat::{CrateType, Func, MetricsCoverage, Stat, TagType},
+    workspace::read_stats,
+};
+use camino::Utf8PathBuf;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Markdown,
+    Json,
+}
+
+impl Format {
+    /// Parse `[markdown|json]`.
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        match args {
+            [] => Ok(Format::Markdown),
+            [arg] if arg == "markdown" => Ok(Format::Markdown),
+            [arg] if arg == "json" => Ok(Format::Json),
+            _ => bail!("Unknown arguments for diff: {args:?}"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Diff {
+    /// Crates with changes, by names prefixed with `bin-` for binaries.
+    pub crates: IndexMap<String, CrateDiff>,
+    /// Whether discharge coverage regresses, i.e. any discharge disappears.
+    pub regressed: bool,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct CrateDiff {
+    pub tags: Vec<TagsChange>,
+    pub new_unsafe_calls: Vec<Call>,
+    pub lost_discharges: Vec<LostDischarge>,
+    pub coverage: Option<CoverageChange>,
+}
+
+/// Tags declared by the function.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TagsChange {
+    pub func: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Call {
+    pub caller: String,
+    pub callee: String,
+    /// Tags discharged at the call.
+    pub discharged: Vec<String>,
+    /// `path:line`
+    pub location: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LostDischarge {
+    pub caller: String,
+    pub callee: String,
+    /// Tags discharged before, but not any more.
+    pub tags: Vec<String>,
+    /// `path:line` in the new run.
+    pub location: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CoverageChange {
+    pub old: MetricsCoverage,
+    pub new: MetricsCoverage,
+}
+
+impl CrateDiff {
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+            && self.new_unsafe_calls.is_empty()
+            && self.lost_discharges.is_empty()
+            && self.coverage.is_none()
+    }
+}
+
+/// Compare stats in the old dir with stats in the new dir.
+pub fn diff(old: &Utf8PathBuf, new: &Utf8PathBuf) -> Result<Diff> {
+    let old = keyed(read_stats(std::slice::from_ref(old))?);
+    let new = keyed(read_stats(std::slice::from_ref(new))?);
+
+    let mut diff = Diff::default();
+    for key in new.keys().chain(old.keys()).unique() {
+        let krate = diff_crate(old.get(key), new.get(key));
+        diff.regressed |= !krate.lost_discharges.is_empty();
+        if !krate.is_empty() {
+            diff.crates.insert(key.clone(), krate);
+        }
+    }
+    diff.crates.sort_unstable_keys();
+    Ok(diff)
+}
+
+fn keyed(stats: Vec<Stat>) -> IndexMap<String, Stat> {
+    let key = |stat: &Stat| match stat.krate.typ {
+        CrateType::Bin => format!("bin-{}", stat.krate.name),
+        CrateType::Lib => stat.krate.name.clone(),
+    };
+    stats.into_iter().map(|stat| (key(&stat), stat)).collect()
+}
+
+fn diff_crate(old: Option<&Stat>, new: Option<&Stat>) -> CrateDiff {
+    fn funcs(stat: Option<&Stat>) -> IndexMap<&str, &Func> {
+        stat.iter().flat_map(|s| &s.funcs).map(|f| (f.name.as_str(), f)).collect()
+    }
+    let (old_funcs, new_funcs) = (funcs(old), funcs(new));
+    let mut krate = CrateDiff::default();
+
+    for name in new_funcs.keys().chain(old_funcs.keys()).unique() {
+        let (old, new) = (old_funcs.get(name).copied(), new_funcs.get(name).copied());
+        let old_tags = old.map(declared).unwrap_or_default();
+        let new_tags = new.map(declared).unwrap_or_default();
+        let added: Vec<_> = new_tags.iter().filter(|t| !old_tags.contains(t)).cloned().collect();
+        let removed: Vec<_> = old_tags.iter().filter(|t| !new_tags.contains(t)).cloned().collect();
+        if !added.is_empty() || !removed.is_empty() {
+            krate.tags.push(TagsChange { func: name.to_string(), added, removed });
+        }
+        if let Some(new) = new {
+            diff_calls(old, new, &new_funcs, &mut krate);
+        }
+    }
+
+    let coverage =
+        |stat: Option<&Stat>| stat.map(|s| s.metrics.coverage.clone()).unwrap_or_default();
+    let (old, new) = (coverage(old), coverage(new));
+    if old != new {
+        krate.coverage = Some(CoverageChange { old, new });
+    }
+    krate
+}
+
+/// Match calls in the new caller with calls to the same callees in order in the old caller.
+/// A discharge is lost only if the callee still declares the tag, which is unknown for
+/// callees outside the crate.
+fn diff_calls(
+    old: Option<&Func>,
+    new: &Func,
+    new_funcs: &IndexMap<&str, &Func>,
+    krate: &mut CrateDiff,
+) {
+    let mut old_calls: Vec<&Func> = old.iter().flat_map(|f| &f.unsafe_calls).collect();
+    for call in &new.unsafe_calls {
+        let now = discharged(call);
+        let location = format!("{}:{}", call.path, call.span);
+        let Some(idx) = old_calls.iter().position(|c| c.name == call.name) else {
+            let (caller, callee) = (new.name.clone(), call.name.clone());
+            krate.new_unsafe_calls.push(Call { caller, callee, discharged: now, location });
+            continue;
+        };
+        let old_call = old_calls.remove(idx);
+        let declared: Option<Vec<&str>> = new_funcs
+            .get(&*call.name)
+            .map(|callee| callee.tags.iter().flat_map(|tag| tag.tag.names()).collect());
+        let tags: Vec<_> = old_call
+            .tags
+            .iter()
+            .filter(|tag| {
+                declared.as_ref().is_none_or(|names| tag.tag.names().any(|n| names.contains(&n)))
+            })
+            .map(|tag| tag_str(&tag.tag))
+            .unique()
+            .filter(|t| !now.contains(t))
+            .collect();
+        if !tags.is_empty() {
+            let (caller, callee) = (new.name.clone(), call.name.clone());
+            krate.lost_discharges.push(LostDischarge { caller, callee, tags, location });
+        }
+    }
+}
+
+fn tag_str(tag: &TagType) -> String {
+    match tag {
+        TagType::Vanilla(_) => tag.to_string(),
+        TagType::Any(_) => format!("any({tag})"),
+    }
+}
+
+fn declared(func: &Func) -> Vec<String> {
+    func.tags.iter().map(|tag| tag_str(&tag.tag)).unique().collect()
+}
+
+fn discharged(call: &Func) -> Vec<String> {
+    call.tags.iter().map(|tag| tag_str(&tag.tag)).unique().collect()
+}
+
+impl Diff {
+    pub fn render(&self, format: Format) -> Result<String> {
+        Ok(match format {
+            Format::Markdown => self.to_markdown(),
+            Format::Json => serde_json::to_string_pretty(self)? + "\n",
+        })
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        _ = self.write_markdown(&mut md);
+        md
+    }
+
+    fn write_markdown(&self, md: &mut String) -> fmt::Result {
+        if self.crates.is_empty() {
+            return writeln!(md, "No changes in safety tags.");
+        }
+        for (name, krate) in &self.crates {
+            writeln!(md, "## {name}\n")?;
+            if !krate.tags.is_empty() {
+                writeln!(md, "### Tags\n")?;
+                writeln!(md, "| Function | Added | Removed |")?;
+                writeln!(md, "|----------|-------|---------|")?;
+                for change in &krate.tags {
+                    let (added, removed) = (list(&change.added), list(&change.removed));
+                    writeln!(md, "| {} | {added} | {removed} |", change.func)?;
+                }
+                writeln!(md)?;
+            }
+            if !krate.new_unsafe_calls.is_empty() {
+                writeln!(md, "### New Unsafe Calls\n")?;
+                writeln!(md, "| Caller | Callee | Discharged | Location |")?;
+                writeln!(md, "|--------|--------|------------|----------|")?;
+                for call in &krate.new_unsafe_calls {
+                    let Call { caller, callee, discharged, location } = call;
+                    writeln!(md, "| {caller} | {callee} | {} | {location} |", list(discharged))?;
+                }
+                writeln!(md)?;
+            }
+            if !krate.lost_discharges.is_empty() {
+                writeln!(md, "### Lost Discharges\n")?;
+                writeln!(md, "| Caller | Callee | Lost | Location |")?;
+                writeln!(md, "|--------|--------|------|----------|")?;
+                for lost in &krate.lost_discharges {
+                    let LostDischarge { caller, callee, tags, location } = lost;
+                    writeln!(md, "| {caller} | {callee} | {} | {location} |", list(tags))?;
+                }
+                writeln!(md)?;
+            }
+            if let Some(CoverageChange { old, new }) = &krate.coverage {
+                writeln!(md, "### Coverage\n")?;
+                writeln!(
+                    md,
+                    "| | occurence | requires | checked | delegated | as_vanilla | in_any |"
+                )?;
+                writeln!(
+                    md,
+                    "|-|-----------|----------|---------|-----------|------------|--------|"
+                )?;
+                for (row, c) in [("old", old), ("new", new)] {
+                    let cells =
+                        [c.occurence, c.requires, c.checked, c.delegated, c.as_vanilla, c.in_any];
+                    writeln!(md, "| {row} | {} |", cells.iter().join(" | "))?;
+                }
+                writeln!(md)?;
+            }
+        }
+        if self.regressed {
+            writeln!(md, "**Discharge coverage regressed.**")?;
+        }
+        Ok(())
+    }
+}
+
+fn list(tags: &[String]) -> String {
+    if tags.is_empty() { "-".to_owned() } else { tags.join(", ") }
+}
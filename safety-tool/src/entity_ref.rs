@@ -0,0 +1,57 @@
+//! Sites linked by entity references, i.e. `#[ref(anchor)]` sites sharing the discharge
+//! of `#[checked { name = anchor; ... }]`.
+//!
+//! Linked sites are written to `SP_OUT_DIR` on each run, and compared against the
+//! previous run to report impacted sites: when the anchor or any referencing site
+//! changes, all linked sites should be reviewed again.
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct EntityRefs {
+    /// Anchor name to linked sites.
+    pub map: IndexMap<String, Linked>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Linked {
+    /// None for a dangling reference.
+    pub anchor: Option<Site>,
+    pub refs: Vec<Site>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Site {
+    /// `file:line:col`
+    pub location: String,
+    /// Source code of the annotated node.
+    pub source: String,
+}
+
+impl Linked {
+    /// Whether sources of linked sites are the same. Locations are not compared,
+    /// because unrelated edits shift lines.
+    fn same_sources(&self, other: &Self) -> bool {
+        self.anchor.as_ref().map(|site| &site.source)
+            == other.anchor.as_ref().map(|site| &site.source)
+            && self.sorted_ref_sources() == other.sorted_ref_sources()
+    }
+
+    fn sorted_ref_sources(&self) -> Vec<&str> {
+        let mut v: Vec<_> = self.refs.iter().map(|site| &*site.source).collect();
+        v.sort_unstable();
+        v
+    }
+}
+
+impl EntityRefs {
+    /// Linked sites whose anchor or referencing sites change since the previous run.
+    /// A newly added anchor name is also impacted.
+    pub fn impacted<'a>(&'a self, prev: &Self) -> IndexMap<&'a str, &'a Linked> {
+        self.map
+            .iter()
+            .filter(|(name, linked)| !prev.map.get(*name).is_some_and(|p| p.same_sources(linked)))
+            .map(|(name, linked)| (name.as_str(), linked))
+            .collect()
+    }
+}
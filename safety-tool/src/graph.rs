@@ -0,0 +1,285 @@
+//! Call graphs of [`Stat`] as Graphviz DOT and Mermaid flowcharts, i.e. the same graph of
+//! functions and their unsafe calls as the tree in `SP_OUT_DIR/<crate>.txt`.
+//!
+//! * Nodes are filled by safe or unsafe, and outlined by discharge status of their calls.
+//! * Edges are labelled with tags discharged at the call, and coloured by discharge status
+//!   against tags declared by the callee, which are only known for local callees.
+//! * The graph can focus on a root function, i.e. functions reachable from it, and on a tag,
+//!   i.e. calls and functions where the tag is declared or discharged.
+use crate::{
+    Result,
+    stat::{Func, Stat, TagType},
+};
+use camino::Utf8Path;
+use eyre::Context;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Dot,
+    Mermaid,
+}
+
+#[derive(Debug, Default)]
+pub struct GraphOptions {
+    pub format: Format,
+    /// Only show calls and functions declaring or discharging the tag.
+    pub tag: Option<String>,
+    /// Only show functions reachable from the root function.
+    pub root: Option<String>,
+}
+
+impl GraphOptions {
+    /// Parse `[dot|mermaid] [--tag TAG] [--root FN]`.
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        let mut opts = GraphOptions::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().cloned().ok_or_else(|| eyre!("{arg} needs a value."));
+            match arg.as_str() {
+                "dot" => opts.format = Format::Dot,
+                "mermaid" => opts.format = Format::Mermaid,
+                "--tag" => opts.tag = Some(value()?),
+                "--root" => opts.root = Some(value()?),
+                _ => bail!("Unknown argument for graph: {arg}"),
+            }
+        }
+        Ok(opts)
+    }
+}
+
+/// Read the stat JSON and render its graph.
+pub fn render_file(path: &Utf8Path, opts: &GraphOptions) -> Result<String> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {path}"))?;
+    let stat: Stat = serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse {path} as stat of a crate"))?;
+    let graph = Graph::new(&stat, opts);
+    if let Some(root) = &opts.root {
+        ensure!(!graph.nodes.is_empty(), "{root} is not a function in {path}");
+    }
+    Ok(graph.render(opts.format))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Status {
+    /// All tags declared by the callee are discharged.
+    Discharged,
+    /// Some tags declared by the callee aren't discharged.
+    Missing,
+    /// The callee is external and no tag is discharged, or there's no call.
+    Unknown,
+}
+
+impl Status {
+    fn color(self) -> &'static str {
+        match self {
+            Status::Discharged => "#2e7d32",
+            Status::Missing => "#c62828",
+            Status::Unknown => "#9e9e9e",
+        }
+    }
+}
+
+fn fill(safe: bool) -> &'static str {
+    if safe { "#e3f2fd" } else { "#ffe0b2" }
+}
+
+struct Node<'a> {
+    name: &'a str,
+    safe: bool,
+    tags: Vec<&'a TagType>,
+    status: Status,
+}
+
+struct Edge {
+    from: usize,
+    to: usize,
+    /// Tags discharged at the call, deduplicated.
+    discharged: String,
+    status: Status,
+}
+
+pub struct Graph<'a> {
+    nodes: IndexMap<&'a str, Node<'a>>,
+    edges: Vec<Edge>,
+}
+
+impl<'a> Graph<'a> {
+    pub fn new(stat: &'a Stat, opts: &GraphOptions) -> Self {
+        let local_fns: IndexMap<_, _> = stat.funcs.iter().map(|f| (f.name.as_str(), f)).collect();
+        let funcs: Vec<&Func> = match &opts.root {
+            Some(root) => reachable(root, &local_fns),
+            None => stat.funcs.iter().collect(),
+        };
+
+        let mut graph = Graph { nodes: IndexMap::new(), edges: Vec::new() };
+        for func in funcs {
+            if func.unsafe_calls.is_empty() && func.has_no_tag() {
+                // Skip functions that have no unsafe calls and no tags, as the tree does.
+                continue;
+            }
+            if opts.tag.as_deref().is_none_or(|tag| declares(func, tag)) {
+                graph.node(func, false);
+            }
+            for call in &func.unsafe_calls {
+                let callee = local_fns.get(&*call.name).copied();
+                let focused = opts.tag.as_deref().is_none_or(|tag| {
+                    declares(call, tag) || callee.is_some_and(|c| declares(c, tag))
+                });
+                if !focused {
+                    continue;
+                }
+                let from = graph.node(func, false);
+                // Tags of a call are discharged ones, so a callee outside the crate is named only.
+                let to = match callee {
+                    Some(callee) => graph.node(callee, false),
+                    None => graph.node(call, true),
+                };
+                let status = status(call, callee);
+                let discharged =
+                    call.tags.iter().map(|tag| tag.tag.to_string()).unique().join(", ");
+                graph.edges.push(Edge { from, to, discharged, status });
+            }
+        }
+
+        // A node is missing tags if any call misses them, and discharged if any call
+        // discharges tags and none misses them.
+        for edge in &graph.edges {
+            let node = &mut graph.nodes[edge.from];
+            node.status = match (node.status, edge.status) {
+                (Status::Missing, _) | (_, Status::Missing) => Status::Missing,
+                (Status::Discharged, _) | (_, Status::Discharged) => Status::Discharged,
+                _ => Status::Unknown,
+            };
+        }
+        graph
+    }
+
+    /// The node of the function, without tags if it's an external callee.
+    fn node(&mut self, func: &'a Func, external: bool) -> usize {
+        let entry = self.nodes.entry(&func.name);
+        let idx = entry.index();
+        entry.or_insert_with(|| Node {
+            name: &func.name,
+            safe: func.safe,
+            tags: if external { Vec::new() } else { tags(func) },
+            status: Status::Unknown,
+        });
+        idx
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Dot => self.to_dot(),
+            Format::Mermaid => self.to_mermaid(),
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph {\n    node [shape=box, style=filled];\n");
+        for node in self.nodes.values() {
+            let label = label(node, "\\n", dot_escape);
+            let (fill, color) = (fill(node.safe), node.status.color());
+            _ = writeln!(
+                out,
+                "    \"{}\" [label=\"{label}\", fillcolor=\"{fill}\", color=\"{color}\"];",
+                dot_escape(node.name)
+            );
+        }
+        for edge in &self.edges {
+            let from = dot_escape(self.nodes[edge.from].name);
+            let to = dot_escape(self.nodes[edge.to].name);
+            let label = dot_escape(&edge.discharged);
+            let color = edge.status.color();
+            _ = writeln!(out, "    \"{from}\" -> \"{to}\" [label=\"{label}\", color=\"{color}\"];");
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("flowchart TD\n");
+        for (idx, node) in self.nodes.values().enumerate() {
+            _ = writeln!(out, "    n{idx}[\"{}\"]", label(node, "<br/>", mermaid_escape));
+        }
+        for edge in &self.edges {
+            let (from, to) = (edge.from, edge.to);
+            if edge.discharged.is_empty() {
+                _ = writeln!(out, "    n{from} --> n{to}");
+            } else {
+                let label = mermaid_escape(&edge.discharged);
+                _ = writeln!(out, "    n{from} -->|\"{label}\"| n{to}");
+            }
+        }
+        for (idx, node) in self.nodes.values().enumerate() {
+            let (fill, color) = (fill(node.safe), node.status.color());
+            _ = writeln!(out, "    style n{idx} fill:{fill},stroke:{color}");
+        }
+        for (idx, edge) in self.edges.iter().enumerate() {
+            _ = writeln!(out, "    linkStyle {idx} stroke:{}", edge.status.color());
+        }
+        out
+    }
+}
+
+/// Functions reachable from the root through local calls, including the root.
+fn reachable<'a>(root: &str, local_fns: &IndexMap<&'a str, &'a Func>) -> Vec<&'a Func> {
+    let Some(&root) = local_fns.get(root) else { return Vec::new() };
+    let mut funcs = vec![root];
+    let mut idx = 0;
+    while let Some(func) = funcs.get(idx) {
+        for call in &func.unsafe_calls {
+            if let Some(&callee) = local_fns.get(&*call.name)
+                && !funcs.iter().any(|f| f.name == callee.name)
+            {
+                funcs.push(callee);
+            }
+        }
+        idx += 1;
+    }
+    funcs
+}
+
+fn tags(func: &Func) -> Vec<&TagType> {
+    func.tags.iter().map(|tag| &tag.tag).collect()
+}
+
+fn declares(func: &Func, tag: &str) -> bool {
+    func.tags.iter().any(|t| t.tag.names().any(|name| name == tag))
+}
+
+/// Compare tags discharged at the call with tags declared by the callee.
+fn status(call: &Func, callee: Option<&Func>) -> Status {
+    let discharged: Vec<&str> = call.tags.iter().flat_map(|tag| tag.tag.names()).collect();
+    match callee {
+        Some(callee) if !callee.has_no_tag() => {
+            let all = callee
+                .tags
+                .iter()
+                .all(|tag| tag.tag.names().any(|name| discharged.contains(&name)));
+            if all { Status::Discharged } else { Status::Missing }
+        }
+        _ if discharged.is_empty() => Status::Unknown,
+        _ => Status::Discharged,
+    }
+}
+
+fn label(node: &Node, sep: &str, escape: fn(&str) -> String) -> String {
+    let name = escape(node.name);
+    if node.tags.is_empty() {
+        name
+    } else {
+        format!("{name}{sep}{{ {} }}", escape(&node.tags.iter().join(", ")))
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;").replace('<', "#lt;").replace('>', "#gt;")
+}
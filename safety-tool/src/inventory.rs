@@ -0,0 +1,93 @@
+//! Inventory of unsafe functions and methods defined in a crate, written to
+//! `SP_OUT_DIR/<crate>.inventory.json` and `SP_OUT_DIR/<crate>.inventory.md`.
+//!
+//! The JSON is keyed by def paths in the same form as `data/std.json`, and declared tags are
+//! grouped as `{ "0": [...] }`, so the inventory can be diffed against upstream mappings. Since
+//! such paths drop impl blocks, e.g. a method and a free function of the same name in a module,
+//! each key holds a list of APIs. The Markdown is a table in the style of
+//! `data/track_record.md`.
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write};
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Inventory {
+    /// Overlay paths to unsafe APIs, e.g. `core::ptr::const_ptr::add`.
+    pub map: IndexMap<String, Vec<UnsafeApi>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UnsafeApi {
+    /// The path visible to users, e.g. `<*const T>::add`.
+    pub api: String,
+    /// `pub`, `pub(crate)`, `pub(self)`, or `pub(in crate::path)`.
+    pub visibility: String,
+    /// Whether it's reachable from other crates.
+    pub exported: bool,
+    /// Required tags grouped like `data/std.json`. A tag ending with `|` forms `any` with
+    /// the next tags.
+    pub tags: IndexMap<String, Vec<String>>,
+    /// Whether the doc has a `# Safety` section.
+    pub safety_doc: bool,
+    /// `file:line:col`
+    pub location: String,
+}
+
+impl UnsafeApi {
+    pub fn has_tags(&self) -> bool {
+        self.tags.values().any(|tags| !tags.is_empty())
+    }
+
+    /// Tags separated by commas, and alternatives by `|`, e.g. `ValidPtr, Size | ValidSlice`.
+    fn tags_in_markdown(&self) -> String {
+        let mut out = String::new();
+        for tag in self.tags.values().flatten() {
+            if !out.is_empty() && !out.ends_with('|') {
+                out.push(',');
+            }
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            out.push_str(tag);
+        }
+        if out.is_empty() { "-".to_owned() } else { out.replace('|', "\\|") }
+    }
+}
+
+/// Whether the doc has a `# Safety` heading.
+pub fn has_safety_section(doc: &str) -> bool {
+    doc.lines().any(|line| {
+        let line = line.trim();
+        let title = line.trim_start_matches('#');
+        title.len() < line.len() && title.trim().eq_ignore_ascii_case("safety")
+    })
+}
+
+impl Inventory {
+    /// Render the table like `data/track_record.md`.
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        _ = self.write_markdown(&mut md);
+        md
+    }
+
+    fn write_markdown(&self, md: &mut String) -> fmt::Result {
+        writeln!(md, "|Num| API | Visibility | Tags | Safety Doc |")?;
+        writeln!(md, "|---|-----|------------|------|------------|")?;
+        for (idx, api) in self.map.values().flatten().enumerate() {
+            let doc = if api.safety_doc { "yes" } else { "no" };
+            let (num, name, vis) = (idx + 1, &api.api, &api.visibility);
+            writeln!(md, "|{num}| {name} | {vis} | {} | {doc} |", api.tags_in_markdown())?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn safety_section() {
+    assert!(has_safety_section(" Reads.\n\n # Safety\n\n Valid."));
+    assert!(has_safety_section("## safety"));
+    assert!(!has_safety_section("Safety: valid."));
+    assert!(!has_safety_section("# Safety requirements"));
+}
@@ -1,8 +1,17 @@
 #![cfg_attr(feature = "asterinas", feature(let_chains))]
 
+pub mod audit;
+pub mod boundary;
+pub mod diff;
+pub mod entity_ref;
+pub mod graph;
+pub mod inventory;
 pub mod logger;
+pub mod reach;
+pub mod report;
 pub mod stat;
 pub mod utils;
+pub mod workspace;
 
 pub use camino::{Utf8Path, Utf8PathBuf};
 pub use eyre::Result;
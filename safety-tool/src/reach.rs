@@ -0,0 +1,43 @@
+//! Monomorphized instances carrying tool attrs that are reachable from entry points, written to
+//! `SP_OUT_DIR/<crate>.reach.json`.
+//!
+//! Entry points are `main` of a binary, or exported functions of a library. An exported
+//! generic function is an entry point with its own generic params, and calls in MIR of local
+//! functions are followed from it without monomorphization. Tagged functions of the crate never
+//! reached from any entry point are listed as dead.
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Reach {
+    /// Def paths of entry points to tagged instances reached from them.
+    pub entries: IndexMap<String, Vec<TaggedInstance>>,
+    /// Tagged functions of the crate never reached.
+    pub dead: Vec<DeadFn>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TaggedInstance {
+    pub name: String,
+    /// Generic args of the instance, e.g. `["u8"]` for `std::ptr::read::<u8>`.
+    pub generic_args: Vec<String>,
+    /// Tool attrs on the function.
+    pub attrs: Vec<String>,
+    /// Shortest paths from the entry point through each caller of the instance. Functions in
+    /// the path are instances with generic args.
+    pub paths: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeadFn {
+    pub name: String,
+    pub attrs: Vec<String>,
+    /// `file:line:col`
+    pub location: String,
+}
+
+impl Reach {
+    pub fn is_empty(&self) -> bool {
+        self.entries.values().all(Vec::is_empty) && self.dead.is_empty()
+    }
+}
@@ -0,0 +1,331 @@
+//! A standalone HTML report of a crate from its stat JSON and source files, like an lcov report.
+//!
+//! * Modules are source files, each summarized by [`MetricsFuncsTotal`] and unsafe call sites.
+//! * Lines of unsafe calls are green when tags of the callee are fully discharged, yellow when
+//!   discharged through `any` or implication, i.e. auto-discharged by provenance or constant
+//!   evaluation, and red when any tag is missing.
+//! * Tagged functions are shown above their first lines with descs of tags rendered from specs.
+//!
+//! Tags required by external callees aren't in the stat, so a call to an external function is
+//! red only if no tag is discharged at all.
+use crate::{
+    Result,
+    stat::{Func, MetricsFuncsTotal, MetricsFunctions, Stat, Tag, TagType},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use eyre::Context;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use safety_parser::safety::Property;
+use std::{collections::BTreeMap, fmt::Write, fs};
+
+#[derive(Debug, Default)]
+pub struct ReportOptions {
+    /// The dir where paths of functions are relative to, i.e. where the crate was compiled.
+    pub src: Option<Utf8PathBuf>,
+    /// Defaults to `<crate>.html` next to the stat JSON.
+    pub out: Option<Utf8PathBuf>,
+}
+
+impl ReportOptions {
+    /// Parse `[--src DIR] [-o FILE]`.
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        let mut opts = ReportOptions::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value =
+                || args.next().map(Utf8PathBuf::from).ok_or_else(|| eyre!("{arg} needs a value."));
+            match arg.as_str() {
+                "--src" => opts.src = Some(value()?),
+                "-o" | "--out" => opts.out = Some(value()?),
+                _ => bail!("Unknown argument for report: {arg}"),
+            }
+        }
+        Ok(opts)
+    }
+}
+
+/// Read the stat JSON, and write the report. Return the path to the report.
+pub fn write_report(path: &Utf8Path, opts: &ReportOptions) -> Result<Utf8PathBuf> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {path}"))?;
+    let stat: Stat = serde_json::from_str(&text)
+        .with_context(|| format!("Failed to parse {path} as stat of a crate"))?;
+    let src = opts.src.as_deref().unwrap_or(Utf8Path::new("."));
+    let out = opts.out.clone().unwrap_or_else(|| path.with_extension("html"));
+    fs::write(&out, render(&stat, src)).with_context(|| format!("Failed to write {out}"))?;
+    Ok(out)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Coverage {
+    Full,
+    Partial,
+    Missing,
+}
+
+impl Coverage {
+    fn class(self) -> &'static str {
+        match self {
+            Coverage::Full => "full",
+            Coverage::Partial => "partial",
+            Coverage::Missing => "missing",
+        }
+    }
+}
+
+/// Coverage of the call against tags declared by the callee, and notes on the call.
+fn coverage(call: &Func, callee: Option<&Func>) -> (Coverage, String) {
+    let mut explicit = Vec::new();
+    let mut implied = Vec::new();
+    for tag in &call.tags {
+        match &tag.tag {
+            TagType::Vanilla(prop) if !tag.auto => explicit.push(prop.tag.name()),
+            _ => implied.extend(tag.tag.names()),
+        }
+    }
+    let discharged = call.tags.iter().map(|tag| tag.tag.to_string()).unique().join(", ");
+    let note = |missing: &[String]| {
+        let mut note = format!("{}: discharged [{discharged}]", call.name);
+        if !missing.is_empty() {
+            _ = write!(note, ", missing [{}]", missing.join(", "));
+        }
+        note
+    };
+
+    if call.tags.iter().any(|tag| tag.eval == Some(crate::stat::EvalResult::Violated)) {
+        return (Coverage::Missing, note(&[]) + ", violated by constant evaluation");
+    }
+    let Some(callee) = callee else {
+        // Tags required by an external callee are unknown.
+        let coverage = if call.tags.is_empty() {
+            Coverage::Missing
+        } else if explicit.is_empty() {
+            Coverage::Partial
+        } else {
+            Coverage::Full
+        };
+        return (coverage, note(&[]));
+    };
+
+    let mut coverage = Coverage::Full;
+    let mut missing = Vec::new();
+    for tag in &callee.tags {
+        let names: Vec<_> = tag.tag.names().collect();
+        let is_vanilla = matches!(tag.tag, TagType::Vanilla(_));
+        if is_vanilla && names.iter().all(|name| explicit.contains(name)) {
+            continue;
+        }
+        if names.iter().any(|name| explicit.contains(name) || implied.contains(name)) {
+            coverage = coverage.max(Coverage::Partial);
+        } else {
+            coverage = Coverage::Missing;
+            missing.push(names.join(" | "));
+        }
+    }
+    (coverage, note(&missing))
+}
+
+fn render(stat: &Stat, src: &Utf8Path) -> String {
+    let local_fns: IndexMap<_, _> = stat.funcs.iter().map(|f| (f.name.as_str(), f)).collect();
+    let mut modules = IndexMap::<&Utf8Path, Vec<&Func>>::new();
+    for func in &stat.funcs {
+        modules.entry(&func.path).or_default().push(func);
+    }
+    modules.sort_unstable_keys();
+
+    let krate = &stat.krate;
+    let mut out = String::new();
+    _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Safety report of {name}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+         <h1>Safety report of <code>{name}</code> {version}</h1>\n",
+        name = escape(&krate.name),
+        version = escape(&krate.version),
+    );
+
+    out.push_str("<h2>Modules</h2>\n<table class=\"summary\">\n<tr><th>Module</th>");
+    for head in SUMMARY_HEADS {
+        _ = write!(out, "<th>{head}</th>");
+    }
+    out.push_str("</tr>\n");
+    let mut sections = String::new();
+    let mut total_sites = [0; 3];
+    for (path, funcs) in &modules {
+        let mut metrics = MetricsFunctions::default();
+        let mut sites = [0; 3];
+        let mut lines = BTreeMap::<usize, (Coverage, Vec<String>)>::new();
+        for func in funcs {
+            func.update_metrics(&mut metrics);
+            for call in &func.unsafe_calls {
+                let (coverage, note) = coverage(call, local_fns.get(&*call.name).copied());
+                sites[coverage as usize] += 1;
+                total_sites[coverage as usize] += 1;
+                for line in span_lines(&call.span) {
+                    let (c, notes) = lines.entry(line).or_insert((coverage, Vec::new()));
+                    *c = (*c).max(coverage);
+                    notes.push(note.clone());
+                }
+            }
+        }
+        metrics.merge();
+        let link = format!("<a href=\"#{0}\">{0}</a>", escape(path.as_str()));
+        summary_row(&mut out, &link, &metrics.total, sites);
+
+        let tagged: BTreeMap<usize, Vec<&Func>> = funcs
+            .iter()
+            .filter(|func| !func.has_no_tag())
+            .filter_map(|&func| Some((span_lines(&func.span).next()?, func)))
+            .into_group_map()
+            .into_iter()
+            .collect();
+        module_section(&mut sections, stat, path, &src.join(path), &lines, &tagged);
+    }
+    summary_row(&mut out, "Total", &stat.metrics.funcs.total, total_sites);
+    out.push_str("</table>\n");
+    out.push_str(&sections);
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+const STYLE: &str = "body{font-family:sans-serif}\
+table{border-collapse:collapse}\
+.summary td,.summary th{border:1px solid #ccc;padding:2px 8px;text-align:right}\
+.summary td:first-child{text-align:left}\
+.src td{padding:0 8px;vertical-align:top}\
+.src pre{margin:0}\
+.ln{color:#888;text-align:right;user-select:none}\
+.ln a{color:inherit;text-decoration:none}\
+.full{background:#c8e6c9}\
+.partial{background:#fff59d}\
+.missing{background:#ffcdd2}\
+.func td{background:#eceff1;border-top:1px solid #b0bec5}\
+.func ul{margin:2px 0}";
+
+const SUMMARY_HEADS: [&str; 10] = [
+    "Funcs",
+    "Funcs with tags declared",
+    "Funcs with tags discharged",
+    "Declared tags",
+    "Discharged tags",
+    "Auto-discharged tags",
+    "Unsafe calls",
+    "Full",
+    "Partial",
+    "Missing",
+];
+
+fn summary_row(out: &mut String, module: &str, m: &MetricsFuncsTotal, sites: [u16; 3]) {
+    let totals = [
+        m.funcs,
+        m.funcs_with_tags_declared,
+        m.funcs_with_tags_discharged,
+        m.declared_tags,
+        m.discharged_tags,
+        m.auto_discharged_tags,
+        m.unsafe_calls,
+    ];
+    let cells =
+        totals.iter().chain(&sites).format_with("", |cell, f| f(&format_args!("<td>{cell}</td>")));
+    _ = writeln!(out, "<tr><td>{module}</td>{cells}</tr>");
+}
+
+/// Lines in a span of `line` or `start:end`.
+fn span_lines(span: &str) -> impl Iterator<Item = usize> {
+    let (start, end) = span.split_once(':').unwrap_or((span, span));
+    let (start, end) = (start.parse().unwrap_or(0), end.parse().unwrap_or(0));
+    (start..=end).filter(|&line| line != 0)
+}
+
+fn module_section(
+    out: &mut String,
+    stat: &Stat,
+    path: &Utf8Path,
+    file: &Utf8Path,
+    lines: &BTreeMap<usize, (Coverage, Vec<String>)>,
+    tagged: &BTreeMap<usize, Vec<&Func>>,
+) {
+    _ = writeln!(out, "<h2 id=\"{0}\">{0}</h2>", escape(path.as_str()));
+    let Ok(source) = fs::read_to_string(file) else {
+        _ = writeln!(
+            out,
+            "<p>Source file <code>{}</code> is not found.</p>",
+            escape(file.as_str())
+        );
+        return;
+    };
+    out.push_str("<table class=\"src\">\n");
+    for (line, code) in source.lines().enumerate().map(|(idx, code)| (idx + 1, code)) {
+        for func in tagged.get(&line).into_iter().flatten() {
+            _ = writeln!(
+                out,
+                "<tr class=\"func\"><td></td><td><code>{}</code>{}</td></tr>",
+                escape(&func.name),
+                tags_desc(stat, &func.tags)
+            );
+        }
+        let anchor = escape(&format!("{path}:{line}"));
+        let (class, title) = match lines.get(&line) {
+            Some((coverage, notes)) => (
+                format!(" class=\"{}\"", coverage.class()),
+                format!(" title=\"{}\"", escape(&notes.iter().unique().join("\n"))),
+            ),
+            None => Default::default(),
+        };
+        _ = writeln!(
+            out,
+            "<tr id=\"{anchor}\"{class}{title}><td class=\"ln\"><a href=\"#{anchor}\">{line}</a>\
+             </td><td><pre>{}</pre></td></tr>",
+            escape(code)
+        );
+    }
+    out.push_str("</table>\n");
+}
+
+fn tags_desc(stat: &Stat, tags: &[Tag]) -> String {
+    let mut out = String::from("<ul>");
+    for tag in tags {
+        match &tag.tag {
+            TagType::Vanilla(prop) => _ = write!(out, "<li>{}</li>", prop_desc(stat, prop)),
+            TagType::Any(props) => {
+                out.push_str("<li><code>any</code>: only one of the following is required<ul>");
+                for prop in props.iter().flat_map(|p| &p.tags) {
+                    _ = write!(out, "<li>{}</li>", prop_desc(stat, prop));
+                }
+                out.push_str("</ul></li>");
+            }
+        }
+    }
+    out.push_str("</ul>");
+    out
+}
+
+/// The tag with its desc rendered from the spec, both escaped as HTML.
+fn prop_desc(stat: &Stat, prop: &Property) -> String {
+    let name = prop.tag.name();
+    let args = prop.args_as_str();
+    let tag = if args.is_empty() {
+        format!("<code>{}</code>", escape(name))
+    } else {
+        format!("<code>{}({})</code>", escape(name), escape(&args.join(", ")))
+    };
+    let spec = stat.specs.map.get(name);
+    match spec.and_then(|spec| prop.gen_desc_in(&spec.item.tag)) {
+        Some(desc) => format!("{tag}: {}", escape(desc.trim())),
+        None => tag,
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
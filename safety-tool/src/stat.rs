@@ -82,6 +82,18 @@ impl Stat {
         {
             _ = serde_json::to_writer_pretty(file, self);
             self.write_call_tree();
+            self.write_graphs();
+        }
+    }
+
+    /// Write the call graph as Graphviz DOT and Mermaid flowchart.
+    pub fn write_graphs(&self) {
+        let graph = crate::graph::Graph::new(self, &Default::default());
+        if let Some(path) = self.krate.output_file_path("dot") {
+            _ = fs::write(path, graph.to_dot());
+        }
+        if let Some(path) = self.krate.output_file_path("mmd") {
+            _ = fs::write(path, graph.to_mermaid());
         }
     }
 
@@ -156,7 +168,8 @@ fn out_dir() -> Option<Utf8PathBuf> {
 }
 
 impl Krate {
-    fn output_file_path(&self, ext: &str) -> Option<Utf8PathBuf> {
+    /// Output file of the crate with the extension under `SP_OUT_DIR`.
+    pub fn output_file_path(&self, ext: &str) -> Option<Utf8PathBuf> {
         let dir = out_dir()?;
         let prefix = match self.typ {
             CrateType::Bin => "bin-",
@@ -283,6 +296,10 @@ pub struct Func {
     pub span: String,
     pub tags: Vec<Tag>,
     pub unsafe_calls: Vec<Func>,
+    /// The version of the crate defining the callee, if it's a dependency analyzed by
+    /// safety-tool. Only set on calls.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
 }
 
 impl Func {
@@ -313,7 +330,7 @@ impl Func {
         }
     }
 
-    fn update_metrics(&self, metrics_funcs: &mut MetricsFunctions) {
+    pub(crate) fn update_metrics(&self, metrics_funcs: &mut MetricsFunctions) {
         let m = if self.safe { &mut metrics_funcs.safe } else { &mut metrics_funcs.r#unsafe };
         m.total.funcs += 1;
 
@@ -327,19 +344,24 @@ impl Func {
         m.total.declared_tags += self.tags.len() as u16;
         m.total.discharged_tags +=
             self.unsafe_calls.iter().map(|c| c.tags.len() as u16).sum::<u16>();
+        m.total.auto_discharged_tags += self
+            .unsafe_calls
+            .iter()
+            .map(|c| c.tags.iter().filter(|tag| tag.auto).count() as u16)
+            .sum::<u16>();
 
         let unsafe_calls = self.unsafe_calls.len() as u16;
         m.total.unsafe_calls += unsafe_calls;
         m.unsafe_calls.entry(unsafe_calls).and_modify(|c| *c += 1).or_insert(1);
     }
 
-    fn has_no_tag(&self) -> bool {
+    pub(crate) fn has_no_tag(&self) -> bool {
         // There must be a tag as an element in self.tags. It could possibly be
         // empty for Any TagType, but that's still an `any` tag.
         self.tags.is_empty()
     }
 
-    fn root_node(&self) -> String {
+    pub(crate) fn root_node(&self) -> String {
         if self.has_no_tag() {
             self.name.to_owned()
         } else {
@@ -367,16 +389,38 @@ pub struct Tag {
     pub predicate: Predicate,
     pub tag: TagType,
     pub doc: Option<Box<str>>,
+    /// The tag is auto-discharged through provenance of pointer args, rather than by a tool attr.
+    #[serde(default)]
+    pub auto: bool,
+    /// The tag is decided at the call site through constant evaluation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval: Option<EvalResult>,
 }
 
 impl Tag {
     pub fn requires_vanilla(prop: Property) -> Tag {
-        Tag { predicate: Predicate::Requires, tag: TagType::Vanilla(prop), doc: None }
+        let tag = TagType::Vanilla(prop);
+        Tag { predicate: Predicate::Requires, tag, doc: None, auto: false, eval: None }
     }
 
     pub fn requires_any(props: Vec<PropertiesAndReason>) -> Tag {
-        Tag { predicate: Predicate::Requires, tag: TagType::Any(props), doc: None }
+        let tag = TagType::Any(props);
+        Tag { predicate: Predicate::Requires, tag, doc: None, auto: false, eval: None }
     }
+
+    pub fn auto_discharged(prop: Property) -> Tag {
+        Tag { auto: true, ..Tag::requires_vanilla(prop) }
+    }
+}
+
+/// Result of constant evaluation of a tag at the call site.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EvalResult {
+    /// The tag always holds, and is auto-discharged.
+    Proven,
+    /// The tag never holds.
+    Violated,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -387,6 +431,17 @@ pub enum TagType {
     Any(Vec<PropertiesAndReason>),
 }
 
+impl TagType {
+    /// Tag names, i.e. the name of a vanilla tag, or names in `any`.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        let (vanilla, any) = match self {
+            TagType::Vanilla(prop) => (Some(prop.tag.name()), &[][..]),
+            TagType::Any(props) => (None, &props[..]),
+        };
+        vanilla.into_iter().chain(any.iter().flat_map(|p| &p.tags).map(|p| p.tag.name()))
+    }
+}
+
 impl fmt::Display for TagType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -428,7 +483,35 @@ pub struct Metrics {
     pub funcs: MetricsFunctions,
 }
 
-#[derive(Debug, Default, Deserialize, Serialize)]
+impl Metrics {
+    /// Add metrics of another crate. Amounts of tags and unused tags aren't added, since specs
+    /// of crates overlap: they're counted against the union of specs by [`Self::count_tags`].
+    pub fn add(&mut self, other: &Self) {
+        for (name, coverage) in &other.used {
+            self.used.entry(name.clone()).or_default().merge(coverage);
+        }
+        self.coverage.merge(&other.coverage);
+        self.funcs.add(&other.funcs);
+    }
+
+    /// Count used and unused tags against tag names in specs. This should be called after all
+    /// metrics are added.
+    pub fn count_tags<'a>(&mut self, spec_tags: impl IntoIterator<Item = &'a str>) {
+        let spec_tags: IndexSet<&str> = spec_tags.into_iter().collect();
+        self.total_tags = spec_tags.len().try_into().unwrap();
+        self.used_tags = self.used.len().try_into().unwrap();
+        self.unused = spec_tags
+            .iter()
+            .filter(|tag| !self.used.contains_key(**tag))
+            .map(|&tag| tag.into())
+            .collect();
+        self.used.sort_unstable_by(|a_name, a_cov, b_name, b_cov| {
+            (b_cov.occurence, a_name).cmp(&(a_cov.occurence, b_name))
+        });
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
 pub struct MetricsCoverage {
     /// Sum of the requires, checked, and delegated (or equivalently as_vanilla + in_any).
     pub occurence: u16,
@@ -485,10 +568,16 @@ pub struct MetricsFunctions {
 }
 
 impl MetricsFunctions {
-    fn merge(&mut self) {
+    pub(crate) fn merge(&mut self) {
         self.total.merge(&self.safe.total);
         self.total.merge(&self.r#unsafe.total);
     }
+
+    fn add(&mut self, other: &Self) {
+        self.total.merge(&other.total);
+        self.safe.add(&other.safe);
+        self.r#unsafe.add(&other.r#unsafe);
+    }
 }
 
 #[derive(Debug, Default, Deserialize, Serialize)]
@@ -497,6 +586,16 @@ pub struct MetricsFuncs {
     pub unsafe_calls: IndexMap<u16, u16>,
 }
 
+impl MetricsFuncs {
+    fn add(&mut self, other: &Self) {
+        self.total.merge(&other.total);
+        for (&calls, &count) in &other.unsafe_calls {
+            *self.unsafe_calls.entry(calls).or_default() += count;
+        }
+        self.unsafe_calls.sort_unstable_keys();
+    }
+}
+
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct MetricsFuncsTotal {
     pub funcs: u16,
@@ -504,6 +603,9 @@ pub struct MetricsFuncsTotal {
     pub funcs_with_tags_discharged: u16,
     pub declared_tags: u16,
     pub discharged_tags: u16,
+    /// Discharged tags that are auto-discharged, which are part of `discharged_tags`.
+    #[serde(default)]
+    pub auto_discharged_tags: u16,
     pub unsafe_calls: u16,
 }
 
@@ -514,6 +616,7 @@ impl MetricsFuncsTotal {
         self.funcs_with_tags_discharged += other.funcs_with_tags_discharged;
         self.declared_tags += other.declared_tags;
         self.discharged_tags += other.discharged_tags;
+        self.auto_discharged_tags += other.auto_discharged_tags;
         self.unsafe_calls += other.unsafe_calls;
     }
 }
@@ -0,0 +1,194 @@
+//! Aggregate stat JSON of crates in `SP_OUT_DIR`s into a workspace report, written to
+//! `workspace.aggregate.json` and `workspace.aggregate.txt` in the first dir.
+//!
+//! * Callees in other crates are resolved to their functions by def paths, so call trees go
+//!   across crates, with tags of callees. A call recording the version of the callee's crate
+//!   is resolved to that version only.
+//! * Metrics are summed, and unused tags are counted against the union of specs.
+//! * Crates are identified by names and versions. A crate name with several versions is
+//!   reported, and nodes of these crates are suffixed with versions in trees.
+use crate::{
+    Result,
+    stat::{Func, Krate, Metrics, Stat},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use eyre::Context;
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Workspace {
+    pub crates: Vec<Krate>,
+    /// Crate names with several versions.
+    pub versions: IndexMap<String, Vec<String>>,
+    pub metrics: Metrics,
+}
+
+/// Stat files in the dirs, i.e. `<crate>.json` and `bin-<crate>.json`. Other outputs like
+/// `<crate>.refs.json` have dots in file stems.
+pub(crate) fn read_stats(dirs: &[Utf8PathBuf]) -> Result<Vec<Stat>> {
+    let mut stats: Vec<Stat> = Vec::new();
+    for dir in dirs {
+        let mut paths = Vec::new();
+        for entry in dir.read_dir_utf8().with_context(|| format!("Failed to read {dir}"))? {
+            let path = entry?.into_path();
+            if path.extension() == Some("json")
+                && path.file_stem().is_some_and(|s| !s.contains('.'))
+            {
+                paths.push(path);
+            }
+        }
+        paths.sort_unstable();
+        for path in paths {
+            let text = fs::read_to_string(&path)?;
+            let stat: Stat = serde_json::from_str(&text)
+                .with_context(|| format!("Failed to parse {path} as stat of a crate"))?;
+            // The same crate in several dirs is taken once.
+            let krate = &stat.krate;
+            let is_dup = stats.iter().any(|s| {
+                s.krate.name == krate.name
+                    && s.krate.version == krate.version
+                    && s.krate.path == krate.path
+            });
+            if !is_dup {
+                stats.push(stat);
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// Aggregate stats in the dirs, and return paths of the written JSON and tree files.
+pub fn aggregate(dirs: &[Utf8PathBuf]) -> Result<[Utf8PathBuf; 2]> {
+    let out = dirs.first().ok_or_else(|| eyre!("No dir to aggregate."))?;
+    let stats = read_stats(dirs)?;
+
+    let mut versions = IndexMap::<String, Vec<String>>::new();
+    for stat in &stats {
+        let v = versions.entry(stat.krate.name.clone()).or_default();
+        if !v.contains(&stat.krate.version) {
+            v.push(stat.krate.version.clone());
+        }
+    }
+    versions.retain(|_, v| v.len() > 1);
+
+    let mut metrics = Metrics::default();
+    let mut spec_tags = IndexSet::new();
+    for stat in &stats {
+        metrics.add(&stat.metrics);
+        spec_tags.extend(stat.specs.map.keys().map(|tag| &**tag));
+    }
+    spec_tags.sort_unstable();
+    metrics.count_tags(spec_tags);
+
+    let trees = Trees::new(&stats, &versions).render();
+    let crates = stats.into_iter().map(|stat| stat.krate).collect();
+    let workspace = Workspace { crates, versions, metrics };
+
+    let json = out.join("workspace.aggregate.json");
+    let txt = out.join("workspace.aggregate.txt");
+    fs::write(&json, serde_json::to_string_pretty(&workspace)?)?;
+    fs::write(&txt, trees)?;
+    Ok([json, txt])
+}
+
+type Tree = termtree::Tree<String>;
+
+/// Call trees across crates.
+struct Trees<'a> {
+    stats: &'a [Stat],
+    /// Functions in each crate by names.
+    funcs: Vec<IndexMap<&'a str, &'a Func>>,
+    versions: &'a IndexMap<String, Vec<String>>,
+}
+
+impl<'a> Trees<'a> {
+    fn new(stats: &'a [Stat], versions: &'a IndexMap<String, Vec<String>>) -> Self {
+        let funcs =
+            stats.iter().map(|s| s.funcs.iter().map(|f| (f.name.as_str(), f)).collect()).collect();
+        Trees { stats, funcs, versions }
+    }
+
+    /// The crate name, suffixed with the version if there are several versions.
+    fn crate_name(&self, idx: usize) -> String {
+        let krate = &self.stats[idx].krate;
+        if self.versions.contains_key(&krate.name) {
+            format!("{}@{}", krate.name, krate.version)
+        } else {
+            krate.name.clone()
+        }
+    }
+
+    fn node(&self, idx: usize, func: &Func) -> String {
+        format!("{}::{}", self.crate_name(idx), func.root_node())
+    }
+
+    /// Functions called in the crate: a local function, or functions in crates named by the
+    /// first segment of the path, of the version the call is resolved to if it's recorded.
+    fn resolve(&self, idx: usize, call: &Func) -> Vec<(usize, &'a Func)> {
+        let name = &*call.name;
+        if let Some(func) = self.funcs[idx].get(name) {
+            return vec![(idx, func)];
+        }
+        let Some((krate, path)) = name.split_once("::") else { return Vec::new() };
+        let candidates = self.stats.iter().enumerate().filter(|(_, s)| {
+            s.krate.name == krate && call.version.as_ref().is_none_or(|v| *v == s.krate.version)
+        });
+        candidates.filter_map(|(i, _)| Some((i, *self.funcs[i].get(path)?))).collect()
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        let mut intree = IndexSet::new();
+        for (idx, stat) in self.stats.iter().enumerate() {
+            for func in &stat.funcs {
+                if func.unsafe_calls.is_empty() && func.has_no_tag() {
+                    // Skip functions that have no unsafe calls and no tags.
+                    continue;
+                }
+                let mut tree = Tree::new(self.node(idx, func));
+                intree.insert((idx, &*func.name));
+                self.call_tree(&mut tree, idx, func, &mut intree);
+                out.push_str(&tree.to_string());
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    // Recursively add the call nodes, which are expanded once.
+    fn call_tree(
+        &self,
+        tree: &mut Tree,
+        idx: usize,
+        func: &'a Func,
+        intree: &mut IndexSet<(usize, &'a str)>,
+    ) {
+        for call in &func.unsafe_calls {
+            let resolved = self.resolve(idx, call);
+            if resolved.is_empty() {
+                // The callee is outside the workspace, thus only the name is available.
+                tree.push(call.name.clone());
+            }
+            for (callee_idx, callee) in resolved {
+                if intree.insert((callee_idx, &callee.name)) {
+                    let mut leaf = Tree::new(self.node(callee_idx, callee));
+                    self.call_tree(&mut leaf, callee_idx, callee, intree);
+                    tree.push(leaf);
+                } else {
+                    tree.push(self.node(callee_idx, callee));
+                }
+            }
+        }
+    }
+}
+
+/// Aggregate into `SP_OUT_DIR` when no dir is given.
+pub fn dirs_or_out_dir(dirs: &[String]) -> Result<Vec<Utf8PathBuf>> {
+    if !dirs.is_empty() {
+        return Ok(dirs.iter().map(Utf8PathBuf::from).collect());
+    }
+    let dir = std::env::var("SP_OUT_DIR").context("Pass dirs to aggregate, or set SP_OUT_DIR.")?;
+    Ok(vec![Utf8Path::new(&dir).to_owned()])
+}
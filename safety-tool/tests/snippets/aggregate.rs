@@ -0,0 +1,13 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+extern crate aggregate_dep;
+
+pub fn user() {
+    #[rapx::checked(SP1, SP2)]
+    unsafe {
+        aggregate_dep::wrapper()
+    }
+}
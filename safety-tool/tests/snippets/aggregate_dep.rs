@@ -0,0 +1,12 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::inner(SP1)]
+unsafe fn read() {}
+
+#[rapx::inner(SP1, SP2)]
+pub unsafe fn wrapper() {
+    unsafe { read() }
+}
@@ -0,0 +1,35 @@
+#![feature(register_tool)]
+#![register_tool(safety)]
+#![allow(dead_code, clippy::missing_safety_doc)]
+
+pub mod ptr {
+    /// Reads the value from `p`.
+    #[inline]
+    pub unsafe fn read<T>(p: *const T) -> T {
+        unsafe { p.read() }
+    }
+
+    #[safety::requires { ValidPtr(p, T, 1) }]
+    pub unsafe fn write<T>(p: *mut T, val: T) {
+        unsafe { p.write(val) }
+    }
+}
+
+pub struct Bytes;
+
+impl Bytes {
+    #[safety::requires {
+        Init(p, u8, len),
+    }]
+    pub unsafe fn from_raw_parts(p: *const u8, len: usize) -> &'static [u8] {
+        unsafe { std::slice::from_raw_parts(p, len) }
+    }
+}
+
+macro_rules! generate {
+    () => {
+        pub unsafe fn generated() {}
+    };
+}
+
+generate!();
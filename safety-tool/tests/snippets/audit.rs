@@ -0,0 +1,43 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::inner(SP1, SP2)]
+unsafe fn from_raw() {}
+
+#[rapx::inner(any(SP3, SP4))]
+unsafe fn any() {}
+
+/// # Safety
+///
+/// SP2 must hold.
+#[rapx::inner(SP1)]
+pub unsafe fn missing() {
+    unsafe { from_raw() };
+    unsafe { any() };
+}
+
+/// Locally discharged tags are not forwarded.
+#[rapx::inner(SP1)]
+pub unsafe fn discharged() {
+    #[rapx::checked(SP2)]
+    unsafe {
+        from_raw()
+    };
+}
+
+/// Private functions are not audited.
+unsafe fn private() {
+    unsafe { from_raw() };
+}
+
+pub struct S;
+
+impl S {
+    /// Shares the overlay path with the free function.
+    #[rapx::inner(SP2)]
+    pub unsafe fn missing(&self) {
+        unsafe { from_raw() };
+    }
+}
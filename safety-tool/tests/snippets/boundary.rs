@@ -0,0 +1,66 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::inner(SP1, SP2)]
+unsafe fn read() {}
+
+#[rapx::inner(any(SP3, SP4))]
+unsafe fn any() {}
+
+/// Forwards `SP1` only.
+#[rapx::inner(SP1)]
+unsafe fn wrapper() {
+    unsafe { read() }
+}
+
+pub fn direct() {
+    #[rapx::checked(SP1, SP2)]
+    unsafe {
+        read()
+    };
+    #[rapx::checked(SP4)]
+    unsafe {
+        any()
+    };
+}
+
+pub fn forward() {
+    #[rapx::checked(SP1)]
+    unsafe {
+        wrapper()
+    };
+}
+
+fn helper() {
+    #[rapx::checked(SP2)]
+    unsafe {
+        read()
+    };
+}
+
+pub fn nested() {
+    helper();
+}
+
+pub fn untagged() {}
+
+/// Calls in the closure are discharged by this function.
+pub fn closure() {
+    let f = || {
+        #[rapx::checked(SP1, SP2)]
+        unsafe {
+            read()
+        };
+    };
+    f();
+}
+
+/// Not a root, since it's never instantiated in the crate.
+pub fn generic<T>(_: T) {
+    #[rapx::checked(SP1, SP2)]
+    unsafe {
+        read()
+    };
+}
@@ -0,0 +1,54 @@
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+fn init() {}
+fn deinit() {}
+
+#[rapx::requires(PostToFunc(init))]
+fn use_after_init() {}
+
+#[rapx::requires(NotPostToFunc(deinit))]
+fn use_before_deinit() {}
+
+#[rapx::requires(CallOnce)]
+fn setup() {}
+
+#[rapx::requires(CalledBy(driver))]
+fn callback() {}
+
+pub fn no_init() {
+    use_after_init();
+}
+
+pub fn init_on_one_branch(b: bool) {
+    if b {
+        init();
+    }
+    helper();
+}
+
+fn helper() {
+    use_after_init();
+}
+
+pub fn after_deinit(b: bool) {
+    if b {
+        deinit();
+    }
+    use_before_deinit();
+}
+
+pub fn setup_in_loop() {
+    for _ in 0..2 {
+        setup();
+    }
+}
+
+pub fn setup_again() {
+    setup();
+}
+
+pub fn not_driver() {
+    callback();
+}
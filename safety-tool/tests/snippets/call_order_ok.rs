@@ -0,0 +1,43 @@
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+fn init() {}
+fn deinit() {}
+
+#[rapx::requires(PostToFunc(init))]
+fn use_after_init() {}
+
+#[rapx::requires(NotPostToFunc(deinit))]
+fn use_before_deinit() {}
+
+#[rapx::requires(CallOnce)]
+fn setup() {}
+
+#[rapx::requires(CalledBy(driver))]
+fn callback() {}
+
+fn init_all() {
+    init();
+}
+
+pub fn driver() {
+    setup();
+    init_all();
+    use_after_init();
+    helper();
+    use_before_deinit();
+    deinit();
+    callback();
+}
+
+fn helper() {
+    // The requirement is satisfied by all callers.
+    use_after_init();
+}
+
+// The requirement is propagated to callers.
+#[rapx::requires(PostToFunc(init))]
+fn delegated() {
+    use_after_init();
+}
@@ -0,0 +1,34 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::requires(ValidNum(N, 1..=64))]
+unsafe fn bits<const N: u32>(x: u64) -> u64 {
+    x >> (64 - N)
+}
+
+#[rapx::requires(ValidNum(shift, 0..64))]
+unsafe fn shl(x: u64, shift: u32) -> u64 {
+    x << shift
+}
+
+// A constant out of range is an error even if it's discharged.
+pub fn out_of_range(x: u64) -> u64 {
+    #[rapx::checked(ValidNum)]
+    unsafe {
+        shl(x, 64)
+    }
+}
+
+pub fn const_generic(x: u64) -> u64 {
+    unsafe { bits::<0>(x) }
+}
+
+// `ValidNum` is proven, so the discharge is redundant.
+pub fn redundant(x: u64) -> u64 {
+    #[rapx::checked(ValidNum)]
+    unsafe {
+        shl(x, 1)
+    }
+}
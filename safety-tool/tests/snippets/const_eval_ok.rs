@@ -0,0 +1,89 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::requires(ValidNum(len * sizeof(T), [0, isize::MAX]), Init(p, T, len))]
+unsafe fn slice<'a, T>(p: *const T, len: usize) -> &'a [T] {
+    unsafe { std::slice::from_raw_parts(p, len) }
+}
+
+#[rapx::requires(ValidNum(N, 1..=64))]
+unsafe fn bits<const N: u32>(x: u64) -> u64 {
+    x >> (64 - N)
+}
+
+#[rapx::requires(ValidNum(shift, 0..64))]
+unsafe fn shl(x: u64, shift: u32) -> u64 {
+    x << shift
+}
+
+#[rapx::requires(ValidNum(count * sizeof(T), [0, isize::MAX]))]
+unsafe fn zeroed<T>(count: u32) -> Vec<T> {
+    Vec::with_capacity(count as usize)
+}
+
+// `ValidNum` is proven for a constant length.
+pub fn constant(p: *const u32) -> &'static [u32] {
+    #[rapx::checked(Init)]
+    unsafe {
+        slice(p, 4)
+    }
+}
+
+// A constant is traced through locals.
+pub fn local(p: *const u64) -> &'static [u64] {
+    let len = 8;
+    #[rapx::checked(Init)]
+    unsafe {
+        slice(p, len)
+    }
+}
+
+// The const generic arg is in range.
+pub fn const_generic(x: u64) -> u64 {
+    unsafe { bits::<8>(x) }
+}
+
+// The type of `count` proves the range: `u32::MAX * 8` is within `isize::MAX`.
+pub fn by_type(count: u32) -> Vec<u64> {
+    unsafe { zeroed::<u64>(count) }
+}
+
+// The range of `u32` is not within `0..64`.
+pub fn not_by_type(x: u64, shift: u32) -> u64 {
+    #[rapx::checked(ValidNum)]
+    unsafe {
+        shl(x, shift)
+    }
+}
+
+// An unknown length needs a discharge.
+pub fn unknown(p: *const u32, len: usize) -> &'static [u32] {
+    #[rapx::checked(ValidNum, Init)]
+    unsafe {
+        slice(p, len)
+    }
+}
+
+// A local written through a mutable borrow isn't traced back to `4`, and needs a discharge.
+pub fn borrowed(p: *const u32) -> &'static [u32] {
+    let mut len = 4;
+    fill(&mut len);
+    #[rapx::checked(ValidNum, Init)]
+    unsafe {
+        slice(p, len)
+    }
+}
+
+fn fill(len: &mut usize) {
+    *len = usize::MAX;
+}
+
+// Integer casts and arithmetic in the caller are traced: `n as usize + 1` is in [1, 65536].
+pub fn computed(p: *const u32, n: u16) -> &'static [u32] {
+    #[rapx::checked(Init)]
+    unsafe {
+        slice(p, n as usize + 1)
+    }
+}
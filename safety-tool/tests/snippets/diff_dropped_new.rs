@@ -0,0 +1,15 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+/// `SP2` is no longer required, so it's not discharged at the call either.
+#[rapx::inner(SP1)]
+unsafe fn read() {}
+
+pub fn caller() {
+    #[rapx::checked(SP1)]
+    unsafe {
+        read()
+    }
+}
@@ -0,0 +1,14 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::inner(SP1, SP2)]
+unsafe fn read() {}
+
+pub fn caller() {
+    #[rapx::checked(SP1, SP2)]
+    unsafe {
+        read()
+    }
+}
@@ -0,0 +1,21 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::inner(SP1, SP2)]
+unsafe fn read() {}
+
+#[rapx::inner(SP4)]
+unsafe fn write() {}
+
+pub fn caller() {
+    #[rapx::checked(SP1)]
+    unsafe {
+        read()
+    }
+    #[rapx::checked(SP4)]
+    unsafe {
+        write()
+    }
+}
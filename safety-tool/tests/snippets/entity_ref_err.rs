@@ -0,0 +1,29 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::requires(SP1, SP2)]
+unsafe fn read(_: *const u8) -> u8 {
+    0
+}
+
+pub fn try_fold(p: *const u8, q: *const u8) -> u8 {
+    #[rapx::checked { name = try_fold; SP1, SP2: "p and q are valid for the whole loop" }]
+    let a = unsafe { read(p) };
+    #[rapx::r#ref(try_fold2)]
+    let b = unsafe { read(q) };
+    a + b
+}
+
+pub fn unnamed(p: *const u8) -> u8 {
+    #[rapx::r#ref]
+    let a = unsafe { read(p) };
+    a
+}
+
+pub fn duplicated(p: *const u8) -> u8 {
+    #[rapx::checked { name = try_fold; SP1, SP2: "p is valid" }]
+    let a = unsafe { read(p) };
+    a
+}
@@ -0,0 +1,17 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::requires(SP1, SP2)]
+unsafe fn read(_: *const u8) -> u8 {
+    0
+}
+
+pub fn try_fold(p: *const u8, q: *const u8) -> u8 {
+    #[rapx::checked { name = try_fold; SP1, SP2: "p and q are valid for the whole loop" }]
+    let a = unsafe { read(p) };
+    #[rapx::r#ref(try_fold)]
+    let b = unsafe { read(q.wrapping_add(1)) };
+    a + b
+}
@@ -0,0 +1,44 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::inner(SP1, SP2)]
+unsafe fn read() {}
+
+#[rapx::inner(any(SP3, SP4))]
+unsafe fn any() {}
+
+#[rapx::inner(SP1)]
+unsafe fn wrapper() {
+    #[rapx::checked(SP1)]
+    unsafe {
+        read()
+    }
+}
+
+pub fn discharged() {
+    #[rapx::checked(SP1, SP2)]
+    unsafe {
+        read()
+    };
+    #[rapx::checked(SP4)]
+    unsafe {
+        any()
+    };
+}
+
+pub fn forward() {
+    #[rapx::checked(SP1)]
+    unsafe {
+        wrapper()
+    };
+}
+
+/// The external callee is labeled without tags discharged here.
+pub fn external(p: *const u8) -> u8 {
+    #[rapx::checked(SP1)]
+    unsafe {
+        p.read()
+    }
+}
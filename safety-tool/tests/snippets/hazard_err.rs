@@ -0,0 +1,25 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::requires(Alias)]
+unsafe fn as_mut(p: *mut u8) -> &'static mut u8 {
+    unsafe { &mut *p }
+}
+
+fn release(_: &mut u8) {}
+
+pub fn never_resolved(p: *mut u8) {
+    #[rapx::checked(Alias)]
+    let r = unsafe { as_mut(p) };
+    *r = 1;
+}
+
+pub fn resolved_on_one_branch(p: *mut u8, b: bool) {
+    #[rapx::checked(Alias)]
+    let r = unsafe { as_mut(p) };
+    if b {
+        release(r);
+    }
+}
@@ -0,0 +1,53 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::requires(Alias)]
+unsafe fn as_mut(p: *mut u8) -> &'static mut u8 {
+    unsafe { &mut *p }
+}
+
+#[rapx::requires(Pinned)]
+unsafe fn pin(_: *mut u8) {}
+
+fn release(_: &mut u8) {}
+
+#[rapx::resolves(Alias)]
+fn forget(_: &mut u8) {}
+
+pub fn resolved_by_call(p: *mut u8, b: bool) {
+    #[rapx::checked(Alias)]
+    let r = unsafe { as_mut(p) };
+    if b {
+        release(r);
+    } else {
+        forget(r);
+    }
+}
+
+pub fn resolved_by_annotation(p: *mut u8) {
+    #[rapx::checked(Alias)]
+    let r = unsafe { as_mut(p) };
+    #[rapx::resolves(Unaliased)]
+    {
+        *r = 0;
+    }
+}
+
+// The hazard is passed on to callers.
+#[rapx::requires(Alias)]
+pub unsafe fn wrapper(p: *mut u8) -> &'static mut u8 {
+    #[rapx::checked(Alias)]
+    unsafe {
+        as_mut(p)
+    }
+}
+
+// Hazards without `resolved_by` are not tracked.
+pub fn untracked(p: *mut u8) {
+    #[rapx::checked(Pinned)]
+    unsafe {
+        pin(p)
+    };
+}
@@ -0,0 +1,19 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::requires(Alias)]
+unsafe fn as_mut(p: *mut u8) -> &'static mut u8 {
+    unsafe { &mut *p }
+}
+
+fn release(_: &mut u8) {}
+
+// The user callback may panic before the hazard is resolved.
+pub fn callback_may_panic(p: *mut u8, f: impl FnOnce(&mut u8)) {
+    #[rapx::checked(Alias)]
+    let r = unsafe { as_mut(p) };
+    f(r);
+    release(r);
+}
@@ -0,0 +1,24 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::requires(Alias)]
+unsafe fn as_mut(p: *mut u8) -> &'static mut u8 {
+    unsafe { &mut *p }
+}
+
+struct Guard;
+
+impl Drop for Guard {
+    #[rapx::resolves(Alias)]
+    fn drop(&mut self) {}
+}
+
+// The guard resolves the hazard on both normal and unwinding paths.
+pub fn guarded(p: *mut u8, f: impl FnOnce(&mut u8)) {
+    #[rapx::checked(Alias)]
+    let r = unsafe { as_mut(p) };
+    let _guard = Guard;
+    f(r);
+}
@@ -0,0 +1,54 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::invariant(SP1)]
+pub struct Buf {
+    len: usize,
+}
+
+impl Buf {
+    pub fn new() -> Self {
+        #[rapx::checked(SP1)]
+        Buf { len: 0 }
+    }
+
+    pub fn clear(&mut self) {
+        // Field writes in the inherent impl are fine.
+        self.len = 0;
+    }
+}
+
+pub fn construct() -> Buf {
+    Buf { len: 1 }
+}
+
+pub fn write(buf: &mut Buf) {
+    buf.len += 1;
+}
+
+pub fn write_through_mut(buf: &mut Buf) {
+    let len = &mut buf.len;
+    *len = 2;
+}
+
+#[rapx::invariant(SP1)]
+pub struct Sorted {
+    items: Vec<u8>,
+}
+
+pub fn push(sorted: &mut Sorted) {
+    sorted.items.push(1);
+}
+
+pub struct Outer {
+    buf: Buf,
+    bufs: Vec<Buf>,
+}
+
+pub fn write_nested(outer: &mut Outer) {
+    outer.buf.len = 3;
+    outer.bufs[0].len = 4;
+    core::mem::swap(&mut outer.buf.len, &mut 5);
+}
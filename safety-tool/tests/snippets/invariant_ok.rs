@@ -0,0 +1,38 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::invariant(SP1)]
+pub struct Buf {
+    len: usize,
+}
+
+pub fn construct() -> Buf {
+    #[rapx::checked(SP1)]
+    Buf { len: 1 }
+}
+
+pub fn write(buf: &mut Buf) {
+    #[rapx::checked(SP1: "len is still in bounds")]
+    {
+        buf.len = 2;
+    }
+}
+
+#[rapx::invariant(SP1)]
+pub struct Sorted {
+    items: Vec<u8>,
+    parent: &'static mut Vec<u8>,
+}
+
+pub fn read(sorted: &Sorted, buf: &Buf) -> usize {
+    // Reads and shared borrows aren't writes.
+    let len = &buf.len;
+    sorted.items.len() + *len
+}
+
+pub fn write_pointee(sorted: &mut Sorted) {
+    // Writes through a reference field change the pointee rather than the field.
+    sorted.parent.push(1);
+}
@@ -0,0 +1,41 @@
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+/// Reads the value.
+///
+/// # Safety
+///
+/// The pointer is valid and aligned.
+#[rapx::inner(SP1, Align)]
+pub unsafe fn read(p: *const u8) -> u8 {
+    unsafe { *p }
+}
+
+#[rapx::inner(any(SP1, SP2), SP3)]
+unsafe fn private() {}
+
+mod inner {
+    /// # Safety
+    /// Untagged.
+    pub unsafe fn hidden() {}
+}
+
+pub struct S;
+
+impl S {
+    /// No safety section.
+    pub(crate) unsafe fn method(&self) {}
+
+    /// Shares the overlay path with the free function.
+    #[rapx::inner(SP2)]
+    pub unsafe fn read(&self) {}
+}
+
+pub unsafe trait Tr {
+    /// ## Safety
+    #[rapx::inner(SP4)]
+    unsafe fn required(&self);
+}
+
+pub fn safe() {}
@@ -0,0 +1,30 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::requires(Alias, Align(p, T), Trait(T, Copy, Alias), Size(T, 0))]
+unsafe fn read<T>(p: *const T) -> T {
+    unsafe { p.read() }
+}
+
+// `String` is not `Copy`.
+pub fn not_copy(p: *const String) -> String {
+    #[rapx::checked(Align)]
+    unsafe {
+        read(p)
+    }
+}
+
+// `Align` is still required, while `Alias` is waived.
+pub fn missing_align(p: *const u8) -> u8 {
+    unsafe { read(p) }
+}
+
+// Without the bound, the option can't be proven.
+pub fn unbounded<T>(p: *const T) -> T {
+    #[rapx::checked(Align)]
+    unsafe {
+        read(p)
+    }
+}
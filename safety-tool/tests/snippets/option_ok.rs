@@ -0,0 +1,38 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::requires(Alias, Align(p, T), Trait(T, Copy, Alias), Size(T, 0))]
+unsafe fn read<T>(p: *const T) -> T {
+    unsafe { p.read() }
+}
+
+// `Alias` is waived because `u8: Copy`.
+pub fn copy(p: *const u8) -> u8 {
+    #[rapx::checked(Align)]
+    unsafe {
+        read(p)
+    }
+}
+
+// Both tags are waived for a zero-sized `Copy` type.
+pub fn zst(p: *const ()) {
+    unsafe { read(p) }
+}
+
+// The bound on the caller proves the option.
+pub fn generic<T: Copy>(p: *const T) -> T {
+    #[rapx::checked(Align)]
+    unsafe {
+        read(p)
+    }
+}
+
+// Discharging a waived tag is not a duplicate.
+pub fn explicit(p: *const u8) -> u8 {
+    #[rapx::checked(Align, Alias)]
+    unsafe {
+        read(p)
+    }
+}
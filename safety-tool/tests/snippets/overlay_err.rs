@@ -0,0 +1,32 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+mod vendored {
+    // No tool attrs in source: tags come from the overlay.
+    pub unsafe fn copy(src: *const u8, dst: *mut u8) {
+        unsafe { *dst = *src }
+    }
+
+    pub unsafe fn take<T>(_p: *const T) -> T {
+        todo!()
+    }
+}
+
+// Tags of `core::ptr::read` come from `data/std.json`.
+pub fn read(x: &u32) -> u32 {
+    #[rapx::checked(ValidPtr)]
+    unsafe {
+        std::ptr::read(x)
+    }
+}
+
+pub fn copy(src: &u8, dst: &mut u8) {
+    unsafe { vendored::copy(src, dst) }
+}
+
+// `String` isn't `Copy`, so `Alias` isn't waived.
+pub fn take(x: &String) -> String {
+    unsafe { vendored::take(x) }
+}
@@ -0,0 +1,67 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::requires(NonNull(p), Align(p, T), Init(p, T))]
+unsafe fn read<T>(p: *const T) -> T {
+    unsafe { p.read() }
+}
+
+// `NonNull` is auto-discharged, so the explicit discharge is redundant.
+pub fn redundant(x: &u32) -> u32 {
+    let p = x as *const u32;
+    #[rapx::checked(NonNull, Init)]
+    unsafe {
+        read(p)
+    }
+}
+
+// Casting to another pointee type loses the provenance.
+pub fn cast(x: &u32) -> u8 {
+    let p = x as *const u32 as *const u8;
+    #[rapx::checked(Init)]
+    unsafe {
+        read(p)
+    }
+}
+
+#[repr(packed)]
+pub struct Packed {
+    pub a: u8,
+    pub b: u32,
+}
+
+// A field of a packed struct may be unaligned.
+pub fn packed(s: &Packed) -> u32 {
+    #[rapx::checked(Init)]
+    unsafe {
+        read(&raw const s.b)
+    }
+}
+
+// The pointer is re-pointed through a mutable borrow.
+pub fn repointed(x: &u32, q: *const u32) -> u32 {
+    let mut p = x as *const u32;
+    repoint(&mut p, q);
+    #[rapx::checked(Init)]
+    unsafe {
+        read(p)
+    }
+}
+
+fn repoint(p: &mut *const u32, q: *const u32) {
+    *p = q;
+}
+
+// The pointer is assigned more than once.
+pub fn reassigned(x: &u32, q: *const u32, c: bool) -> u32 {
+    let mut p = x as *const u32;
+    if c {
+        p = q;
+    }
+    #[rapx::checked(Init)]
+    unsafe {
+        read(p)
+    }
+}
@@ -0,0 +1,70 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+use std::ptr::NonNull;
+
+#[rapx::requires(NonNull(p), Align(p, T), Init(p, T))]
+unsafe fn read<T>(p: *const T) -> T {
+    unsafe { p.read() }
+}
+
+// `NonNull` and `Align` are auto-discharged for a pointer from a reference.
+pub fn from_ref(x: &u32) -> u32 {
+    let p = x as *const u32;
+    #[rapx::checked(Init)]
+    unsafe {
+        read(p)
+    }
+}
+
+pub fn from_local() -> u64 {
+    let x = 1;
+    #[rapx::checked(Init)]
+    unsafe {
+        read(&raw const x)
+    }
+}
+
+// `Box::into_raw` is a producer guaranteeing `NonNull` and `Align`.
+pub fn from_box(b: Box<u8>) -> u8 {
+    let p = Box::into_raw(b);
+    #[rapx::checked(Init)]
+    unsafe {
+        read(p)
+    }
+}
+
+// `NonNull::as_ptr` only guarantees `NonNull`, which is kept through the cast.
+pub fn from_non_null(p: NonNull<u16>) -> u16 {
+    #[rapx::checked(Align, Init)]
+    unsafe {
+        read(p.as_ptr() as *const u16)
+    }
+}
+
+// A raw pointer param guarantees nothing.
+pub fn from_param(p: *const u32) -> u32 {
+    #[rapx::checked(NonNull, Align, Init)]
+    unsafe {
+        read(p)
+    }
+}
+
+// The discharge is needed by one of the calls it covers, thus not redundant.
+pub fn shared(x: &u32, p: *const u32) -> u32 {
+    #[rapx::checked(NonNull, Align, Init)]
+    unsafe {
+        read(x as *const u32) + read(p)
+    }
+}
+
+// A redundant discharge is a warning, which doesn't fail the check.
+pub fn redundant(x: &u32) -> u32 {
+    let p = x as *const u32;
+    #[rapx::checked(NonNull, Init)]
+    unsafe {
+        read(p)
+    }
+}
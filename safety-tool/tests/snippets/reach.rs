@@ -0,0 +1,42 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::inner(SP1)]
+unsafe fn read<T: Copy>(p: *const T) -> T {
+    unsafe { *p }
+}
+
+fn helper(x: &u8) -> u8 {
+    #[rapx::checked(SP1)]
+    unsafe {
+        read(x)
+    }
+}
+
+pub fn entry(x: &u8, y: &u16) -> u16 {
+    let z = helper(x) as u16;
+    #[rapx::checked(SP1)]
+    unsafe {
+        read(y) + z
+    }
+}
+
+#[rapx::inner(SP2)]
+unsafe fn never_called() {}
+
+#[rapx::inner(SP3)]
+unsafe fn only_generic() {}
+
+/// Reached from a generic entry point only.
+pub fn generic<T: Copy>(x: &T) -> T {
+    #[rapx::checked(SP3)]
+    unsafe {
+        only_generic()
+    };
+    #[rapx::checked(SP1)]
+    unsafe {
+        read(x)
+    }
+}
@@ -0,0 +1,29 @@
+#![feature(stmt_expr_attributes)]
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::inner(SP1, Align(p, u8))]
+unsafe fn read(p: *const u8) {}
+
+#[rapx::inner(any(SP3, SP4))]
+unsafe fn any() {}
+
+pub fn full(p: *const u8) {
+    #[rapx::checked(SP1, Align(p, u8))]
+    unsafe { read(p) }
+}
+
+pub fn partial() {
+    #[rapx::checked(SP4)]
+    unsafe { any() }
+}
+
+pub fn missing(p: *const u8) {
+    #[rapx::checked(SP1)]
+    unsafe { read(p) }
+}
+
+/// The desc has the arg `addr < 4096` rendered, which is escaped.
+#[rapx::inner(ValidBaseAddr(addr, addr < 4096))]
+unsafe fn base(addr: usize) {}
@@ -0,0 +1,29 @@
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code, unused_variables)]
+
+struct Buf {
+    ptr: *mut u8,
+}
+
+impl Buf {
+    #[rapx::requires(Align(self.data, u8))]
+    unsafe fn first(&self) -> u8 {
+        unsafe { *self.ptr }
+    }
+}
+
+#[rapx::requires(Align(q, T))]
+unsafe fn unknown_param<T>(p: *const T) {}
+
+#[rapx::requires(Align(len, T))]
+unsafe fn not_pointer<T>(p: *const T, len: usize) {}
+
+#[rapx::requires(Align(p, U))]
+unsafe fn unknown_type<T>(p: *const T) {}
+
+#[rapx::requires(Align(p, T, len))]
+unsafe fn too_many_args<T>(p: *const T, len: usize) {}
+
+#[rapx::requires(Readable(len, len))]
+unsafe fn declared_pointer(buf: *const u8, len: usize) {}
@@ -0,0 +1,45 @@
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code, unused_variables)]
+
+use std::{alloc::Layout, ptr::NonNull};
+
+struct Buf {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Buf {
+    #[rapx::requires(Align(self.ptr, u8), ValidPtr(self.ptr, u8, self.len))]
+    unsafe fn first(&self) -> u8 {
+        unsafe { *self.ptr }
+    }
+
+    #[rapx::requires(Align(p, Self))]
+    unsafe fn read(p: *const Self) -> Self {
+        unsafe { p.read() }
+    }
+}
+
+// `T` is omitted, and inferred from `p`.
+#[rapx::requires(Align(p), ValidPtr(p, T, len))]
+unsafe fn read<T>(p: *const T, len: usize) -> T {
+    unsafe { p.read() }
+}
+
+#[rapx::requires(Align(dst, Buf))]
+unsafe fn write(dst: NonNull<Buf>, buf: Buf) {
+    unsafe { dst.write(buf) }
+}
+
+// `Layout` is imported by `use` in the module.
+#[rapx::requires(Align(p, Layout))]
+unsafe fn read_layout(p: *const u8) -> Layout {
+    unsafe { p.cast::<Layout>().read() }
+}
+
+#[rapx::requires(Readable(buf, len))]
+unsafe fn declared_pointer(buf: *const u8, len: usize) {}
+
+#[rapx::requires(Positive(p))]
+unsafe fn value_named_p(p: i32) {}
@@ -0,0 +1,38 @@
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::ensures(SP1)]
+pub unsafe trait Trait {
+    #[rapx::ensures(SP2)]
+    fn method(&self);
+
+    // Not checked: default methods that aren't overridden in impls.
+    #[rapx::ensures(SP2)]
+    fn default_method(&self) {}
+}
+
+pub trait SafeTrait {
+    // Not checked: unsafe methods in safe traits.
+    #[rapx::ensures(SP2)]
+    unsafe fn method(&self);
+}
+
+struct Unchecked;
+
+impl SafeTrait for Unchecked {
+    unsafe fn method(&self) {}
+}
+
+struct NoDischarge;
+
+unsafe impl Trait for NoDischarge {
+    fn method(&self) {}
+}
+
+struct PartialDischarge;
+
+#[rapx::checked(SP1)]
+unsafe impl Trait for PartialDischarge {
+    fn method(&self) {}
+}
@@ -0,0 +1,19 @@
+#![feature(register_tool)]
+#![register_tool(rapx)]
+#![allow(dead_code)]
+
+#[rapx::ensures(SP1)]
+pub unsafe trait Trait {
+    #[rapx::ensures(SP2)]
+    fn method(&self);
+
+    fn untagged(&self) {}
+}
+
+struct Discharge;
+
+#[rapx::checked(SP1: "reason")]
+unsafe impl Trait for Discharge {
+    #[rapx::checked(SP2)]
+    fn method(&self) {}
+}
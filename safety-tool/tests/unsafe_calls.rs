@@ -43,6 +43,11 @@ fn compile(file: &str, opts: CompilationOptions) -> (&'static str, std::process:
     cmd.arg(file).args(opts.args).env("LD_LIBRARY_PATH", &*LD_LIBRARY_PATH);
 
     cmd.envs(opts.envs.iter().copied());
+    // A database shared through the env, e.g. by run.sh, would hide how tags are loaded from
+    // sidecars of dependencies.
+    if !opts.envs.iter().any(|(key, _)| *key == "DATA_SQLITE3") {
+        cmd.env_remove("DATA_SQLITE3");
+    }
 
     if opts.stop {
         cmd.env(STOP_COMPILATION, "1");
@@ -153,6 +158,216 @@ fn any_ok_2() {
     fine(file, outfile, Default::default());
 }
 
+#[test]
+fn unsafe_impl_err() {
+    let [file, outfile] = &testcase("unsafe_impl_err");
+    should_panic(file, outfile, Default::default());
+}
+
+#[test]
+fn unsafe_impl_ok() {
+    let [file, outfile] = &testcase("unsafe_impl_ok");
+    fine(file, outfile, Default::default());
+}
+
+#[test]
+fn invariant_err() {
+    let [file, outfile] = &testcase("invariant_err");
+    should_panic(file, outfile, Default::default());
+}
+
+#[test]
+fn invariant_ok() {
+    let [file, outfile] = &testcase("invariant_ok");
+    fine(file, outfile, Default::default());
+}
+
+#[test]
+fn entity_ref_err() {
+    let [file, outfile] = &testcase("entity_ref_err");
+    should_panic(file, outfile, Default::default());
+}
+
+#[test]
+fn entity_ref_ok() {
+    let [file, outfile] = &testcase("entity_ref_ok");
+    fine(file, outfile, Default::default());
+}
+
+#[test]
+fn entity_ref_impacted() {
+    let dir = "target/entity_ref";
+    _ = std::fs::remove_dir_all(dir);
+    let opts = CompilationOptions {
+        args: &["--crate-type=lib", "--crate-name=entity_ref"],
+        envs: &[("SP_FILE", "tests/snippets/sp.toml"), ("SP_OUT_DIR", dir)],
+        stop: true,
+    };
+    let compile_ok = |file: &str| {
+        let (exe, output) = compile(file, opts);
+        let stderr = std::str::from_utf8(&output.stderr).unwrap();
+        assert!(output.status.success(), "`{exe} {file}` failed:\n{stderr}");
+    };
+    let impacted = format!("{dir}/entity_ref.impacted.json");
+
+    // Nothing is impacted without a previous run.
+    compile_ok("./tests/snippets/entity_ref_ok.rs");
+    assert!(!Path::new(&impacted).exists());
+    let refs = std::fs::read_to_string(format!("{dir}/entity_ref.refs.json")).unwrap();
+
+    // The referencing site changes.
+    let [file, outfile] = &testcase("entity_ref_impacted");
+    compile_ok(file);
+    let impacted_json = std::fs::read_to_string(&impacted).unwrap();
+
+    // Nothing changes since the previous run.
+    compile_ok(file);
+    assert!(!Path::new(&impacted).exists());
+
+    let out = format!("refs.json=\n{refs}\nimpacted.json=\n{impacted_json}");
+    expect_file![outfile].assert_eq(&strip_current_path(&out));
+}
+
+/// Call-order tags are defined in a separate spec, thus the database is separated too,
+/// otherwise tags from other snippets are not defined in the spec.
+const CALL_ORDER: CompilationOptions = CompilationOptions {
+    args: &["--crate-type=lib"],
+    envs: &[
+        ("SP_FILE", "tests/snippets/sp-call-order.toml"),
+        ("DATA_SQLITE3", "target/call_order.sqlite3"),
+    ],
+    stop: true,
+};
+
+#[test]
+fn call_order_err() {
+    let [file, outfile] = &testcase("call_order_err");
+    should_panic(file, outfile, CALL_ORDER);
+}
+
+#[test]
+fn call_order_ok() {
+    let [file, outfile] = &testcase("call_order_ok");
+    fine(file, outfile, CALL_ORDER);
+}
+
+const HAZARD: CompilationOptions = CompilationOptions {
+    args: &["--crate-type=lib"],
+    envs: &[
+        ("SP_FILE", "tests/snippets/sp-hazard.toml"),
+        ("DATA_SQLITE3", "target/hazard.sqlite3"),
+    ],
+    stop: true,
+};
+
+#[test]
+fn hazard_err() {
+    let [file, outfile] = &testcase("hazard_err");
+    should_panic(file, outfile, HAZARD);
+}
+
+#[test]
+fn hazard_ok() {
+    let [file, outfile] = &testcase("hazard_ok");
+    fine(file, outfile, HAZARD);
+}
+
+#[test]
+fn hazard_unwind_err() {
+    let [file, outfile] = &testcase("hazard_unwind_err");
+    should_panic(file, outfile, HAZARD);
+}
+
+#[test]
+fn hazard_unwind_ok() {
+    let [file, outfile] = &testcase("hazard_unwind_ok");
+    fine(file, outfile, HAZARD);
+}
+
+const OPTION: CompilationOptions = CompilationOptions {
+    args: &["--crate-type=lib"],
+    envs: &[
+        ("SP_FILE", "tests/snippets/sp-option.toml"),
+        ("DATA_SQLITE3", "target/option.sqlite3"),
+    ],
+    stop: true,
+};
+
+#[test]
+fn option_err() {
+    let [file, outfile] = &testcase("option_err");
+    should_panic(file, outfile, OPTION);
+}
+
+#[test]
+fn option_ok() {
+    let [file, outfile] = &testcase("option_ok");
+    fine(file, outfile, OPTION);
+}
+
+const TAG_ARGS: CompilationOptions = CompilationOptions {
+    args: &["--crate-type=lib"],
+    envs: &[
+        ("SP_FILE", "tests/snippets/sp-tag-args.toml"),
+        ("DATA_SQLITE3", "target/tag_args.sqlite3"),
+    ],
+    stop: true,
+};
+
+#[test]
+fn tag_args_err() {
+    let [file, outfile] = &testcase("tag_args_err");
+    should_panic(file, outfile, TAG_ARGS);
+}
+
+#[test]
+fn tag_args_ok() {
+    let [file, outfile] = &testcase("tag_args_ok");
+    fine(file, outfile, TAG_ARGS);
+}
+
+const PROVENANCE: CompilationOptions = CompilationOptions {
+    args: &["--crate-type=lib"],
+    envs: &[
+        ("SP_FILE", "tests/snippets/sp-provenance.toml"),
+        ("DATA_SQLITE3", "target/provenance.sqlite3"),
+    ],
+    stop: true,
+};
+
+#[test]
+fn provenance_err() {
+    let [file, outfile] = &testcase("provenance_err");
+    should_panic(file, outfile, PROVENANCE);
+}
+
+#[test]
+fn provenance_ok() {
+    let [file, outfile] = &testcase("provenance_ok");
+    fine(file, outfile, PROVENANCE);
+}
+
+const CONST_EVAL: CompilationOptions = CompilationOptions {
+    args: &["--crate-type=lib"],
+    envs: &[
+        ("SP_FILE", "tests/snippets/sp-const-eval.toml"),
+        ("DATA_SQLITE3", "target/const_eval.sqlite3"),
+    ],
+    stop: true,
+};
+
+#[test]
+fn const_eval_err() {
+    let [file, outfile] = &testcase("const_eval_err");
+    should_panic(file, outfile, CONST_EVAL);
+}
+
+#[test]
+fn const_eval_ok() {
+    let [file, outfile] = &testcase("const_eval_ok");
+    fine(file, outfile, CONST_EVAL);
+}
+
 fn fine(file: &str, outfile: &str, opts: CompilationOptions) {
     let (exe, output) = compile(file, opts);
     let stdout = std::str::from_utf8(&output.stdout).unwrap();
@@ -197,6 +412,8 @@ fn compile_libunsafe_calls() -> CompilationOptions<'static> {
                 ..Default::default()
             },
         );
+        // Tags are loaded by downstream crates from the sidecar next to the rlib.
+        assert!(Path::new("target/libunsafe_calls.safety.json").exists());
         CompilationOptions {
             args: &["--crate-type=lib", "--extern=unsafe_calls=target/libunsafe_calls.rlib"],
             stop: false,
@@ -213,3 +430,340 @@ fn unsafe_calls_panic_with_dep() {
     let [file, outfile] = &testcase("unsafe_calls_panic_with_dep");
     should_panic(file, outfile, opts);
 }
+
+#[test]
+fn unsafe_calls_without_sidecar() {
+    compile_libunsafe_calls();
+    // The same rlib without its sidecar, so tags of the dependency are unknown.
+    std::fs::create_dir_all("target/no_sidecar").unwrap();
+    std::fs::copy("target/libunsafe_calls.rlib", "target/no_sidecar/libunsafe_calls.rlib").unwrap();
+    let opts = CompilationOptions {
+        args: &["--crate-type=lib", "--extern=unsafe_calls=target/no_sidecar/libunsafe_calls.rlib"],
+        stop: false,
+        ..Default::default()
+    };
+
+    let [file, _] = &testcase("unsafe_calls_panic_with_dep");
+    fine(file, "snapshots/unsafe_calls_without_sidecar.txt", opts);
+}
+
+const OVERLAY: CompilationOptions = CompilationOptions {
+    args: &["--crate-type=lib"],
+    envs: &[
+        ("SP_FILE", "tests/snippets/sp-overlay.toml"),
+        ("DATA_SQLITE3", "target/overlay.sqlite3"),
+    ],
+    stop: true,
+};
+
+#[test]
+fn overlay_err() {
+    let [file, outfile] = &testcase("overlay_err");
+    should_panic(file, outfile, OVERLAY);
+}
+
+#[test]
+fn overlay_ok() {
+    let [file, outfile] = &testcase("overlay_ok");
+    fine(file, outfile, OVERLAY);
+}
+
+#[test]
+fn apply_tags() {
+    // The snippet is copied, since it's rewritten in place.
+    let file = "target/apply_tags.rs";
+    std::fs::copy("tests/snippets/apply_tags.rs", file).unwrap();
+    let opts = CompilationOptions {
+        args: &["--crate-type=lib"],
+        envs: &[
+            ("SP_FILE", "tests/snippets/sp-overlay.toml"),
+            ("SP_APPLY", "tests/snippets/apply_tags.json"),
+            ("CARGO_PRIMARY_PACKAGE", "1"),
+        ],
+        stop: true,
+    };
+
+    let (exe, output) = compile(file, opts);
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(output.status.success(), "`{exe} {file}` failed:\n{stderr}");
+    let src = std::fs::read_to_string(file).unwrap();
+    let out = format!("stderr=\n{stderr}\nsource=\n{src}");
+    expect_file!["snapshots/apply_tags.txt"].assert_eq(&strip_current_path(&out));
+
+    // Applying again changes nothing.
+    let (_, output) = compile(file, opts);
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(file).unwrap(), src);
+}
+
+#[test]
+fn inventory() {
+    let dir = "target/inventory";
+    _ = std::fs::remove_dir_all(dir);
+    let [file, outfile] = &testcase("inventory");
+    let opts = CompilationOptions {
+        args: &["--crate-type=lib", "--crate-name=inventory"],
+        envs: &[("SP_FILE", "tests/snippets/sp.toml"), ("SP_OUT_DIR", dir)],
+        stop: true,
+    };
+    let (exe, output) = compile(file, opts);
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(output.status.success(), "`{exe} {file}` failed:\n{stderr}");
+
+    let json = std::fs::read_to_string(format!("{dir}/inventory.inventory.json")).unwrap();
+    let md = std::fs::read_to_string(format!("{dir}/inventory.inventory.md")).unwrap();
+    let out = format!("json=\n{json}\nmarkdown=\n{md}");
+    expect_file![outfile].assert_eq(&strip_current_path(&out));
+}
+
+#[test]
+fn audit() {
+    let dir = "target/audit";
+    _ = std::fs::remove_dir_all(dir);
+    let [file, outfile] = &testcase("audit");
+    let opts = CompilationOptions {
+        args: &["--crate-type=lib", "--crate-name=audit"],
+        envs: &[
+            ("SP_FILE", "tests/snippets/sp.toml"),
+            ("SP_OUT_DIR", dir),
+            // Calls forwarded to undeclared tags are also errors.
+            ("EXIT_AND_EMIT", "silence_and_no_emit"),
+        ],
+        stop: true,
+    };
+    let (exe, output) = compile(file, opts);
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(output.status.success(), "`{exe} {file}` failed:\n{stderr}");
+
+    let json = std::fs::read_to_string(format!("{dir}/audit.audit.json")).unwrap();
+    let md = std::fs::read_to_string(format!("{dir}/audit.audit.md")).unwrap();
+    let out = format!("json=\n{json}\nmarkdown=\n{md}");
+    expect_file![outfile].assert_eq(&strip_current_path(&out));
+}
+
+#[test]
+fn boundary() {
+    let dir = "target/boundary";
+    _ = std::fs::remove_dir_all(dir);
+    let [file, outfile] = &testcase("boundary");
+    let opts = CompilationOptions {
+        args: &["--crate-type=lib", "--crate-name=boundary"],
+        envs: &[
+            ("SP_FILE", "tests/snippets/sp.toml"),
+            ("SP_OUT_DIR", dir),
+            ("EXIT_AND_EMIT", "silence_and_no_emit"),
+        ],
+        stop: true,
+    };
+    let (exe, output) = compile(file, opts);
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(output.status.success(), "`{exe} {file}` failed:\n{stderr}");
+
+    let json = std::fs::read_to_string(format!("{dir}/boundary.boundary.json")).unwrap();
+    let tree = std::fs::read_to_string(format!("{dir}/boundary.boundary.txt")).unwrap();
+    let out = format!("json=\n{json}\ntree=\n{tree}");
+    expect_file![outfile].assert_eq(&strip_current_path(&out));
+}
+
+#[test]
+fn reach() {
+    let dir = "target/reach";
+    _ = std::fs::remove_dir_all(dir);
+    let [file, outfile] = &testcase("reach");
+    let opts = CompilationOptions {
+        args: &["--crate-type=lib", "--crate-name=reach"],
+        envs: &[("SP_FILE", "tests/snippets/sp.toml"), ("SP_OUT_DIR", dir)],
+        stop: true,
+    };
+    let (exe, output) = compile(file, opts);
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(output.status.success(), "`{exe} {file}` failed:\n{stderr}");
+
+    let json = std::fs::read_to_string(format!("{dir}/reach.reach.json")).unwrap();
+    expect_file![outfile].assert_eq(&strip_current_path(&json));
+}
+
+#[test]
+fn aggregate() {
+    let dir = "target/aggregate";
+    _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir(dir).unwrap();
+    let compile_ok = |file: &str, opts: CompilationOptions| {
+        let (exe, output) = compile(file, opts);
+        let stderr = std::str::from_utf8(&output.stderr).unwrap();
+        assert!(output.status.success(), "`{exe} {file}` failed:\n{stderr}");
+    };
+
+    let dep = "tests/snippets/aggregate_dep.rs";
+    let rlib = "-otarget/aggregate/libaggregate_dep.rlib";
+    let sp_file = ("SP_FILE", "tests/snippets/sp.toml");
+    let out = ("SP_OUT_DIR", "target/aggregate/out");
+    let version = ("CARGO_PKG_VERSION", "0.1.0");
+    compile_ok(
+        dep,
+        CompilationOptions {
+            args: &["--crate-type=lib", rlib],
+            envs: &[sp_file, out, version],
+            stop: false,
+        },
+    );
+    let args =
+        &["--crate-type=lib", "--extern=aggregate_dep=target/aggregate/libaggregate_dep.rlib"];
+    compile_ok(
+        "tests/snippets/aggregate.rs",
+        CompilationOptions { args, envs: &[sp_file, out, version], stop: true },
+    );
+    // Another version of the dep in another dir.
+    let envs = &[sp_file, ("SP_OUT_DIR", "target/aggregate/out2"), ("CARGO_PKG_VERSION", "0.2.0")];
+    compile_ok(dep, CompilationOptions { args: &["--crate-type=lib"], envs, stop: true });
+
+    let output = Command::cargo_bin("cargo-safety-tool")
+        .unwrap()
+        .args(["safety-tool", "aggregate", "target/aggregate/out", "target/aggregate/out2"])
+        .output()
+        .unwrap();
+    let stdout = std::str::from_utf8(&output.stdout).unwrap();
+    assert!(output.status.success(), "Failed to aggregate:\n{stdout}");
+
+    let json = std::fs::read_to_string(format!("{dir}/out/workspace.aggregate.json")).unwrap();
+    let tree = std::fs::read_to_string(format!("{dir}/out/workspace.aggregate.txt")).unwrap();
+    let out = format!("stdout=\n{stdout}\njson=\n{json}\ntree=\n{tree}");
+    expect_file!["snapshots/aggregate.txt"].assert_eq(&strip_current_path(&out));
+}
+
+#[test]
+fn graph() {
+    let dir = "target/graph";
+    _ = std::fs::remove_dir_all(dir);
+    let [file, outfile] = &testcase("graph");
+    let opts = CompilationOptions {
+        args: &["--crate-type=lib", "--crate-name=graph"],
+        envs: &[
+            ("SP_FILE", "tests/snippets/sp.toml"),
+            ("SP_OUT_DIR", dir),
+            ("EXIT_AND_EMIT", "silence_and_no_emit"),
+        ],
+        stop: true,
+    };
+    let (exe, output) = compile(file, opts);
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(output.status.success(), "`{exe} {file}` failed:\n{stderr}");
+
+    let dot = std::fs::read_to_string(format!("{dir}/graph.dot")).unwrap();
+    let mermaid = std::fs::read_to_string(format!("{dir}/graph.mmd")).unwrap();
+    let focus = |args: &[&str]| {
+        let output = Command::cargo_bin("cargo-safety-tool")
+            .unwrap()
+            .args(["safety-tool", "graph", "target/graph/graph.json"])
+            .args(args)
+            .output()
+            .unwrap();
+        let stderr = std::str::from_utf8(&output.stderr).unwrap();
+        assert!(output.status.success(), "Failed to render graph with {args:?}:\n{stderr}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let tag = focus(&["mermaid", "--tag", "SP2"]);
+    let root = focus(&["dot", "--root", "forward"]);
+    let out = format!("dot=\n{dot}\nmermaid=\n{mermaid}\ntag=\n{tag}\nroot=\n{root}");
+    expect_file![outfile].assert_eq(&strip_current_path(&out));
+}
+
+#[test]
+fn report() {
+    let dir = "target/report";
+    _ = std::fs::remove_dir_all(dir);
+    let [file, outfile] = &testcase("report");
+    let opts = CompilationOptions {
+        args: &["--crate-type=lib", "--crate-name=report"],
+        envs: &[
+            ("SP_FILE", "tests/snippets/sp.toml"),
+            ("SP_OUT_DIR", dir),
+            ("EXIT_AND_EMIT", "silence_and_no_emit"),
+            ("CARGO_PKG_VERSION", "0.1.0"),
+        ],
+        stop: true,
+    };
+    let (exe, output) = compile(file, opts);
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(output.status.success(), "`{exe} {file}` failed:\n{stderr}");
+
+    let output = Command::cargo_bin("cargo-safety-tool")
+        .unwrap()
+        .args(["safety-tool", "report", "target/report/report.json"])
+        .output()
+        .unwrap();
+    let stdout = std::str::from_utf8(&output.stdout).unwrap();
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(output.status.success(), "Failed to report:\n{stderr}");
+
+    let html = std::fs::read_to_string(format!("{dir}/report.html")).unwrap();
+    let out = format!("stdout=\n{stdout}\nhtml=\n{html}");
+    expect_file![outfile].assert_eq(&strip_current_path(&out));
+}
+
+#[test]
+fn diff() {
+    // Discharge coverage regresses.
+    diff_runs("diff", 1);
+}
+
+/// Discharges of tags no longer declared by the callee are not lost.
+#[test]
+fn diff_dropped() {
+    diff_runs("diff_dropped", 0);
+}
+
+/// Compare runs on `tests/snippets/<name>_{old,new}.rs`, and check the exit code.
+fn diff_runs(name: &str, code: i32) {
+    let dir = &format!("target/{name}");
+    _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir(dir).unwrap();
+    for run in ["old", "new"] {
+        let file = &format!("tests/snippets/{name}_{run}.rs");
+        let out_dir = &format!("{dir}/{run}");
+        let opts = CompilationOptions {
+            args: &["--crate-type=lib", "--crate-name=diff"],
+            envs: &[
+                ("SP_FILE", "tests/snippets/sp.toml"),
+                ("SP_OUT_DIR", out_dir),
+                ("EXIT_AND_EMIT", "silence_and_no_emit"),
+            ],
+            stop: true,
+        };
+        let (exe, output) = compile(file, opts);
+        let stderr = std::str::from_utf8(&output.stderr).unwrap();
+        assert!(output.status.success(), "`{exe} {file}` failed:\n{stderr}");
+    }
+
+    let diff = |format: &str| {
+        let output = Command::cargo_bin("cargo-safety-tool")
+            .unwrap()
+            .args(["safety-tool", "diff", &format!("{dir}/old"), &format!("{dir}/new"), format])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(code));
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let out = format!("markdown=\n{}\njson=\n{}", diff("markdown"), diff("json"));
+    expect_file![format!("snapshots/{name}.txt")].assert_eq(&strip_current_path(&out));
+}
+
+/// When compilation stops after analysis, MIR inlining at high opt-levels must not hide calls
+/// from analyses on MIR, so results are the same as unoptimized ones.
+#[test]
+fn optimized() {
+    const ARGS: &[&str] = &["--crate-type=lib", "-Copt-level=3"];
+    let cases = [
+        ("call_order", CALL_ORDER),
+        ("hazard", HAZARD),
+        ("provenance", PROVENANCE),
+        ("const_eval", CONST_EVAL),
+    ];
+    for (name, opts) in cases {
+        let opts = CompilationOptions { args: ARGS, ..opts };
+        let [file, outfile] = &testcase(&format!("{name}_err"));
+        should_panic(file, outfile, opts);
+        let [file, outfile] = &testcase(&format!("{name}_ok"));
+        fine(file, outfile, opts);
+    }
+}